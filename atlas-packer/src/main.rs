@@ -0,0 +1,80 @@
+//! Packs every PNG in a folder into one texture and writes the metadata `game_core::load_atlas`
+//! reads next to it. Sprites are named after their files, `player.png` becomes `player`.
+//!
+//! `atlas-packer assets/sprites assets/atlas.png` writes `assets/atlas.png` and
+//! `assets/atlas.txt`.
+
+extern crate game_core;
+extern crate rasterizer;
+
+use game_core::sprite::Atlas;
+use rasterizer::image::{load_png, save_png, Rgba8};
+use std::{env, fs, path::Path, process};
+
+mod pack;
+
+fn run(input_dir: &str, output: &str,) -> Result<(), String,> {
+    let mut files: Vec<_,> = fs::read_dir(input_dir,)
+        .map_err(|e| format!("{}: {}", input_dir, e),)?
+        .filter_map(|entry| entry.ok(),)
+        .map(|entry| entry.path(),)
+        .filter(|path| path.extension().map_or(false, |e| e == "png",),)
+        .collect();
+    // sorted so the same folder always gives the same atlas
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no png files in {}", input_dir),);
+    }
+
+    let mut images = Vec::new();
+    for path in &files {
+        let image = load_png(&path.to_string_lossy(),).map_err(|e| e.to_string(),)?;
+        images.push(image,);
+    }
+    let sizes: Vec<_,> = images.iter().map(|i| (i.width, i.height,),).collect();
+    let (width, height, placed,) = pack::pack(&sizes,);
+
+    let mut atlas_image = Rgba8 {
+        width,
+        height,
+        pixels: vec![0; (width * height * 4) as usize],
+    };
+    let texture_name = Path::new(output,).file_name().unwrap().to_string_lossy().to_string();
+    let mut atlas = Atlas::new(&texture_name, width, height,);
+    for ((path, image,), rect,) in files.iter().zip(images.iter(),).zip(placed.iter(),) {
+        for row in 0..image.height {
+            let from = (row * image.width * 4) as usize;
+            let to = (((rect.y + row) * width + rect.x) * 4) as usize;
+            let length = (image.width * 4) as usize;
+            atlas_image.pixels[to..to + length]
+                .copy_from_slice(&image.pixels[from..from + length],);
+        }
+        let name = path.file_stem().unwrap().to_string_lossy();
+        atlas.add(&name, *rect,);
+    }
+
+    save_png(output, &atlas_image,).map_err(|e| e.to_string(),)?;
+    let metadata_path = Path::new(output,).with_extension("txt",);
+    fs::write(&metadata_path, atlas.to_config_string(),)
+        .map_err(|e| format!("{}: {}", metadata_path.display(), e),)?;
+    println!(
+        "Packed {} sprites into {}x{} {}",
+        files.len(),
+        width,
+        height,
+        output
+    );
+    Ok((),)
+}
+
+fn main() {
+    let args: Vec<String,> = env::args().collect();
+    if args.len() != 3 {
+        println!("usage: atlas-packer <folder with pngs> <output.png>");
+        process::exit(1,);
+    }
+    if let Err(e,) = run(&args[1], &args[2],) {
+        println!("{}", e);
+        process::exit(1,);
+    }
+}
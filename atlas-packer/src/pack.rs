@@ -0,0 +1,65 @@
+use game_core::sprite::SourceRect;
+
+/// Empty pixels around each image, so filtering never picks up a neighbour.
+pub const PADDING: u32 = 1;
+
+/// Places rectangles of the given sizes on shelves, tallest first. Returns the texture size,
+/// a power of two each way, and where each input ended up, in input order.
+pub fn pack(sizes: &[(u32, u32,)]) -> (u32, u32, Vec<SourceRect,>,) {
+    let area: u32 = sizes
+        .iter()
+        .map(|(w, h,)| (w + PADDING * 2) * (h + PADDING * 2),)
+        .sum();
+    let widest = sizes.iter().map(|(w, _,)| w + PADDING * 2,).max().unwrap_or(1,);
+    let width = ((area as f32).sqrt().ceil() as u32).max(widest,).next_power_of_two();
+
+    let mut order: Vec<usize,> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1,).then(a.cmp(b,),),);
+
+    let mut placed = vec![
+        SourceRect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+        sizes.len()
+    ];
+    let (mut x, mut y, mut shelf_height,) = (0, 0, 0,);
+    for index in order {
+        let (w, h,) = sizes[index];
+        if x + w + PADDING * 2 > width {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        placed[index] = SourceRect {
+            x: x + PADDING,
+            y: y + PADDING,
+            width: w,
+            height: h,
+        };
+        x += w + PADDING * 2;
+        shelf_height = shelf_height.max(h + PADDING * 2,);
+    }
+    let height = (y + shelf_height).max(1,).next_power_of_two();
+    (width, height, placed,)
+}
+
+#[test]
+fn test_pack_does_not_overlap() {
+    let sizes = [(16, 16,), (4, 4,), (30, 8,), (16, 16,), (1, 40,), (10, 3,)];
+    let (width, height, placed,) = pack(&sizes,);
+    assert!(width.is_power_of_two() && height.is_power_of_two());
+    for (i, a,) in placed.iter().enumerate() {
+        assert_eq!((a.width, a.height,), sizes[i]);
+        assert!(a.x + a.width <= width && a.y + a.height <= height);
+        for b in &placed[i + 1..] {
+            let apart = a.x + a.width + PADDING <= b.x
+                || b.x + b.width + PADDING <= a.x
+                || a.y + a.height + PADDING <= b.y
+                || b.y + b.height + PADDING <= a.y;
+            assert!(apart, "{:?} and {:?} overlap", a, b);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! How the simulation scales with the number of enemies and bullets, see `game_core::bench`.
+//! `cargo bench -p game-core --features benchmarks`, reports end up in `target/criterion`.
+//! Sizes are the number of enemies, the throughput counts every entity actually in the game.
+
+#[macro_use]
+extern crate criterion;
+extern crate game_core;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput};
+use game_core::bench::{Scene, SIZES};
+use std::hint::black_box;
+
+/// One frame of `GameState::simulate`, what `update` does before drawing.
+fn simulate(c: &mut Criterion,) {
+    let mut group = c.benchmark_group("simulate",);
+    group.sample_size(10,);
+    for &size in SIZES.iter() {
+        let scene = Scene::crowded(size,);
+        group.throughput(Throughput::Elements(scene.entities() as u64,),);
+        group.bench_with_input(BenchmarkId::from_parameter(size,), &scene, |b, scene| {
+            b.iter_batched_ref(|| scene.clone(), |s| s.simulate(), BatchSize::LargeInput,)
+        },);
+    }
+    group.finish();
+}
+
+fn collisions(c: &mut Criterion,) {
+    let mut group = c.benchmark_group("collisions",);
+    group.sample_size(10,);
+    for &size in SIZES.iter() {
+        let scene = Scene::crowded(size,);
+        group.throughput(Throughput::Elements(scene.entities() as u64,),);
+        group.bench_with_input(BenchmarkId::from_parameter(size,), &scene, |b, scene| {
+            b.iter_batched_ref(|| scene.clone(), |s| s.check_collisions(), BatchSize::LargeInput,)
+        },);
+    }
+    group.finish();
+}
+
+/// Pushing `size` enemies at once into a game that has none yet.
+fn insert_enemies(c: &mut Criterion,) {
+    let mut group = c.benchmark_group("insert_enemies",);
+    for &size in SIZES.iter() {
+        group.throughput(Throughput::Elements(size as u64,),);
+        group.bench_with_input(BenchmarkId::from_parameter(size,), &size, |b, &size| {
+            b.iter_batched_ref(Scene::empty, |s| s.insert_enemies(size,), BatchSize::LargeInput,)
+        },);
+    }
+    group.finish();
+}
+
+fn draw_list(c: &mut Criterion,) {
+    let mut group = c.benchmark_group("draw_list",);
+    for &size in SIZES.iter() {
+        let scene = Scene::crowded(size,);
+        group.throughput(Throughput::Elements(scene.entities() as u64,),);
+        group.bench_with_input(BenchmarkId::from_parameter(size,), &scene, |b, scene| {
+            b.iter(|| black_box(scene.draw_list(),),)
+        },);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, simulate, collisions, insert_enemies, draw_list);
+criterion_main!(benches);
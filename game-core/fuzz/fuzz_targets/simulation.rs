@@ -0,0 +1,12 @@
+//! Plays games made from the fuzzer's bytes, see `game_core::fuzz::run_bytes`. Panics when an
+//! invariant breaks, so libFuzzer keeps the input.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate game_core;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(e,) = game_core::fuzz::run_bytes(data,) {
+        panic!("{}", e);
+    }
+});
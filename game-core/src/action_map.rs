@@ -0,0 +1,468 @@
+use std::{fmt, fs};
+use GameInput;
+
+/// Platform independent key identifiers. Frontends translate their own key codes into these.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    Escape,
+    Enter,
+    Tab,
+    Shift,
+    Control,
+}
+
+pub const KEY_COUNT: usize = 46;
+
+pub const ALL_KEYS: [Key; KEY_COUNT] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::Up,
+    Key::Down,
+    Key::Left,
+    Key::Right,
+    Key::Space,
+    Key::Escape,
+    Key::Enter,
+    Key::Tab,
+    Key::Shift,
+    Key::Control,
+];
+
+const KEY_NAMES: [&str; KEY_COUNT] = [
+    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s",
+    "t", "u", "v", "w", "x", "y", "z", "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "up",
+    "down", "left", "right", "space", "escape", "enter", "tab", "shift", "control",
+];
+
+impl Key {
+    pub fn name(&self) -> &'static str {
+        KEY_NAMES[*self as usize]
+    }
+
+    /// Inverse of `name`, case insensitive.
+    pub fn from_name(name: &str) -> Option<Key,> {
+        let name = name.trim().to_lowercase();
+        KEY_NAMES
+            .iter()
+            .position(|n| *n == name,)
+            .map(|i| ALL_KEYS[i],)
+    }
+}
+
+/// Which keys are held down this frame, a bit per key.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct KeyState {
+    pressed: u64,
+}
+
+impl KeyState {
+    pub fn new() -> KeyState {
+        KeyState::default()
+    }
+
+    pub fn set(&mut self, key: Key, pressed: bool,) {
+        if pressed {
+            self.pressed |= 1 << key as usize;
+        } else {
+            self.pressed &= !(1 << key as usize);
+        }
+    }
+
+    pub fn is_pressed(&self, key: Key,) -> bool {
+        self.pressed & (1 << key as usize) != 0
+    }
+}
+
+/// Things the game reacts to, each one maps to a flag in `GameInput`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    ShootUp,
+    ShootDown,
+    ShootLeft,
+    ShootRight,
+    Boost,
+    Quit,
+    ToggleDebug,
+}
+
+pub const ACTION_COUNT: usize = 11;
+
+pub const ALL_ACTIONS: [Action; ACTION_COUNT] = [
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::ShootUp,
+    Action::ShootDown,
+    Action::ShootLeft,
+    Action::ShootRight,
+    Action::Boost,
+    Action::Quit,
+    Action::ToggleDebug,
+];
+
+const ACTION_NAMES: [&str; ACTION_COUNT] = [
+    "move_up",
+    "move_down",
+    "move_left",
+    "move_right",
+    "shoot_up",
+    "shoot_down",
+    "shoot_left",
+    "shoot_right",
+    "boost",
+    "quit",
+    "toggle_debug",
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        ACTION_NAMES[*self as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Action,> {
+        let name = name.trim().to_lowercase();
+        ACTION_NAMES
+            .iter()
+            .position(|n| *n == name,)
+            .map(|i| ALL_ACTIONS[i],)
+    }
+
+    fn flag<'a,>(&self, input: &'a mut GameInput,) -> &'a mut bool {
+        match *self {
+            Action::MoveUp => &mut input.up_key,
+            Action::MoveDown => &mut input.down_key,
+            Action::MoveLeft => &mut input.left_key,
+            Action::MoveRight => &mut input.right_key,
+            Action::ShootUp => &mut input.shoot_up,
+            Action::ShootDown => &mut input.shoot_down,
+            Action::ShootLeft => &mut input.shoot_left,
+            Action::ShootRight => &mut input.shoot_right,
+            Action::Boost => &mut input.space,
+            Action::Quit => &mut input.quit_key,
+            Action::ToggleDebug => &mut input.debug_key,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ActionMapError {
+    Io(String,),
+    Parse { line: usize, message: String, },
+}
+
+impl fmt::Display for ActionMapError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match self {
+            ActionMapError::Io(message,) => write!(f, "could not read bindings: {}", message),
+            ActionMapError::Parse { line, message, } => {
+                write!(f, "bindings line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+/// Maps keys to actions. An action can have any number of keys bound to it.
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct ActionMap {
+    bindings: Vec<Vec<Key,>,>,
+}
+
+impl ActionMap {
+    /// A map with no bindings at all.
+    pub fn empty() -> ActionMap {
+        ActionMap {
+            bindings: vec![Vec::new(); ACTION_COUNT],
+        }
+    }
+
+    /// The bindings the game has always shipped with.
+    pub fn default_bindings() -> ActionMap {
+        let mut map = ActionMap::empty();
+        map.bind(Action::MoveUp, Key::W,);
+        map.bind(Action::MoveDown, Key::S,);
+        map.bind(Action::MoveLeft, Key::A,);
+        map.bind(Action::MoveRight, Key::D,);
+        map.bind(Action::ShootUp, Key::I,);
+        map.bind(Action::ShootDown, Key::K,);
+        map.bind(Action::ShootLeft, Key::J,);
+        map.bind(Action::ShootRight, Key::L,);
+        map.bind(Action::Boost, Key::Space,);
+        map.bind(Action::Quit, Key::Escape,);
+        map.bind(Action::Quit, Key::Q,);
+        map.bind(Action::ToggleDebug, Key::Tab,);
+        map
+    }
+
+    /// Defaults for a player slot. The first two players share the keyboard, the others only
+    /// get their gamepad until they are bound.
+    pub fn default_bindings_for(slot: usize) -> ActionMap {
+        match slot {
+            0 => ActionMap::default_bindings(),
+            1 => {
+                let mut map = ActionMap::empty();
+                map.bind(Action::MoveUp, Key::T,);
+                map.bind(Action::MoveDown, Key::G,);
+                map.bind(Action::MoveLeft, Key::F,);
+                map.bind(Action::MoveRight, Key::H,);
+                map.bind(Action::ShootUp, Key::Up,);
+                map.bind(Action::ShootDown, Key::Down,);
+                map.bind(Action::ShootLeft, Key::Left,);
+                map.bind(Action::ShootRight, Key::Right,);
+                map.bind(Action::Boost, Key::Enter,);
+                map.bind(Action::Quit, Key::Escape,);
+                map
+            }
+            _ => {
+                let mut map = ActionMap::empty();
+                map.bind(Action::Quit, Key::Escape,);
+                map
+            }
+        }
+    }
+
+    pub fn load(path: &str) -> Result<ActionMap, ActionMapError,> {
+        let text = fs::read_to_string(path,).map_err(|e| ActionMapError::Io(e.to_string(),),)?;
+        ActionMap::parse(&text,)
+    }
+
+    /// Parses lines of the form `move_up = w, up`. Empty lines and lines starting with `#`
+    /// are skipped. Actions that are not listed get no keys.
+    pub fn parse(text: &str) -> Result<ActionMap, ActionMapError,> {
+        let mut map = ActionMap::empty();
+        for (index, line,) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#',) {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=',);
+            let action_name = parts.next().unwrap_or("",);
+            let keys = match parts.next() {
+                Some(keys,) => keys,
+                None => {
+                    return Err(ActionMapError::Parse {
+                        line: line_number,
+                        message: format!("expected `action = key, key`, got `{}`", line),
+                    },)
+                }
+            };
+            let action = match Action::from_name(action_name,) {
+                Some(action,) => action,
+                None => {
+                    return Err(ActionMapError::Parse {
+                        line: line_number,
+                        message: format!("unknown action `{}`", action_name.trim()),
+                    },)
+                }
+            };
+            for key_name in keys.split(',',).filter(|k| !k.trim().is_empty(),) {
+                match Key::from_name(key_name,) {
+                    Some(key,) => map.bind(action, key,),
+                    None => {
+                        return Err(ActionMapError::Parse {
+                            line: line_number,
+                            message: format!("unknown key `{}`", key_name.trim()),
+                        },)
+                    }
+                }
+            }
+        }
+        Ok(map,)
+    }
+
+    /// Writes the map in the format `parse` reads.
+    pub fn to_config_string(&self) -> String {
+        let mut out = String::new();
+        for action in ALL_ACTIONS.iter() {
+            let keys: Vec<&str,> = self.keys_for(*action,).iter().map(|k| k.name(),).collect();
+            out.push_str(&format!("{} = {}\n", action.name(), keys.join(", ")),);
+        }
+        out
+    }
+
+    pub fn keys_for(&self, action: Action,) -> &[Key] {
+        &self.bindings[action as usize]
+    }
+
+    /// Adds a key to an action, keeping the keys already bound to it.
+    pub fn bind(&mut self, action: Action, key: Key,) {
+        let keys = &mut self.bindings[action as usize];
+        if !keys.contains(&key,) {
+            keys.push(key,);
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, key: Key,) {
+        self.bindings[action as usize].retain(|k| *k != key,);
+    }
+
+    /// Replaces `old` with `new` for the action. If `old` was not bound, `new` is added.
+    pub fn rebind(&mut self, action: Action, old: Key, new: Key,) {
+        let keys = &mut self.bindings[action as usize];
+        match keys.iter().position(|k| *k == old,) {
+            Some(i,) if !keys.contains(&new,) => keys[i] = new,
+            Some(i,) => {
+                keys.remove(i,);
+            }
+            None => {
+                if !keys.contains(&new,) {
+                    keys.push(new,);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self, action: Action,) {
+        self.bindings[action as usize].clear();
+    }
+
+    pub fn is_active(&self, action: Action, keys: &KeyState,) -> bool {
+        self.keys_for(action,).iter().any(|k| keys.is_pressed(*k,),)
+    }
+
+    /// Sets the action flags of `input` from its raw key state. Flags that are already set
+    /// stay set, so frontends that fill them in directly keep working.
+    pub fn apply(&self, input: &mut GameInput,) {
+        let keys = input.keys;
+        for action in ALL_ACTIONS.iter() {
+            if self.is_active(*action, &keys,) {
+                *action.flag(input,) = true;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_key_names() {
+    for key in ALL_KEYS.iter() {
+        assert_eq!(Key::from_name(key.name(),), Some(*key));
+    }
+    assert_eq!(Key::from_name("Space",), Some(Key::Space));
+    assert_eq!(Key::from_name("f13",), None);
+}
+
+#[test]
+fn test_parse_and_apply() {
+    let map = ActionMap::parse(
+        "# movement\nmove_up = w, up\n\nshoot_left = j\nquit = escape",
+    ).unwrap();
+    assert_eq!(map.keys_for(Action::MoveUp,), &[Key::W, Key::Up]);
+    assert_eq!(map.keys_for(Action::MoveDown,).len(), 0);
+
+    let mut input = GameInput::new();
+    input.keys.set(Key::Up, true,);
+    map.apply(&mut input,);
+    assert!(input.up_key);
+    assert!(!input.shoot_left);
+
+    let round_trip = ActionMap::parse(&map.to_config_string(),).unwrap();
+    assert_eq!(round_trip.keys_for(Action::MoveUp,), &[Key::W, Key::Up]);
+}
+
+#[test]
+fn test_parse_errors() {
+    match ActionMap::parse("move_up = w\njump = space",) {
+        Err(ActionMapError::Parse { line, .. },) => assert_eq!(line, 2),
+        _ => panic!("expected parse error"),
+    }
+    match ActionMap::parse("move_up = hyperspace",) {
+        Err(ActionMapError::Parse { line, .. },) => assert_eq!(line, 1),
+        _ => panic!("expected parse error"),
+    }
+    assert!(ActionMap::parse("move_up w",).is_err());
+}
+
+#[test]
+fn test_rebind() {
+    let mut map = ActionMap::default_bindings();
+    map.rebind(Action::MoveUp, Key::W, Key::Up,);
+    assert_eq!(map.keys_for(Action::MoveUp,), &[Key::Up]);
+    map.bind(Action::MoveUp, Key::W,);
+    assert_eq!(map.keys_for(Action::MoveUp,), &[Key::Up, Key::W]);
+    map.unbind(Action::MoveUp, Key::Up,);
+    assert_eq!(map.keys_for(Action::MoveUp,), &[Key::W]);
+    map.clear(Action::Quit,);
+    assert_eq!(map.keys_for(Action::Quit,).len(), 0);
+}
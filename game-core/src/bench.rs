@@ -0,0 +1,80 @@
+//! Seeded games crowded with enemies and bullets, for the criterion benchmarks in `benches/`.
+//! The same size always gives the same game, so runs before and after a change compare.
+
+use draw::DrawList;
+use game_state::{GameState, MAX_BULLETS};
+use GameInput;
+
+/// Enemies in the benchmarked games, and bullets up to `MAX_BULLETS`.
+pub const SIZES: [usize; 3] = [10, 1_000, 10_000];
+pub const SEED: u64 = 0x5eed;
+pub const PLAYERS: usize = 2;
+/// Every simulated frame is this long.
+pub const DELTA: f32 = 1.0 / 60.0;
+
+#[derive(Clone)]
+pub struct Scene {
+    state: GameState,
+    inputs: Vec<GameInput,>,
+    time_elapsed: f32,
+}
+
+impl Scene {
+    /// `PLAYERS` players walking and shooting, with nothing around them but the first enemy.
+    pub fn empty() -> Scene {
+        let mut input = GameInput::new();
+        input.right_key = true;
+        input.shoot_up = true;
+        Scene {
+            state: GameState::new(960.0, 540.0, PLAYERS, SEED,),
+            inputs: vec![input; PLAYERS],
+            time_elapsed: 0.0,
+        }
+    }
+
+    /// An empty scene with `count` enemies added, and as many bullets as long as the game
+    /// keeps them. A frame drops the bullets past `MAX_BULLETS`.
+    pub fn crowded(count: usize) -> Scene {
+        let mut scene = Scene::empty();
+        scene.state.crowd(count, count.min(MAX_BULLETS,),);
+        scene
+    }
+
+    /// Players, enemies and bullets in the game, what a frame works through.
+    pub fn entities(&self) -> usize {
+        let stats = self.state.debug_stats();
+        PLAYERS + stats.enemies + stats.bullets
+    }
+
+    /// One frame, the same as the game loop simulates.
+    pub fn simulate(&mut self) {
+        self.time_elapsed += DELTA;
+        self.state.simulate(&self.inputs, self.time_elapsed, DELTA,);
+    }
+
+    /// Only the collision checks of a frame, without moving anything first.
+    pub fn check_collisions(&mut self) {
+        self.state.check_collisions();
+    }
+
+    /// Pushes `count` enemies at random spots away from the players, past `MAX_ENEMIES`. Not
+    /// the spawner of the game, which adds one enemy or swarm at a time up to the cap.
+    pub fn insert_enemies(&mut self, count: usize,) {
+        self.state.crowd(count, 0,);
+    }
+
+    /// What the frame would draw, without handing it to the platform.
+    pub fn draw_list(&self) -> DrawList {
+        self.state.draw_list(&[],)
+    }
+}
+
+#[test]
+fn test_crowded_is_seeded() {
+    let a = Scene::crowded(100,);
+    let b = Scene::crowded(100,);
+    assert_eq!(a.state.state_hash(), b.state.state_hash());
+    let stats = a.state.debug_stats();
+    assert_eq!((stats.enemies, stats.bullets,), (101, 100,));
+    assert_eq!(a.entities(), PLAYERS + 201);
+}
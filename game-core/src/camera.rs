@@ -0,0 +1,82 @@
+use entities::BoundingBox;
+use math::vector::Vector2d;
+
+/// Maps between world space (origin bottom left, y up) and screen space
+/// (pixels, origin top left, y down) as used by the frontends.
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Camera {
+    /// World position shown at the center of the screen.
+    pub pos: Vector2d,
+    /// Screen pixels per world unit.
+    pub zoom: f32,
+    screen_width: f32,
+    screen_height: f32,
+}
+
+impl Camera {
+    /// Camera that shows the world 1:1, with world (0, 0) in the bottom left corner.
+    pub fn new(screen_width: f32, screen_height: f32,) -> Camera {
+        Camera {
+            pos: Vector2d::new(screen_width / 2.0, screen_height / 2.0,),
+            zoom: 1.0,
+            screen_width,
+            screen_height,
+        }
+    }
+
+    pub fn screen_to_world(&self, screen_x: f32, screen_y: f32,) -> Vector2d {
+        // flip y so it points up, same as platform.js does when drawing
+        let flipped_y = self.screen_height - screen_y;
+        Vector2d::new(
+            (screen_x - self.screen_width / 2.0) / self.zoom + self.pos.x,
+            (flipped_y - self.screen_height / 2.0) / self.zoom + self.pos.y,
+        )
+    }
+
+    pub fn world_to_screen(&self, world: Vector2d,) -> Vector2d {
+        let x = (world.x - self.pos.x) * self.zoom + self.screen_width / 2.0;
+        let flipped_y = (world.y - self.pos.y) * self.zoom + self.screen_height / 2.0;
+        Vector2d::new(x, self.screen_height - flipped_y,)
+    }
+
+    /// Moves a world space point into the space the platform draws in (pixels, y up).
+    pub fn world_to_view_point(&self, world: Vector2d,) -> Vector2d {
+        Vector2d::new(
+            (world.x - self.pos.x) * self.zoom + self.screen_width / 2.0,
+            (world.y - self.pos.y) * self.zoom + self.screen_height / 2.0,
+        )
+    }
+
+    /// Moves a world space box into the space the platform draws in (pixels, y up).
+    pub fn world_to_view(&self, rect: &BoundingBox,) -> BoundingBox {
+        let half_width = self.screen_width / 2.0;
+        let half_height = self.screen_height / 2.0;
+        BoundingBox {
+            left: (rect.left - self.pos.x) * self.zoom + half_width,
+            right: (rect.right - self.pos.x) * self.zoom + half_width,
+            top: (rect.top - self.pos.y) * self.zoom + half_height,
+            bottom: (rect.bottom - self.pos.y) * self.zoom + half_height,
+        }
+    }
+}
+
+#[test]
+fn test_screen_to_world() {
+    let mut cam = Camera::new(960.0, 540.0,);
+    let w = cam.screen_to_world(0.0, 0.0,);
+    assert_eq!(w.x, 0.0);
+    assert_eq!(w.y, 540.0);
+    let w = cam.screen_to_world(480.0, 270.0,);
+    assert_eq!(w.x, 480.0);
+    assert_eq!(w.y, 270.0);
+
+    cam.zoom = 2.0;
+    cam.pos = Vector2d::new(100.0, 100.0,);
+    let w = cam.screen_to_world(960.0, 540.0,);
+    assert_eq!(w.x, 340.0);
+    assert_eq!(w.y, -35.0);
+    let s = cam.world_to_screen(w,);
+    assert_eq!(s.x, 960.0);
+    assert_eq!(s.y, 540.0);
+}
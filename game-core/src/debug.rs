@@ -0,0 +1,246 @@
+//! Debug overlay: collider outlines, velocities, the last contact, entity counts and a graph of
+//! recent frame times. Everything goes through the normal draw list, so it shows up the same in
+//! every frontend. Toggled with `Action::ToggleDebug`, tab by default.
+
+use draw::{DrawCommand, DrawList, Shape, LAYER_HUD};
+use entities::{Color, Side};
+use font::{push_text, GLYPH_HEIGHT};
+use math::vector::Vector2d;
+use GameInput;
+
+/// On top of the hud, below menus.
+pub const LAYER_DEBUG: i32 = LAYER_HUD + 50;
+/// Frames shown in the frame time graph.
+pub const FRAME_HISTORY: usize = 120;
+/// Velocity arrows show where the entity will be this many seconds later.
+pub const VELOCITY_SCALE: f32 = 0.25;
+
+/// Where and how two boxes last overlapped, as found by the collision checks.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Contact {
+    pub position: Vector2d,
+    pub side: Side,
+    pub amount: f32,
+}
+
+/// Numbers shown in the corner of the overlay.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugStats {
+    pub players: usize,
+    pub enemies: usize,
+    pub bullets: usize,
+    pub walls: usize,
+    pub contact: Option<Contact,>,
+}
+
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct DebugOverlay {
+    enabled: bool,
+    key_held: bool,
+    /// Seconds, oldest first once full.
+    frame_times: Vec<f32,>,
+    next: usize,
+}
+
+impl DebugOverlay {
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            enabled: false,
+            key_held: false,
+            frame_times: Vec::with_capacity(FRAME_HISTORY,),
+            next: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool,) {
+        self.enabled = enabled;
+    }
+
+    /// Toggles on a newly pressed debug key and records `frame_time`, also while hidden so the
+    /// graph is full when it is shown.
+    pub fn update(&mut self, inputs: &[GameInput], frame_time: f32,) {
+        let held = inputs.iter().any(|i| i.debug_key,);
+        if held && !self.key_held {
+            self.enabled = !self.enabled;
+        }
+        self.key_held = held;
+
+        let frame_time = if frame_time.is_finite() { frame_time.max(0.0,) } else { 0.0 };
+        if self.frame_times.len() < FRAME_HISTORY {
+            self.frame_times.push(frame_time,);
+        } else {
+            self.frame_times[self.next] = frame_time;
+        }
+        self.next = (self.next + 1) % FRAME_HISTORY;
+    }
+
+    /// Recorded frame times, oldest first.
+    pub fn frame_times(&self) -> Vec<f32,> {
+        if self.frame_times.len() < FRAME_HISTORY {
+            return self.frame_times.clone();
+        }
+        let mut times = self.frame_times[self.next..].to_vec();
+        times.extend_from_slice(&self.frame_times[..self.next],);
+        times
+    }
+
+    /// Counts, the last contact and the frame time graph in the bottom left of the view.
+    pub fn draw(&self, list: &mut DrawList, stats: &DebugStats,) {
+        let scale = 2.0;
+        let line_height = (GLYPH_HEIGHT as f32 + 2.0) * scale;
+        let times = self.frame_times();
+        let mut lines = vec![
+            format!("PLAYERS {} ENEMIES {}", stats.players, stats.enemies),
+            format!("BULLETS {} WALLS {}", stats.bullets, stats.walls),
+        ];
+        if let Some(last,) = times.last() {
+            let worst = times.iter().cloned().fold(0.0, f32::max,);
+            lines.push(format!(
+                "FRAME {:.1} MS MAX {:.1} MS",
+                last * 1000.0,
+                worst * 1000.0
+            ),);
+        }
+        if let Some(ref contact,) = stats.contact {
+            lines.push(format!("CONTACT {:?} {:.2}", contact.side, contact.amount),);
+        }
+
+        let graph_height = 40.0;
+        let mut top = 10.0 + graph_height + 6.0 + line_height * lines.len() as f32;
+        for line in &lines {
+            push_text(list, LAYER_DEBUG, line, 10.0, top, scale, text_color(),);
+            top -= line_height;
+        }
+        self.draw_graph(list, &times, Vector2d::new(10.0, 10.0,), graph_height,);
+    }
+
+    /// One bar per frame, the line marks 60 frames per second. Slow frames are red.
+    fn draw_graph(&self, list: &mut DrawList, times: &[f32], origin: Vector2d, height: f32,) {
+        let target = 1.0 / 60.0;
+        // a 60 fps frame reaches half way up
+        let units_per_second = height / (target * 2.0);
+        let bar_width = 2.0;
+        let width = FRAME_HISTORY as f32 * bar_width;
+        list.push_outline(
+            LAYER_DEBUG,
+            origin,
+            origin + Vector2d::new(width, height,),
+            1.0,
+            text_color(),
+        );
+        for (index, time,) in times.iter().enumerate() {
+            let left = origin.x + index as f32 * bar_width;
+            let top = origin.y + (time * units_per_second).min(height,);
+            let color = if *time > target * 1.5 { slow_color() } else { bar_color() };
+            list.push(DrawCommand {
+                layer: LAYER_DEBUG,
+                min_x: left,
+                min_y: origin.y,
+                max_x: left + bar_width,
+                max_y: top,
+                shape: Shape::Rectangle { color, },
+            },);
+        }
+        let target_y = origin.y + target * units_per_second;
+        list.push_line(
+            LAYER_DEBUG + 1,
+            Vector2d::new(origin.x, target_y,),
+            Vector2d::new(origin.x + width, target_y,),
+            1.0,
+            text_color(),
+        );
+    }
+}
+
+impl Default for DebugOverlay {
+    fn default() -> DebugOverlay {
+        DebugOverlay::new()
+    }
+}
+
+/// Outline colours for the collider boxes.
+pub fn collider_color() -> Color {
+    Color {
+        r: 0.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    }
+}
+
+pub fn velocity_color() -> Color {
+    Color {
+        r: 1.0,
+        g: 1.0,
+        b: 0.0,
+        a: 1.0,
+    }
+}
+
+pub fn contact_color() -> Color {
+    Color {
+        r: 1.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    }
+}
+
+fn text_color() -> Color {
+    Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 0.8,
+    }
+}
+
+fn bar_color() -> Color {
+    Color {
+        r: 0.2,
+        g: 0.9,
+        b: 0.2,
+        a: 0.8,
+    }
+}
+
+fn slow_color() -> Color {
+    Color {
+        r: 1.0,
+        g: 0.2,
+        b: 0.2,
+        a: 0.8,
+    }
+}
+
+#[test]
+fn test_toggle_and_frame_history() {
+    let mut overlay = DebugOverlay::new();
+    let mut toggle = GameInput::new();
+    toggle.debug_key = true;
+    let none = GameInput::new();
+
+    overlay.update(&[toggle], 0.016,);
+    assert!(overlay.is_enabled());
+    // held down, does not flip back
+    overlay.update(&[toggle], 0.016,);
+    assert!(overlay.is_enabled());
+    overlay.update(&[none], 0.016,);
+    overlay.update(&[none, toggle], 0.016,);
+    assert!(!overlay.is_enabled());
+
+    for frame in 0..FRAME_HISTORY + 10 {
+        overlay.update(&[none], frame as f32,);
+    }
+    overlay.update(&[none], f32::NAN,);
+    let times = overlay.frame_times();
+    assert_eq!(times.len(), FRAME_HISTORY);
+    assert_eq!(times[0], 11.0);
+    assert_eq!(times[FRAME_HISTORY - 2], (FRAME_HISTORY + 9) as f32);
+    assert_eq!(times[FRAME_HISTORY - 1], 0.0);
+}
@@ -0,0 +1,169 @@
+use draw_quad;
+use draw_rectangle;
+use draw_sprite;
+use entities::Color;
+use math::vector::Vector2d;
+use sprite::{Flip, Sprite};
+
+/// Draw layers, lower ones are drawn first. Anything in between works too, `LAYER_ENEMIES + 1`
+/// is drawn on top of enemies but below players.
+pub const LAYER_BACKGROUND: i32 = 0;
+pub const LAYER_WALLS: i32 = 100;
+pub const LAYER_BULLETS: i32 = 200;
+pub const LAYER_ENEMIES: i32 = 300;
+pub const LAYER_PLAYERS: i32 = 400;
+pub const LAYER_EFFECTS: i32 = 500;
+pub const LAYER_HUD: i32 = 1000;
+
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Rectangle { color: Color, },
+    Sprite { sprite: Sprite, tint: Color, flip: Flip, },
+    /// Any convex quad, like a rotated box. The min and max of the command are its bounds.
+    Quad { corners: [Vector2d; 4], color: Color, },
+}
+
+/// One draw call, in view coordinates like `Platform::draw_rectangle`.
+#[derive(Debug, Clone)]
+pub struct DrawCommand {
+    pub layer: i32,
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+    pub shape: Shape,
+}
+
+/// Draws collected over a frame. `submit` hands them to the platform sorted by layer, draws on
+/// the same layer keep the order they were pushed in.
+#[derive(Default)]
+pub struct DrawList {
+    commands: Vec<DrawCommand,>,
+}
+
+impl DrawList {
+    pub fn new() -> DrawList {
+        DrawList {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, command: DrawCommand,) {
+        self.commands.push(command,);
+    }
+
+    /// A quad with its bounds filled in.
+    pub fn push_quad(&mut self, layer: i32, corners: [Vector2d; 4], color: Color,) {
+        let mut command = DrawCommand {
+            layer,
+            min_x: corners[0].x,
+            min_y: corners[0].y,
+            max_x: corners[0].x,
+            max_y: corners[0].y,
+            shape: Shape::Quad { corners, color, },
+        };
+        for c in &corners[1..] {
+            command.min_x = command.min_x.min(c.x,);
+            command.min_y = command.min_y.min(c.y,);
+            command.max_x = command.max_x.max(c.x,);
+            command.max_y = command.max_y.max(c.y,);
+        }
+        self.commands.push(command,);
+    }
+
+    /// A line `width` thick from `from` to `to`, drawn as a quad.
+    pub fn push_line(
+        &mut self,
+        layer: i32,
+        from: Vector2d,
+        to: Vector2d,
+        width: f32,
+        color: Color,
+    ) {
+        let direction = to - from;
+        if direction.len() == 0.0 {
+            return;
+        }
+        let normal = Vector2d::new(-direction.y, direction.x,).normalized() * (width / 2.0);
+        self.push_quad(layer, [from + normal, to + normal, to - normal, from - normal], color,);
+    }
+
+    /// The edges of a rectangle, `width` thick on the inside.
+    pub fn push_outline(
+        &mut self,
+        layer: i32,
+        min: Vector2d,
+        max: Vector2d,
+        width: f32,
+        color: Color,
+    ) {
+        let width = width.min((max.x - min.x) / 2.0,).min((max.y - min.y) / 2.0,).max(0.0,);
+        let edges = [
+            (min.x, min.y, max.x, min.y + width,),
+            (min.x, max.y - width, max.x, max.y,),
+            (min.x, min.y + width, min.x + width, max.y - width,),
+            (max.x - width, min.y + width, max.x, max.y - width,),
+        ];
+        for &(min_x, min_y, max_x, max_y,) in &edges {
+            self.commands.push(DrawCommand {
+                layer,
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                shape: Shape::Rectangle { color, },
+            },);
+        }
+    }
+
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    pub fn sort(&mut self) {
+        // sort_by_key is stable
+        self.commands.sort_by_key(|c| c.layer,);
+    }
+
+    pub fn submit(mut self) {
+        self.sort();
+        for c in self.commands {
+            match c.shape {
+                Shape::Rectangle { color, } => {
+                    draw_rectangle(c.min_x, c.min_y, c.max_x, c.max_y, color,);
+                }
+                Shape::Sprite { sprite, tint, flip, } => {
+                    draw_sprite(sprite, c.min_x, c.min_y, c.max_x, c.max_y, tint, flip,);
+                }
+                Shape::Quad { corners, color, } => {
+                    draw_quad(corners, color,);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_sort_keeps_push_order_within_a_layer() {
+    let mut list = DrawList::new();
+    for (index, layer,) in [LAYER_HUD, LAYER_WALLS, LAYER_PLAYERS, LAYER_WALLS].iter().enumerate() {
+        list.push(DrawCommand {
+            layer: *layer,
+            min_x: index as f32,
+            min_y: 0.0,
+            max_x: 1.0,
+            max_y: 1.0,
+            shape: Shape::Rectangle {
+                color: Color {
+                    r: 1.0,
+                    g: 1.0,
+                    b: 1.0,
+                    a: 1.0,
+                },
+            },
+        },);
+    }
+    list.sort();
+    let order: Vec<f32,> = list.commands().iter().map(|c| c.min_x,).collect();
+    assert_eq!(order, vec![1.0, 3.0, 2.0, 0.0]);
+}
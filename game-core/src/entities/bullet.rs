@@ -1,6 +1,12 @@
 use entities::{BoundingBox, Collider, Color, Drawable, Position};
-use math::{pulse_value, vector::Vector2d};
+use math::vector::Vector2d;
+use std::f32::consts::PI;
+use transform::Transform;
+use tunables::tunables;
+use tween::{Animation, Animator, Ease};
 
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct Bullet {
     pos: Vector2d,
     width: f32,
@@ -8,32 +14,56 @@ pub struct Bullet {
     color: Color,
     vel: Vector2d,
     life_time: f32,
+    owner: usize,
+    animator: Animator<Bullet,>,
 }
 
-const VEL: f32 = 300.0;
+/// Owner of bullets fired by enemies. They hit players instead of enemies.
+pub const ENEMY_OWNER: usize = usize::MAX;
 
 impl Bullet {
-    pub fn new(pos: Vector2d, direction: Vector2d,) -> Bullet {
+    /// `owner` is the slot of the player that fired it.
+    /// Size, colour and speed come from the tunables.
+    pub fn new(pos: Vector2d, direction: Vector2d, owner: usize,) -> Bullet {
+        let tunables = tunables();
         Bullet {
             pos,
-            width: 4.0,
-            height: 4.0,
-            color: Color {
-                r: 1.0,
-                g: 0.7,
-                b: 0.7,
-                a: 1.0,
-            },
+            width: tunables.bullet_size,
+            height: tunables.bullet_size,
+            color: tunables.bullet_color,
             life_time: 0.0,
-            vel: direction.mul(VEL,),
+            vel: direction.mul(tunables.bullet_speed,),
+            owner,
+            animator: Animator::new(
+                Animation::tween(
+                    |b: &mut Bullet, v| b.color.r = v,
+                    tunables.bullet_color.r,
+                    1.0,
+                    PI / 5.0,
+                    Ease::SineInOut,
+                ).ping_pong()
+                .starting_at(PI / 10.0,),
+            ),
         }
     }
 
+    pub fn get_owner(&self) -> usize {
+        self.owner
+    }
+
+    pub fn is_from_enemy(&self) -> bool {
+        self.owner == ENEMY_OWNER
+    }
+
+    pub fn get_velocity(&self) -> Vector2d {
+        self.vel
+    }
+
     pub fn update(&mut self, delta: f32,) {
         self.life_time += delta;
         self.pos.x += self.vel.x * delta;
         self.pos.y += self.vel.y * delta;
-        self.color.r = pulse_value(0.7, 1.0, self.life_time * 5.0,);
+        Animator::update_in(self, |b| &mut b.animator, delta,);
     }
 }
 
@@ -71,4 +101,9 @@ impl Drawable for Bullet {
             a: self.color.a,
         }
     }
+
+    /// Turned to point where it flies.
+    fn get_transform(&self) -> Transform {
+        Transform::new(self.pos, self.vel.angle(), Vector2d::new(self.width, self.height,),)
+    }
 }
@@ -1,3 +1,5 @@
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct Cooldown {
     current: f32,
     cool_down: f32,
@@ -1,10 +1,30 @@
 use entities::{player::Player, BoundingBox, Collider, Color, Drawable, Position};
-use math::{pulse_value, vector::Vector2d};
+use math::vector::Vector2d;
+use nav::{Navigation, REPATH_DISTANCE};
+use rng::Rng;
+use std::f32::consts::PI;
+use steering::{
+    alignment, cohesion, combine, seek, separation, wander, Agent, FlockWeights, Wander,
+};
+use transform::Transform;
+use tunables::tunables;
+use tween::{Animation, Animator, Ease};
 
+/// Swarm members flock with each other on their way to the player.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub enum EnemyType {
     Normal,
+    Swarm,
 }
 
+/// Swarm members keep this far apart.
+const SEPARATION_RADIUS: f32 = 20.0;
+/// Swarm members within this distance move as a group.
+const FLOCK_RADIUS: f32 = 60.0;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct Enemy {
     enemy_type: EnemyType,
     pos: Vector2d,
@@ -12,39 +32,232 @@ pub struct Enemy {
     height: f32,
     color: Color,
     life_time: f32,
+    /// Radians, enemies turn to face the way they move.
+    rotation: f32,
+    animator: Animator<Enemy,>,
+    /// Name of the script function that moves it, None for the built in behaviour.
+    behaviour: Option<String,>,
+    /// Waypoints around walls, the next one last.
+    path: Vec<Vector2d,>,
+    /// Where the target was when `path` was found.
+    path_goal: Option<Vector2d,>,
+    velocity: Vector2d,
+    /// Units per second. Normal enemies used to step one unit every frame, the default
+    /// `enemy_speed` of 60 is the same at 60 frames per second and no longer depends on it.
+    max_speed: f32,
+    /// Units per second per second, only swarm members accelerate, normal enemies go full
+    /// speed at once.
+    max_acceleration: f32,
+    wander: Wander,
+    /// Seeded from where the enemy spawned, so it wanders the same way on every peer.
+    rng: Rng,
 }
 
 impl Enemy {
+    /// Size, colour and speed come from the tunables.
     pub fn new(enemy_type: EnemyType, pos: Vector2d,) -> Enemy {
+        let tunables = tunables();
+        let (max_speed, color,) = match enemy_type {
+            EnemyType::Normal => (tunables.enemy_speed, tunables.enemy_color,),
+            EnemyType::Swarm => (tunables.swarm_speed, tunables.swarm_color,),
+        };
+        let size = tunables.enemy_size;
+        let seed = (u64::from(pos.x.to_bits(),) << 32) | u64::from(pos.y.to_bits(),);
         Enemy {
             enemy_type,
             pos,
             life_time: 0.0,
-            width: 10.0,
-            height: 10.0,
-            color: Color {
-                r: 0.1,
-                g: 1.0,
-                b: 0.1,
-                a: 1.0,
-            },
+            rotation: 0.0,
+            animator: Animator::new(Enemy::pulse(size,),),
+            behaviour: None,
+            path: Vec::new(),
+            path_goal: None,
+            velocity: Vector2d::zero(),
+            max_speed,
+            max_acceleration: 200.0,
+            wander: Wander::default(),
+            rng: Rng::new(seed,),
+            width: size,
+            height: size,
+            color,
+        }
+    }
+
+    pub fn is_swarm(&self) -> bool {
+        self.enemy_type == EnemyType::Swarm
+    }
+
+    pub fn get_velocity(&self) -> Vector2d {
+        self.velocity
+    }
+
+    pub fn set_max_speed(&mut self, max_speed: f32,) {
+        self.max_speed = max_speed;
+    }
+
+    pub fn set_max_acceleration(&mut self, max_acceleration: f32,) {
+        self.max_acceleration = max_acceleration;
+    }
+
+    /// Position, velocity and limits for the steering behaviours.
+    pub fn get_agent(&self) -> Agent {
+        Agent {
+            position: self.pos,
+            velocity: self.velocity,
+            max_speed: self.max_speed,
+            max_acceleration: self.max_acceleration,
         }
     }
 
-    pub fn update(&mut self, player: &Player, delta: f32,) {
+    /// An enemy moved by the script function called `behaviour`. Without the `scripting`
+    /// feature, or when the function is missing, it acts like a normal one.
+    pub fn scripted(pos: Vector2d, behaviour: &str,) -> Enemy {
+        let mut enemy = Enemy::new(EnemyType::Normal, pos,);
+        enemy.behaviour = Some(behaviour.to_string(),);
+        enemy
+    }
+
+    pub fn get_behaviour(&self) -> Option<&str,> {
+        self.behaviour.as_deref()
+    }
+
+    pub fn get_life_time(&self) -> f32 {
+        self.life_time
+    }
+
+    /// Grows and shrinks a little from `size`, a bit out of step in each direction so it
+    /// wobbles. Both start half way, growing.
+    fn pulse(size: f32) -> Animation<Enemy,> {
+        Animation::Parallel(vec![
+            Animation::tween(
+                |e: &mut Enemy, v| e.width = v,
+                size,
+                size * 1.5,
+                PI / 10.0,
+                Ease::SineInOut,
+            ).ping_pong()
+            .starting_at(PI / 20.0,),
+            Animation::tween(
+                |e: &mut Enemy, v| e.height = v,
+                size,
+                size * 1.5,
+                PI / 7.5,
+                Ease::SineInOut,
+            ).ping_pong()
+            .starting_at(PI / 15.0,),
+        ],)
+    }
+
+    /// Moves towards the nearest living player, around walls on `nav`. Swarm members also
+    /// flock with the others in `flock`, `own` is where this one is in it. Stands still when
+    /// everyone is dead.
+    pub fn update(
+        &mut self,
+        players: &[Player],
+        nav: &Navigation,
+        flock: &[Agent],
+        own: Option<usize,>,
+        delta: f32,
+    ) {
         self.life_time += delta;
+        let target = match self.nearest_player(players,) {
+            Some(player,) => player.get_position(),
+            None => return,
+        };
+        let direction = self.route_towards(target, nav,);
         match self.enemy_type {
             EnemyType::Normal => {
-                let player_pos = player.get_position();
-                let mut current_pos = self.pos;
-                current_pos.sub(&player_pos,);
-                current_pos.normalize();
-                current_pos = current_pos.mul(-1.0,);
-                self.pos.add(&current_pos,);
-                self.width = 10.0 + pulse_value(0.0, 5.0, self.life_time * 10.0,);
-                self.height = 10.0 + pulse_value(0.0, 5.0, self.life_time * 7.5,);
+                self.velocity = direction * self.max_speed;
+                let step = direction * (self.max_speed * delta).min(self.pos.distance(&target,),);
+                let pos = self.pos + step;
+                self.move_to(pos,);
+            }
+            EnemyType::Swarm => {
+                let mut agent = self.get_agent();
+                let weights = FlockWeights::default();
+                let random = self.rng.range(-1.0, 1.0,);
+                let force = combine(&[
+                    (seek(&agent, agent.position + direction * agent.max_speed,), weights.seek,),
+                    (separation(&agent, flock, own, SEPARATION_RADIUS,), weights.separation,),
+                    (alignment(&agent, flock, own, FLOCK_RADIUS,), weights.alignment,),
+                    (cohesion(&agent, flock, own, FLOCK_RADIUS,), weights.cohesion,),
+                    (wander(&agent, &mut self.wander, random,), weights.wander,),
+                ],);
+                agent.apply(force, delta,);
+                self.velocity = agent.velocity;
+                self.move_to(agent.position,);
+            }
+        }
+        Animator::update_in(self, |e| &mut e.animator, delta,);
+    }
+
+    /// Unit direction to walk in to reach `target`. Straight at it when nothing is in the way,
+    /// otherwise along the flow field towards it, or along a path that is only searched again
+    /// when the target moved `REPATH_DISTANCE` from where it was.
+    fn route_towards(&mut self, target: Vector2d, nav: &Navigation,) -> Vector2d {
+        let grid = nav.get_grid();
+        if grid.has_line_of_sight(self.pos, target,) {
+            self.path.clear();
+            return (target - self.pos).normalized();
+        }
+        if let Some(field,) = nav.flow_field_to(target,) {
+            let direction = field.direction(self.pos,);
+            if direction.len() > 0.0 {
+                self.path.clear();
+                return direction;
             }
         }
+
+        let moved_far = match self.path_goal {
+            Some(goal,) => goal.distance(&target,) > REPATH_DISTANCE,
+            None => true,
+        };
+        if self.path.is_empty() || moved_far {
+            self.path = match grid.find_path(self.pos, target,) {
+                Some(path,) => grid.smooth_path(self.pos, &path,),
+                None => Vec::new(),
+            };
+            // next waypoint last, so reached ones are popped off the end
+            self.path.reverse();
+            self.path_goal = Some(target,);
+        }
+        while self.path.len() > 1 && self.path[self.path.len() - 1].distance(&self.pos,) < 1.0 {
+            self.path.pop();
+        }
+        match self.path.last() {
+            Some(waypoint,) => (*waypoint - self.pos).normalized(),
+            None => (target - self.pos).normalized(),
+        }
+    }
+
+    /// Ages the enemy and plays its pulse without moving it, for scripted behaviours.
+    pub fn animate(&mut self, delta: f32,) {
+        self.life_time += delta;
+        Animator::update_in(self, |e| &mut e.animator, delta,);
+    }
+
+    /// Moves to `pos`, turning to face the way it went.
+    pub fn move_to(&mut self, pos: Vector2d,) {
+        let moved = pos - self.pos;
+        if moved.len() > 0.0 {
+            self.rotation = moved.angle();
+        }
+        self.pos = pos;
+    }
+
+    /// Nearest living player.
+    pub fn nearest_player<'a,>(&self, players: &'a [Player],) -> Option<&'a Player,> {
+        let mut nearest: Option<(&Player, f32,),> = None;
+        for p in players.iter().filter(|p| p.is_alive(),) {
+            let mut diff = p.get_position();
+            diff.sub(&self.pos,);
+            let dist = diff.len();
+            match nearest {
+                Some((_, best,),) if best <= dist => {}
+                _ => nearest = Some((p, dist,),),
+            }
+        }
+        nearest.map(|(p, _,)| p,)
     }
 }
 
@@ -82,4 +295,30 @@ impl Drawable for Enemy {
             a: self.color.a,
         }
     }
+
+    fn get_transform(&self) -> Transform {
+        Transform::new(self.pos, self.rotation, Vector2d::new(self.width, self.height,),)
+    }
+}
+
+#[test]
+fn test_enemy_walks_around_wall() {
+    use entities::BoundingBox;
+    use nav::NavGrid;
+
+    let wall = BoundingBox {
+        left: 90.0,
+        right: 110.0,
+        top: 160.0,
+        bottom: 0.0,
+    };
+    let nav = Navigation::new(NavGrid::new(200.0, 200.0, 10.0, &[wall], 8.0,),);
+    let players = vec![Player::new(0, Vector2d::new(180.0, 20.0,),)];
+    let mut enemy = Enemy::new(EnemyType::Normal, Vector2d::new(20.0, 20.0,),);
+    for _ in 0..600 {
+        enemy.update(&players, &nav, &[], None, 1.0 / 60.0,);
+        let pos = enemy.get_position();
+        assert!(pos.x < 90.0 || pos.x > 110.0 || pos.y > 160.0);
+    }
+    assert!(enemy.get_position().distance(&players[0].get_position(),) < 2.0);
 }
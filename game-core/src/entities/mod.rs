@@ -1,4 +1,5 @@
 use math::vector::Vector2d;
+use transform::Transform;
 
 pub mod bullet;
 pub mod cooldown;
@@ -6,7 +7,8 @@ pub mod enemies;
 pub mod player;
 pub mod wall;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -26,6 +28,16 @@ pub trait Collider: Position {
 
 pub trait Drawable: Collider {
     fn get_color(&self) -> Color;
+
+    /// Where and how the entity is drawn. Collisions keep using the unrotated bounding box.
+    fn get_transform(&self) -> Transform {
+        let b = self.get_bounding_box();
+        Transform::new(
+            Vector2d::new((b.left + b.right) / 2.0, (b.bottom + b.top) / 2.0,),
+            0.0,
+            Vector2d::new(b.right - b.left, b.top - b.bottom,),
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -36,7 +48,8 @@ pub struct BoundingBox {
     pub bottom: f32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub enum Side {
     Left,
     Right,
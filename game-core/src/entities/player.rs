@@ -3,78 +3,157 @@ use entities::{
     Position, Side,
 };
 use math::vector::Vector2d;
+use tunables::tunables;
 use GameInput;
 
+pub const MAX_PLAYERS: usize = 4;
+pub const START_LIVES: u32 = 3;
+
+const PLAYER_COLORS: [(f32, f32, f32,); MAX_PLAYERS] = [
+    (1.0, 1.0, 1.0,),
+    (0.3, 0.8, 1.0,),
+    (1.0, 0.9, 0.2,),
+    (1.0, 0.4, 1.0,),
+];
+
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct Player {
+    slot: usize,
     pos: Vector2d,
     width: f32,
     height: f32,
     color: Color,
     shoot_cooldown: Cooldown,
+    /// Seconds left before the player can be hit again.
+    invulnerable: f32,
+    score: u32,
+    lives: u32,
+    /// Last horizontal direction moved in, sprites are drawn mirrored when going left.
+    facing_left: bool,
+    /// Unit vector of the last direction aimed in, the gun barrel points this way.
+    aim: Vector2d,
+    /// Units per second moved during the last update.
+    velocity: Vector2d,
 }
 
 impl Player {
-    pub fn new() -> Player {
+    /// `slot` is the input slot the player reads from, and picks the player colour.
+    pub fn new(slot: usize, pos: Vector2d,) -> Player {
+        let (r, g, b,) = PLAYER_COLORS[slot % MAX_PLAYERS];
+        let tunables = tunables();
         Player {
-            pos: Vector2d {
-                x: 960.0 / 2.0,
-                y: 540.0 / 2.0,
-            },
-            width: 10.0,
-            height: 10.0,
-            color: Color {
-                r: 1.0,
-                g: 1.0,
-                b: 1.0,
-                a: 1.0,
-            },
-            shoot_cooldown: Cooldown::new(0.1,),
+            slot,
+            pos,
+            width: tunables.player_size,
+            height: tunables.player_size,
+            color: Color { r, g, b, a: 1.0, },
+            shoot_cooldown: Cooldown::new(tunables.shoot_cooldown,),
+            invulnerable: 0.0,
+            score: 0,
+            lives: START_LIVES,
+            facing_left: false,
+            aim: Vector2d::new(1.0, 0.0,),
+            velocity: Vector2d::zero(),
         }
     }
 
-    pub fn update(&mut self, input: &GameInput, bullets: &mut Vec<Bullet,>, delta: f32,) {
-        self.shoot_cooldown.update(delta,);
-        self.update_pos(&input,);
-        self.fire_bullets(&input, bullets,);
+    pub fn get_velocity(&self) -> Vector2d {
+        self.velocity
     }
 
-    fn update_pos(&mut self, input: &GameInput,) {
-        let mut step_size: f32 = 1.5;
-        if input.space {
-            step_size = 10.0;
-        }
-        if input.up_key {
-            self.pos.y += step_size;
+    pub fn get_aim(&self) -> Vector2d {
+        self.aim
+    }
+
+    pub fn is_facing_left(&self) -> bool {
+        self.facing_left
+    }
+
+    pub fn get_slot(&self) -> usize {
+        self.slot
+    }
+
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn add_score(&mut self, points: u32,) {
+        self.score += points;
+    }
+
+    pub fn get_lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.lives > 0
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable > 0.0
+    }
+
+    /// Takes a life unless the player is already dead or just got hit.
+    /// Returns true if a life was lost.
+    pub fn hit(&mut self) -> bool {
+        if !self.is_alive() || self.is_invulnerable() {
+            return false;
         }
-        if input.down_key {
-            self.pos.y -= step_size;
+        self.lives -= 1;
+        self.invulnerable = 1.0;
+        true
+    }
+
+    /// `cursor` is the mouse position in world space. Speeds and the shooting cooldown follow
+    /// the tunables.
+    pub fn update(
+        &mut self,
+        input: &GameInput,
+        cursor: Vector2d,
+        bullets: &mut Vec<Bullet,>,
+        delta: f32,
+    ) {
+        let tunables = tunables();
+        self.shoot_cooldown.set_cooldown(tunables.shoot_cooldown,);
+        self.shoot_cooldown.update(delta,);
+        self.invulnerable -= delta;
+        let before = self.pos;
+        self.update_pos(&input, tunables.player_step, tunables.boost_step,);
+        if delta > 0.0 {
+            self.velocity = (self.pos - before) * (1.0 / delta);
         }
-        if input.left_key {
-            self.pos.x -= step_size;
+        self.fire_bullets(&input, cursor, bullets,);
+    }
+
+    fn update_pos(&mut self, input: &GameInput, walk_step: f32, boost_step: f32,) {
+        let mut step_size = walk_step;
+        if input.space {
+            step_size = boost_step;
         }
-        if input.right_key {
-            self.pos.x += step_size;
+        let step = input.move_vector().mul(step_size,);
+        if step.x < 0.0 {
+            self.facing_left = true;
+        } else if step.x > 0.0 {
+            self.facing_left = false;
         }
+        self.pos.add(&step,);
     }
 
-    fn fire_bullets(&mut self, input: &GameInput, bullets: &mut Vec<Bullet,>,) {
-        let mut direction = Vector2d { x: 0.0, y: 0.0, };
+    fn fire_bullets(&mut self, input: &GameInput, cursor: Vector2d, bullets: &mut Vec<Bullet,>,) {
+        let mut direction = input.aim_vector();
+        if input.mouse_left {
+            direction = cursor;
+            direction.sub(&self.pos,);
+        }
+        let aiming = direction.len() > 0.5;
+        if aiming {
+            self.aim = direction.normalized();
+        }
         if self.shoot_cooldown.is_elapsed() {
             self.shoot_cooldown.restart();
-            if input.shoot_right {
-                direction.x += 1.0;
-            }
-            if input.shoot_left {
-                direction.x -= 1.0;
-            }
-            if input.shoot_up {
-                direction.y += 1.0;
-            }
-            if input.shoot_down {
-                direction.y -= 1.0;
-            }
-            if direction.len() > 0.5 {
-                let bullet = Bullet::new(self.get_position(), direction,);
+            if aiming {
+                let bullet = Bullet::new(self.get_position(), self.aim, self.slot,);
                 bullets.push(bullet,);
             }
         }
@@ -83,26 +162,23 @@ impl Player {
     pub fn handle_collisions(&mut self, intersections: Option<Vec<Intersection,>,>,) {
         let player_pos = self.get_position();
 
-        match intersections {
-            Some(inter,) => {
-                for i in inter {
-                    match i.hit_side {
-                        Side::Left => {
-                            self.set_x(player_pos.x + i.amount,);
-                        }
-                        Side::Right => {
-                            self.set_x(player_pos.x - i.amount,);
-                        }
-                        Side::Top => {
-                            self.set_y(player_pos.y - i.amount,);
-                        }
-                        Side::Bottom => {
-                            self.set_y(player_pos.y + i.amount,);
-                        }
+        if let Some(inter,) = intersections {
+            for i in inter {
+                match i.hit_side {
+                    Side::Left => {
+                        self.set_x(player_pos.x + i.amount,);
+                    }
+                    Side::Right => {
+                        self.set_x(player_pos.x - i.amount,);
+                    }
+                    Side::Top => {
+                        self.set_y(player_pos.y - i.amount,);
+                    }
+                    Side::Bottom => {
+                        self.set_y(player_pos.y + i.amount,);
                     }
                 }
             }
-            None => {}
         }
     }
 }
@@ -134,11 +210,32 @@ impl Collider for Player {
 
 impl Drawable for Player {
     fn get_color(&self) -> Color {
+        let mut a = self.color.a;
+        if self.is_invulnerable() {
+            a = 0.5;
+        }
         Color {
             r: self.color.r,
             g: self.color.g,
             b: self.color.b,
-            a: self.color.a,
+            a,
         }
     }
 }
+
+#[test]
+fn test_hit_and_lives() {
+    let mut p = Player::new(1, Vector2d::new(0.0, 0.0,),);
+    assert_eq!(p.get_lives(), START_LIVES);
+    assert!(p.hit());
+    // still invulnerable from the first hit
+    assert!(!p.hit());
+    let mut bullets = Vec::new();
+    let input = GameInput::new();
+    p.update(&input, Vector2d::new(0.0, 0.0,), &mut bullets, 1.1,);
+    assert!(p.hit());
+    p.update(&input, Vector2d::new(0.0, 0.0,), &mut bullets, 1.1,);
+    assert!(p.hit());
+    assert!(!p.is_alive());
+    assert_eq!(p.get_lives(), 0);
+}
@@ -1,6 +1,9 @@
 use entities::{BoundingBox, Collider, Color, Drawable, Position};
 use math::vector::Vector2d;
+use tunables::tunables;
 
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct Wall {
     pos: Vector2d,
     width: f32,
@@ -14,12 +17,7 @@ impl Wall {
             pos,
             width,
             height,
-            color: Color {
-                r: 1.0,
-                g: 0.1,
-                b: 0.1,
-                a: 1.0,
-            },
+            color: tunables().wall_color,
         }
     }
 }
@@ -0,0 +1,264 @@
+//! Screens around the game itself: title, pause menu and game over. Confirm is space or the
+//! left mouse button, back is the quit key. Both only count on the frame they are pressed, so
+//! holding a key does not skip through several screens.
+
+use draw::{DrawCommand, DrawList, Shape, LAYER_HUD};
+use entities::Color;
+use font::{push_text_centered, GLYPH_HEIGHT};
+use GameInput;
+
+/// Menus are drawn on top of the hud.
+pub const LAYER_MENU: i32 = LAYER_HUD + 100;
+
+/// What the game is doing after a frame, returned by the game loops.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GameStatus {
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+    /// The player asked to quit, the platform should close the game.
+    Quit,
+}
+
+impl GameStatus {
+    pub fn should_quit(&self) -> bool {
+        *self == GameStatus::Quit
+    }
+}
+
+/// How a finished game went.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Summary {
+    /// Indexed by player slot.
+    pub scores: Vec<u32,>,
+    /// Seconds of game time.
+    pub time: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub enum Screen {
+    Title,
+    Playing,
+    Paused,
+    GameOver(Summary),
+}
+
+/// What the game loop has to do after a screen change.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FlowCommand {
+    /// Throw away the current game and start a fresh one.
+    NewGame,
+    Quit,
+}
+
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct GameFlow {
+    screen: Screen,
+    confirm_held: bool,
+    back_held: bool,
+}
+
+impl GameFlow {
+    /// Starts on the title screen.
+    pub fn new() -> GameFlow {
+        GameFlow {
+            screen: Screen::Title,
+            confirm_held: false,
+            back_held: false,
+        }
+    }
+
+    pub fn get_screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn status(&self) -> GameStatus {
+        match self.screen {
+            Screen::Title => GameStatus::Title,
+            Screen::Playing => GameStatus::Playing,
+            Screen::Paused => GameStatus::Paused,
+            Screen::GameOver(_,) => GameStatus::GameOver,
+        }
+    }
+
+    /// Moves between screens on the keys pressed by any player this frame.
+    ///
+    /// - title: confirm starts a new game, back quits
+    /// - playing: back pauses
+    /// - paused: confirm resumes, back goes to the title
+    /// - game over: confirm starts a new game, back goes to the title
+    pub fn update(&mut self, inputs: &[GameInput],) -> Option<FlowCommand,> {
+        let confirm = inputs.iter().any(|i| i.space || i.mouse_left,);
+        let back = inputs.iter().any(|i| i.quit_key,);
+        let confirm_pressed = confirm && !self.confirm_held;
+        let back_pressed = back && !self.back_held;
+        self.confirm_held = confirm;
+        self.back_held = back;
+
+        let (next, command,) = match self.screen {
+            Screen::Title if back_pressed => (None, Some(FlowCommand::Quit,),),
+            Screen::Title | Screen::GameOver(_,) if confirm_pressed => {
+                (Some(Screen::Playing,), Some(FlowCommand::NewGame,),)
+            }
+            Screen::Playing if back_pressed => (Some(Screen::Paused,), None,),
+            Screen::Paused if confirm_pressed => (Some(Screen::Playing,), None,),
+            Screen::Paused | Screen::GameOver(_,) if back_pressed => (Some(Screen::Title,), None,),
+            _ => (None, None,),
+        };
+        if let Some(next,) = next {
+            self.screen = next;
+        }
+        command
+    }
+
+    /// Shows the summary, called when every player is dead.
+    pub fn game_over(&mut self, summary: Summary,) {
+        if self.screen == Screen::Playing {
+            self.screen = Screen::GameOver(summary,);
+        }
+    }
+
+    /// Menu of the current screen, nothing while playing. `width` and `height` are the view
+    /// size.
+    pub fn draw(&self, list: &mut DrawList, width: f32, height: f32,) {
+        let center = width / 2.0;
+        let top = height * 0.7;
+        match self.screen {
+            Screen::Title => {
+                dim(list, width, height, 1.0,);
+                push_text_centered(list, LAYER_MENU, "SHOOTER", center, top, 12.0, white(),);
+                prompt(list, "PRESS SPACE TO START", center, top - 150.0,);
+                push_text_centered(
+                    list,
+                    LAYER_MENU,
+                    "ESC TO QUIT",
+                    center,
+                    top - 200.0,
+                    3.0,
+                    grey(),
+                );
+            }
+            Screen::Playing => {}
+            Screen::Paused => {
+                dim(list, width, height, 0.6,);
+                push_text_centered(list, LAYER_MENU, "PAUSED", center, top, 10.0, white(),);
+                prompt(list, "SPACE TO RESUME", center, top - 120.0,);
+                push_text_centered(
+                    list,
+                    LAYER_MENU,
+                    "ESC FOR TITLE",
+                    center,
+                    top - 170.0,
+                    3.0,
+                    grey(),
+                );
+            }
+            Screen::GameOver(ref summary,) => {
+                dim(list, width, height, 0.6,);
+                push_text_centered(list, LAYER_MENU, "GAME OVER", center, top, 10.0, white(),);
+                let mut line = top - 90.0;
+                let seconds = summary.time as u32;
+                let time = format!("TIME {}:{:02}", seconds / 60, seconds % 60);
+                push_text_centered(list, LAYER_MENU, &time, center, line, 4.0, white(),);
+                for (slot, score,) in summary.scores.iter().enumerate() {
+                    line -= (GLYPH_HEIGHT as f32 + 3.0) * 4.0;
+                    let text = format!("PLAYER {} SCORE {}", slot + 1, score);
+                    push_text_centered(list, LAYER_MENU, &text, center, line, 4.0, white(),);
+                }
+                prompt(list, "SPACE TO PLAY AGAIN", center, line - 60.0,);
+            }
+        }
+    }
+}
+
+impl Default for GameFlow {
+    fn default() -> GameFlow {
+        GameFlow::new()
+    }
+}
+
+/// Darkens everything below the menu. An alpha of 1 hides it.
+fn dim(list: &mut DrawList, width: f32, height: f32, alpha: f32,) {
+    list.push(DrawCommand {
+        layer: LAYER_MENU - 1,
+        min_x: 0.0,
+        min_y: 0.0,
+        max_x: width,
+        max_y: height,
+        shape: Shape::Rectangle {
+            color: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: alpha,
+            },
+        },
+    },);
+}
+
+/// The line that tells the player what to press.
+fn prompt(list: &mut DrawList, text: &str, center: f32, top: f32,) {
+    push_text_centered(list, LAYER_MENU, text, center, top, 4.0, white(),);
+}
+
+fn white() -> Color {
+    Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    }
+}
+
+fn grey() -> Color {
+    Color {
+        r: 0.6,
+        g: 0.6,
+        b: 0.6,
+        a: 1.0,
+    }
+}
+
+#[test]
+fn test_screen_transitions() {
+    let mut flow = GameFlow::new();
+    let mut space = GameInput::new();
+    space.space = true;
+    let mut escape = GameInput::new();
+    escape.quit_key = true;
+    let none = GameInput::new();
+    assert_eq!(flow.status(), GameStatus::Title);
+
+    assert_eq!(flow.update(&[space],), Some(FlowCommand::NewGame));
+    assert_eq!(flow.status(), GameStatus::Playing);
+    // still held, not pressed again
+    assert_eq!(flow.update(&[space],), None);
+
+    assert_eq!(flow.update(&[escape],), None);
+    assert_eq!(flow.status(), GameStatus::Paused);
+    assert_eq!(flow.update(&[escape],), None);
+    assert_eq!(flow.status(), GameStatus::Paused);
+    // any player can resume
+    assert_eq!(flow.update(&[none, space],), None);
+    assert_eq!(flow.status(), GameStatus::Playing);
+
+    let summary = Summary {
+        scores: vec![3],
+        time: 65.0,
+    };
+    flow.game_over(summary.clone(),);
+    assert_eq!(flow.get_screen(), &Screen::GameOver(summary));
+    assert_eq!(flow.update(&[none],), None);
+    assert_eq!(flow.update(&[space],), Some(FlowCommand::NewGame));
+    assert_eq!(flow.status(), GameStatus::Playing);
+
+    flow.update(&[escape],);
+    flow.update(&[none],);
+    flow.update(&[escape],);
+    assert_eq!(flow.status(), GameStatus::Title);
+    flow.update(&[none],);
+    assert_eq!(flow.update(&[escape],), Some(FlowCommand::Quit));
+}
@@ -0,0 +1,113 @@
+//! Tiny block font for menus and the hud, drawn as one rectangle per lit pixel so it works on
+//! every platform without a texture.
+
+use draw::{DrawCommand, DrawList, Shape};
+use entities::Color;
+
+/// Glyphs are this many pixels wide and high, with one pixel between letters.
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+
+/// Rows top to bottom, the highest of the three bits is the left pixel.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        ' ' => [0; 5],
+        // anything else shows as a box so it is easy to spot
+        _ => [0b111, 0b101, 0b101, 0b101, 0b111],
+    }
+}
+
+/// Width of `text` drawn with pixels `scale` units big.
+pub fn text_width(text: &str, scale: f32,) -> f32 {
+    let count = text.chars().count() as f32;
+    if count == 0.0 {
+        return 0.0;
+    }
+    (count * (GLYPH_WIDTH + 1) as f32 - 1.0) * scale
+}
+
+/// Draws `text` with its top left corner at `left`, `top`, in view coordinates.
+pub fn push_text(
+    list: &mut DrawList,
+    layer: i32,
+    text: &str,
+    left: f32,
+    top: f32,
+    scale: f32,
+    color: Color,
+) {
+    for (index, c,) in text.chars().enumerate() {
+        let x = left + (index as u32 * (GLYPH_WIDTH + 1)) as f32 * scale;
+        for (row, bits,) in glyph(c,).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                let min_x = x + column as f32 * scale;
+                let max_y = top - row as f32 * scale;
+                list.push(DrawCommand {
+                    layer,
+                    min_x,
+                    min_y: max_y - scale,
+                    max_x: min_x + scale,
+                    max_y,
+                    shape: Shape::Rectangle { color, },
+                },);
+            }
+        }
+    }
+}
+
+/// `push_text` centered on `center_x`.
+pub fn push_text_centered(
+    list: &mut DrawList,
+    layer: i32,
+    text: &str,
+    center_x: f32,
+    top: f32,
+    scale: f32,
+    color: Color,
+) {
+    let left = center_x - text_width(text, scale,) / 2.0;
+    push_text(list, layer, text, left, top, scale, color,);
+}
@@ -0,0 +1,195 @@
+//! Plays games through `game_loop_players` on arbitrary inputs and deltas and checks
+//! `GameState::check_invariants` after every frame. The property tests below generate the
+//! games, the cargo-fuzz target in `game-core/fuzz` builds them from raw bytes with `run_bytes`.
+
+use entities::Color;
+use math::vector::Vector2d;
+use sound::SoundEvent;
+use sprite::{Flip, Sprite};
+use std::{cell::Cell, ptr};
+use {game_init_players, game_loop_players, set_debug_overlay, GameInput, Platform, GAME_STATE};
+
+/// Deltas a platform could hand over besides ordinary frame times.
+pub const ODD_DELTAS: [f32; 7] = [0.0, -0.016, 1.0, 1.0e6, f32::MAX, f32::INFINITY, f32::NAN];
+/// Bytes `run_bytes` reads for one player's input.
+pub const INPUT_BYTES: usize = 8;
+
+/// An input per player slot and the delta of one frame.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub inputs: Vec<GameInput,>,
+    pub delta: f32,
+}
+
+thread_local!(static RANDOM_STATE: Cell<u64,> = const { Cell::new(1,) });
+
+fn random() -> f32 {
+    // xorshift64*
+    RANDOM_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x,);
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d,) >> 40) as f32 / (1u64 << 24) as f32
+    },)
+}
+
+/// Draws and plays nothing, the random numbers come from `RANDOM_STATE`.
+fn platform() -> Platform {
+    fn rectangle(_: f32, _: f32, _: f32, _: f32, _: Color,) {}
+    fn sprite(_: Sprite, _: f32, _: f32, _: f32, _: f32, _: Color, _: Flip,) {}
+    fn quad(_: [Vector2d; 4], _: Color,) {}
+    Platform {
+        random,
+        log: |_| {},
+        start_frame: || {},
+        end_frame: || {},
+        draw_rectangle: rectangle,
+        play_sound: |_: SoundEvent| {},
+        draw_sprite: sprite,
+        draw_quad: quad,
+        now: || 0.0,
+    }
+}
+
+/// Starts a game like a platform would, with the debug overlay on so its drawing runs too,
+/// and plays `frames` through `game_loop_players`. The invariants are checked after every
+/// frame, the error names the frame. Stops early when the game quits.
+pub fn run(player_count: usize, seed: u64, frames: &[Frame],) -> Result<(), String,> {
+    // xorshift gets stuck on 0
+    RANDOM_STATE.with(|state| state.set(seed.max(1,),),);
+    game_init_players(960.0, 540.0, player_count, platform(),);
+    set_debug_overlay(true,);
+    let mut time_elapsed = 0.0;
+    for (index, frame,) in frames.iter().enumerate() {
+        time_elapsed += frame.delta;
+        let status = game_loop_players(frame.inputs.clone(), time_elapsed, frame.delta,);
+        match unsafe { &*ptr::addr_of!(GAME_STATE) } {
+            Some(ref gs,) => gs.check_invariants().map_err(|e| format!("frame {}: {}", index, e),)?,
+            None => return Err(format!("frame {}: the game is gone", index),),
+        }
+        if status.should_quit() {
+            break;
+        }
+    }
+    Ok((),)
+}
+
+/// A game from fuzzer bytes: the player count, an eight byte seed, then per frame a byte for
+/// the delta and `INPUT_BYTES` per player. Runs out of bytes at the end.
+pub fn run_bytes(data: &[u8]) -> Result<(), String,> {
+    if data.len() < 9 {
+        return Ok((),);
+    }
+    let player_count = 1 + data[0] as usize % 4;
+    let mut seed = [0; 8];
+    seed.copy_from_slice(&data[1..9],);
+    let frame_bytes = 1 + player_count * INPUT_BYTES;
+    let frames: Vec<Frame,> = data[9..]
+        .chunks(frame_bytes,)
+        .filter(|chunk| chunk.len() == frame_bytes,)
+        .map(|chunk| Frame {
+            inputs: chunk[1..].chunks(INPUT_BYTES,).map(input_from_bytes,).collect(),
+            delta: delta_from_byte(chunk[0],),
+        },)
+        .collect();
+    run(player_count, u64::from_le_bytes(seed,), &frames,)
+}
+
+/// The first few values are `ODD_DELTAS`, the rest spread over ordinary frame times.
+pub fn delta_from_byte(byte: u8) -> f32 {
+    match ODD_DELTAS.get(byte as usize,) {
+        Some(delta,) => *delta,
+        None => f32::from(byte,) / 255.0 * 0.05,
+    }
+}
+
+/// Keys and buttons from bit flags, sticks from signed bytes with two of them standing for NaN
+/// and infinity, the mouse anywhere around the screen.
+pub fn input_from_bytes(bytes: &[u8]) -> GameInput {
+    let byte = |index: usize| bytes.get(index,).cloned().unwrap_or(0,);
+    let bit = |index: usize| byte(index / 8,) & (1 << (index % 8)) != 0;
+    let axis = |index: usize| match byte(index,) {
+        0x80 => f32::NAN,
+        0x81 => f32::INFINITY,
+        b => f32::from(b as i8,) / 100.0,
+    };
+    let mut input = GameInput::new();
+    input.up_key = bit(0,);
+    input.down_key = bit(1,);
+    input.left_key = bit(2,);
+    input.right_key = bit(3,);
+    input.shoot_up = bit(4,);
+    input.shoot_down = bit(5,);
+    input.shoot_left = bit(6,);
+    input.shoot_right = bit(7,);
+    input.space = bit(8,);
+    input.mouse_left = bit(9,);
+    input.mouse_right = bit(10,);
+    input.move_x = axis(2,);
+    input.move_y = axis(3,);
+    input.aim_x = axis(4,);
+    input.aim_y = axis(5,);
+    input.mouse_x = f32::from(byte(6,),) * 4.0 - 32.0;
+    input.mouse_y = f32::from(byte(7,),) * 2.5 - 32.0;
+    input
+}
+
+#[cfg(test)]
+mod properties {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn axis() -> impl Strategy<Value = f32,> {
+        prop_oneof![
+            8 => -1.5f32..1.5,
+            1 => Just(f32::NAN),
+            1 => Just(f32::INFINITY),
+        ]
+    }
+
+    fn input() -> impl Strategy<Value = GameInput,> {
+        (any::<u16>(), axis(), axis(), axis(), axis(), -100f32..1100.0, -100f32..700.0,).prop_map(
+            |(bits, move_x, move_y, aim_x, aim_y, mouse_x, mouse_y,)| {
+                let mut input = input_from_bytes(&bits.to_le_bytes(),);
+                input.move_x = move_x;
+                input.move_y = move_y;
+                input.aim_x = aim_x;
+                input.aim_y = aim_y;
+                input.mouse_x = mouse_x;
+                input.mouse_y = mouse_y;
+                input
+            },
+        )
+    }
+
+    fn delta() -> impl Strategy<Value = f32,> {
+        prop_oneof![
+            8 => 0f32..0.05,
+            1 => proptest::sample::select(ODD_DELTAS.to_vec(),),
+        ]
+    }
+
+    /// Up to one input more than there are players, a few hundred frames.
+    fn frames() -> impl Strategy<Value = Vec<Frame,>,> {
+        proptest::collection::vec(
+            (proptest::collection::vec(input(), 0..=5,), delta(),)
+                .prop_map(|(inputs, delta,)| Frame { inputs, delta, },),
+            1..300,
+        )
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+        #[test]
+        fn test_simulation_invariants(
+            player_count in 1usize..=4,
+            seed in any::<u64>(),
+            frames in frames(),
+        ) {
+            let _globals = ::TEST_GLOBALS.lock().unwrap_or_else(|e| e.into_inner(),);
+            prop_assert_eq!(run(player_count, seed, &frames,), Ok(()));
+        }
+    }
+}
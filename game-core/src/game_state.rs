@@ -1,107 +1,531 @@
-use draw_rectangle;
+use camera::Camera;
+use debug::{
+    collider_color, contact_color, velocity_color, Contact, DebugStats, LAYER_DEBUG,
+    VELOCITY_SCALE,
+};
+use draw::{
+    DrawCommand, DrawList, Shape, LAYER_BULLETS, LAYER_ENEMIES, LAYER_HUD, LAYER_PLAYERS,
+    LAYER_WALLS,
+};
 use end_frame;
 use entities::{
     bullet::Bullet,
-    cooldown::Cooldown,
     enemies::{Enemy, EnemyType},
-    player::Player,
+    player::{Player, MAX_PLAYERS},
     wall::Wall,
-    Collider, Drawable, Intersection, Position, Side,
+    BoundingBox, Collider, Color, Drawable, Intersection, Position, Side,
 };
-use get_random;
-use math::vector::Vector2d;
+use find_sprite;
+use flow::Summary;
+use math::{matrix::Mat3, vector::Vector2d};
+use nav::{NavGrid, Navigation};
+use play_sound;
+use rng::Rng;
+use scheduler::{Scheduler, TimerId};
+#[cfg(feature = "scripting")]
+use script::{self, WaveInfo};
+use sound::{Sound, SoundEvent};
+use sprite::Flip;
+use steering::Agent;
 use start_frame;
+use transform::{quad_corners, Hierarchy, Transform};
+use tunables::tunables;
 use GameInput;
 use GameTime;
 
+/// Cell size of the navigation grid enemies find their way on.
+const NAV_CELL_SIZE: f32 = 16.0;
+/// Half the biggest enemy, so enemies walking the grid do not scrape along walls.
+const NAV_CLEARANCE: f32 = 8.0;
+
+/// Longest step simulated in one frame. Longer frames, like after a breakpoint, slow the game
+/// down instead of moving everything through the walls.
+pub const MAX_DELTA: f32 = 0.25;
+/// Spawning stops at this many enemies.
+pub const MAX_ENEMIES: usize = 500;
+/// Bullets fired past this many in the air are dropped.
+pub const MAX_BULLETS: usize = 2000;
+
+/// Tries at finding a spawn position away from the players before taking one that is not.
+const SPAWN_ATTEMPTS: u32 = 100;
+
+/// Things the game state schedules for later.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub(crate) enum TimedEvent {
+    SpawnEnemy,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct GameState {
     frame: u32,
     time: GameTime,
-    player: Player,
+    players: Vec<Player,>,
     walls: Vec<Wall,>,
     bullets: Vec<Bullet,>,
     enemies: Vec<Enemy,>,
-    enemy_spawn: Cooldown,
+    timers: Scheduler<TimedEvent,>,
+    /// The repeating spawn timer and its interval, changed when the tunables change.
+    spawn_timer: TimerId,
+    spawn_interval: f32,
+    nav: Navigation,
+    /// Wave number kept for the wave script.
+    #[cfg(feature = "scripting")]
+    wave: i64,
     world_size_x: f32,
     world_size_y: f32,
+    camera: Camera,
+    rng: Rng,
+    /// Sounds started during the last simulated frame.
+    sounds: Vec<SoundEvent,>,
+    /// Last overlap the collision checks found, for the debug overlay.
+    last_contact: Option<Contact,>,
 }
 
 impl GameState {
-    pub fn update(&mut self, input: GameInput, time_elapsed: f32, delta: f32,) {
+    /// Simulates one frame and draws it.
+    /// `inputs` holds one input per player slot. Slots without input stand still.
+    pub fn update(&mut self, inputs: &[GameInput], time_elapsed: f32, delta: f32,) {
+        self.simulate(inputs, time_elapsed, delta,);
+        self.play_sounds();
+        self.draw(&[],);
+    }
+
+    /// Moves the game one frame forward without drawing anything. `delta` is clamped to
+    /// `0..=MAX_DELTA`, a delta that is not a number counts as 0.
+    pub fn simulate(&mut self, inputs: &[GameInput], time_elapsed: f32, delta: f32,) {
+        profile_scope!("simulate");
+        self.sounds.clear();
         self.frame += 1;
-        self.time.time_elapsed = time_elapsed;
-        self.time.delta = delta;
-        self.update_enemy_spawn();
+        if time_elapsed.is_finite() {
+            self.time.time_elapsed = time_elapsed;
+        }
+        self.time.delta = if delta.is_finite() { delta.clamp(0.0, MAX_DELTA,) } else { 0.0 };
+        self.update_timers();
         self.update_bullets();
         self.update_enemies();
-        self.player
-            .update(&input, &mut self.bullets, self.time.delta,);
-
-        let intersections = self.check_player_walls_intersections();
-        self.player.handle_collisions(intersections,);
+        self.update_players(inputs,);
+        self.bullets.truncate(MAX_BULLETS,);
+        self.check_collisions();
+    }
 
+    /// Removes what got hit this frame and scores the hits.
+    pub(crate) fn check_collisions(&mut self) {
+        profile_scope!("collisions");
         self.check_bullets_enemies_intersections();
+        self.check_bullets_players_intersections();
+        self.check_enemies_players_intersections();
+    }
 
+    /// Draws the current frame. Players are drawn moved by `player_offsets`, indexed by slot,
+    /// which is used to smooth out corrections after a network rollback.
+    pub fn draw(&self, player_offsets: &[Vector2d],) {
+        let list = self.draw_list(player_offsets,);
         start_frame();
+        list.submit();
+        end_frame();
+    }
+
+    /// Everything `draw` would draw, without calling the platform.
+    pub fn draw_list(&self, player_offsets: &[Vector2d],) -> DrawList {
+        profile_scope!("draw_list");
+        let mut list = DrawList::new();
+        // sprites are drawn untinted except for the players, who keep their slot colour
         for b in &self.bullets {
-            let rect = b.get_bounding_box();
-            draw_rectangle(rect.left, rect.bottom, rect.right, rect.top, b.get_color(),);
+            self.draw_rotated(&mut list, LAYER_BULLETS, "bullet", b,);
         }
-        {
-            let rect = self.player.get_bounding_box();
-            draw_rectangle(
-                rect.left,
-                rect.bottom,
-                rect.right,
-                rect.top,
-                self.player.get_color(),
+        for p in self.players.iter().filter(|p| p.is_alive(),) {
+            let offset = player_offsets.get(p.get_slot(),).cloned().unwrap_or_default();
+            let mut rect = p.get_bounding_box();
+            rect.left += offset.x;
+            rect.right += offset.x;
+            rect.top += offset.y;
+            rect.bottom += offset.y;
+            let shape = entity_shape("player", p.get_color(), true, p.is_facing_left(),);
+            self.draw_shape(&mut list, LAYER_PLAYERS, &rect, shape,);
+
+            // the barrel hangs off a pivot in the middle of the player that turns with the aim
+            let mut hierarchy = Hierarchy::new();
+            let body = hierarchy.add_root(Transform::at(p.get_position() + offset,),);
+            let pivot = hierarchy.add_child(
+                body,
+                Transform::new(Vector2d::zero(), p.get_aim().angle(), Vector2d::new(1.0, 1.0,),),
+            );
+            let barrel = hierarchy.add_child(
+                pivot,
+                Transform::new(Vector2d::new(7.0, 0.0,), 0.0, Vector2d::new(6.0, 2.0,),),
             );
+            let matrix = hierarchy.world_matrix(barrel,);
+            self.draw_quad(&mut list, LAYER_PLAYERS + 1, &matrix, p.get_color(),);
         }
         for e in &self.enemies {
-            let rect = e.get_bounding_box();
-            draw_rectangle(rect.left, rect.bottom, rect.right, rect.top, e.get_color(),);
+            self.draw_rotated(&mut list, LAYER_ENEMIES, "enemy", e,);
         }
         for w in &self.walls {
-            let rect = w.get_bounding_box();
-            draw_rectangle(rect.left, rect.bottom, rect.right, rect.top, w.get_color(),);
+            self.draw_box(&mut list, LAYER_WALLS, &w.get_bounding_box(), w.get_color(),);
         }
-        end_frame();
+        self.draw_lives(&mut list,);
+        list
     }
 
-    fn update_enemy_spawn(&mut self) {
-        self.enemy_spawn.update(self.time.delta,);
-        if self.enemy_spawn.is_elapsed() {
-            self.spawn_enemy();
-            self.enemy_spawn.restart();
+    /// Collider outlines, velocity arrows and the last contact, on top of the game.
+    pub fn draw_debug(&self, list: &mut DrawList,) {
+        let mut colliders: Vec<(BoundingBox, Vector2d, Vector2d,),> = Vec::new();
+        for p in self.players.iter().filter(|p| p.is_alive(),) {
+            colliders.push((p.get_bounding_box(), p.get_position(), p.get_velocity(),),);
+        }
+        for e in &self.enemies {
+            colliders.push((e.get_bounding_box(), e.get_position(), e.get_velocity(),),);
+        }
+        for b in &self.bullets {
+            colliders.push((b.get_bounding_box(), b.get_position(), b.get_velocity(),),);
+        }
+        for w in &self.walls {
+            colliders.push((w.get_bounding_box(), w.get_position(), Vector2d::zero(),),);
+        }
+        for (rect, position, velocity,) in colliders {
+            let rect = self.camera.world_to_view(&rect,);
+            let min = Vector2d::new(rect.left, rect.bottom,);
+            let max = Vector2d::new(rect.right, rect.top,);
+            list.push_outline(LAYER_DEBUG, min, max, 1.0, collider_color(),);
+            let from = self.camera.world_to_view_point(position,);
+            let to = self.camera.world_to_view_point(position + velocity * VELOCITY_SCALE,);
+            list.push_line(LAYER_DEBUG, from, to, 1.0, velocity_color(),);
+        }
+
+        // a cross on the contact and a line as long as the overlap, pointing the way it pushed
+        if let Some(contact,) = self.last_contact {
+            let center = self.camera.world_to_view_point(contact.position,);
+            let size = Vector2d::new(4.0, 4.0,);
+            let flipped = Vector2d::new(4.0, -4.0,);
+            list.push_line(LAYER_DEBUG, center - size, center + size, 1.0, contact_color(),);
+            list.push_line(LAYER_DEBUG, center - flipped, center + flipped, 1.0, contact_color(),);
+            let push = match contact.side {
+                Side::Left => Vector2d::new(1.0, 0.0,),
+                Side::Right => Vector2d::new(-1.0, 0.0,),
+                Side::Top => Vector2d::new(0.0, -1.0,),
+                Side::Bottom => Vector2d::new(0.0, 1.0,),
+            };
+            let end = center + push * contact.amount.max(4.0,);
+            list.push_line(LAYER_DEBUG, center, end, 2.0, contact_color(),);
         }
     }
 
+    /// Counts for the debug overlay.
+    pub fn debug_stats(&self) -> DebugStats {
+        DebugStats {
+            players: self.players.iter().filter(|p| p.is_alive(),).count(),
+            enemies: self.enemies.len(),
+            bullets: self.bullets.len(),
+            walls: self.walls.len(),
+            contact: self.last_contact,
+        }
+    }
+
+    /// What must hold after every frame whatever the input, for `fuzz`. Says what broke.
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub fn check_invariants(&self) -> Result<(), String,> {
+        let finite = |v: Vector2d| v.x.is_finite() && v.y.is_finite();
+        for p in &self.players {
+            let pos = p.get_position();
+            if !finite(pos,) || !finite(p.get_velocity(),) || !finite(p.get_aim(),) {
+                return Err(format!("player {} at {:?} is not finite", p.get_slot(), pos),);
+            }
+            let in_world = pos.x >= 0.0
+                && pos.x <= self.world_size_x
+                && pos.y >= 0.0
+                && pos.y <= self.world_size_y;
+            if !in_world {
+                return Err(format!("player {} at {:?} left the world", p.get_slot(), pos),);
+            }
+        }
+        for e in &self.enemies {
+            if !finite(e.get_position(),) || !finite(e.get_velocity(),) {
+                return Err(format!("enemy at {:?} is not finite", e.get_position()),);
+            }
+        }
+        for b in &self.bullets {
+            if !finite(b.get_position(),) || !finite(b.get_velocity(),) {
+                return Err(format!("bullet at {:?} is not finite", b.get_position()),);
+            }
+        }
+        if self.enemies.len() > MAX_ENEMIES {
+            return Err(format!("{} enemies, more than {}", self.enemies.len(), MAX_ENEMIES),);
+        }
+        if self.bullets.len() > MAX_BULLETS {
+            return Err(format!("{} bullets, more than {}", self.bullets.len(), MAX_BULLETS),);
+        }
+        Ok((),)
+    }
+
+    /// Hands the sounds of the last simulated frame to the platform.
+    pub fn play_sounds(&self) {
+        for sound in &self.sounds {
+            play_sound(*sound,);
+        }
+    }
+
+    pub fn player_positions(&self) -> Vec<Vector2d,> {
+        self.players.iter().map(|p| p.get_position(),).collect()
+    }
+
+    /// True when every player has run out of lives.
+    pub fn is_game_over(&self) -> bool {
+        self.players.iter().all(|p| !p.is_alive(),)
+    }
+
+    /// Scores and play time, for the game over screen.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            scores: self.players.iter().map(|p| p.get_score(),).collect(),
+            time: self.timers.get_time(),
+        }
+    }
+
+    /// A new game in a world of the same size with as many players.
+    pub fn restarted(&self, seed: u64,) -> GameState {
+        GameState::new(self.world_size_x, self.world_size_y, self.players.len(), seed,)
+    }
+
+    pub fn get_world_size(&self) -> Vector2d {
+        Vector2d::new(self.world_size_x, self.world_size_y,)
+    }
+
+    fn update_players(&mut self, inputs: &[GameInput],) {
+        profile_scope!("players");
+        let no_input = GameInput::new();
+        for slot in 0..self.players.len() {
+            if !self.players[slot].is_alive() {
+                continue;
+            }
+            let input = inputs.get(slot,).unwrap_or(&no_input,);
+            let cursor = self.camera.screen_to_world(input.mouse_x, input.mouse_y,);
+            let bullet_count = self.bullets.len();
+            self.players[slot].update(input, cursor, &mut self.bullets, self.time.delta,);
+            if self.bullets.len() > bullet_count {
+                // every player's gun gets its own pitch
+                let pitch = 1.0 + slot as f32 * 0.12;
+                self.sounds.push(SoundEvent::new(Sound::Shot, pitch, 0.5,),);
+            }
+
+            let intersections = self.check_player_walls_intersections(&self.players[slot],);
+            if let Some(ref intersections,) = intersections {
+                let position = self.players[slot].get_position();
+                self.record_contact(position, &intersections[intersections.len() - 1],);
+            }
+            self.players[slot].handle_collisions(intersections,);
+        }
+    }
+
+    /// One small box per remaining life, a row per player in the top left corner.
+    fn draw_lives(&self, list: &mut DrawList,) {
+        for p in &self.players {
+            let top = self.world_size_y - 10.0 - p.get_slot() as f32 * 8.0;
+            for life in 0..p.get_lives() {
+                let left = 10.0 + life as f32 * 8.0;
+                let rect = BoundingBox {
+                    left,
+                    right: left + 5.0,
+                    top,
+                    bottom: top - 5.0,
+                };
+                self.draw_box(list, LAYER_HUD, &rect, p.get_color(),);
+            }
+        }
+    }
+
+    fn draw_box(&self, list: &mut DrawList, layer: i32, rect: &BoundingBox, color: Color,) {
+        self.draw_shape(list, layer, rect, Shape::Rectangle { color, },);
+    }
+
+    /// The entity's sprite if there is one, sprites are not rotated. Otherwise a box turned and
+    /// scaled by the entity's transform.
+    fn draw_rotated(
+        &self,
+        list: &mut DrawList,
+        layer: i32,
+        sprite_name: &str,
+        entity: &dyn Drawable,
+    ) {
+        match entity_shape(sprite_name, entity.get_color(), false, false,) {
+            Shape::Rectangle { color, } => {
+                let matrix = entity.get_transform().to_matrix();
+                self.draw_quad(list, layer, &matrix, color,);
+            }
+            shape => self.draw_shape(list, layer, &entity.get_bounding_box(), shape,),
+        }
+    }
+
+    /// The unit square centered on the origin, moved into the world by `matrix`.
+    fn draw_quad(&self, list: &mut DrawList, layer: i32, matrix: &Mat3, color: Color,) {
+        let mut corners = quad_corners(matrix,);
+        for c in corners.iter_mut() {
+            *c = self.camera.world_to_view_point(*c,);
+        }
+        list.push_quad(layer, corners, color,);
+    }
+
+    fn draw_shape(&self, list: &mut DrawList, layer: i32, rect: &BoundingBox, shape: Shape,) {
+        let rect = self.camera.world_to_view(rect,);
+        list.push(DrawCommand {
+            layer,
+            min_x: rect.left,
+            min_y: rect.bottom,
+            max_x: rect.right,
+            max_y: rect.top,
+            shape,
+        },);
+    }
+
+    fn update_timers(&mut self) {
+        profile_scope!("timers");
+        let interval = tunables().spawn_interval;
+        if interval != self.spawn_interval {
+            self.timers.cancel(self.spawn_timer,);
+            self.spawn_timer = self.timers.every(interval, TimedEvent::SpawnEnemy,);
+            self.spawn_interval = interval;
+        }
+        let scripted_waves = self.run_wave_script();
+        for event in self.timers.update(self.time.delta,) {
+            match event {
+                // a wave script does all the spawning
+                TimedEvent::SpawnEnemy => {
+                    if !scripted_waves {
+                        self.spawn_enemy();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Spawns what the wave script asks for. False when there is no wave script.
+    #[cfg(feature = "scripting")]
+    fn run_wave_script(&mut self) -> bool {
+        let mut info = WaveInfo {
+            time: self.timers.get_time(),
+            delta: self.time.delta,
+            enemy_count: self.enemies.len(),
+            wave: self.wave,
+            world_width: self.world_size_x,
+            world_height: self.world_size_y,
+        };
+        let spawns = match script::with_scripts(|s| s.update_waves(&mut info,),) {
+            Some(Some(spawns,),) => spawns,
+            _ => return false,
+        };
+        self.wave = info.wave;
+        for spawn in spawns {
+            if self.enemies.len() >= MAX_ENEMIES {
+                break;
+            }
+            let pos = match spawn.position {
+                Some(pos,) => pos,
+                None => self.random_spawn_position(),
+            };
+            let enemy = match spawn.behaviour {
+                Some(ref behaviour,) => Enemy::scripted(pos, behaviour,),
+                None => Enemy::new(EnemyType::Normal, pos,),
+            };
+            self.enemies.push(enemy,);
+        }
+        true
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn run_wave_script(&mut self) -> bool {
+        false
+    }
+
+    /// Now and then a whole swarm instead of a single enemy.
     fn spawn_enemy(&mut self) {
-        let mut x = get_random(5.0, self.world_size_x - 5.0,);
-        let mut y = get_random(5.0, self.world_size_y - 5.0,);
-        let mut rand_pos = Vector2d::new(x as f32, y as f32,);
-        rand_pos.sub(&self.player.get_position(),);
+        profile_scope!("spawn_enemy");
+        if self.enemies.len() >= MAX_ENEMIES {
+            return;
+        }
+        let pos = self.random_spawn_position();
+        let tunables = tunables();
+        if self.rng.next_f32() < tunables.swarm_chance {
+            for _ in 0..tunables.swarm_size.min(MAX_ENEMIES - self.enemies.len(),) {
+                let x = self.rng.range(-20.0, 20.0,);
+                let y = self.rng.range(-20.0, 20.0,);
+                let offset = Vector2d::new(x, y,);
+                self.enemies.push(Enemy::new(EnemyType::Swarm, pos + offset,),);
+            }
+        } else {
+            self.enemies.push(Enemy::new(EnemyType::Normal, pos,),);
+        }
+    }
 
-        while rand_pos.len() < 100.0 {
-            x = get_random(5.0, self.world_size_x - 5.0,);
-            y = get_random(5.0, self.world_size_y - 5.0,);
-            rand_pos = Vector2d::new(x as f32, y as f32,);
-            rand_pos.sub(&self.player.get_position(),);
+    /// Adds `enemies` enemies and `bullets` player bullets flying every way, at random spots
+    /// away from the players. Goes past `MAX_ENEMIES` and `MAX_BULLETS`, for `bench`.
+    #[cfg(any(test, feature = "benchmarks"))]
+    pub fn crowd(&mut self, enemies: usize, bullets: usize,) {
+        for index in 0..enemies {
+            let pos = self.random_spawn_position();
+            let enemy_type = if index % 4 == 0 { EnemyType::Swarm } else { EnemyType::Normal };
+            self.enemies.push(Enemy::new(enemy_type, pos,),);
+        }
+        for _ in 0..bullets {
+            let pos = self.random_spawn_position();
+            let angle = self.rng.range(0.0, 2.0 * std::f32::consts::PI,);
+            let direction = Vector2d::new(angle.cos(), angle.sin(),);
+            let owner = self.rng.next_u64() as usize % self.players.len();
+            self.bullets.push(Bullet::new(pos, direction, owner,),);
         }
+    }
 
-        self.enemies.push(Enemy::new(
-            EnemyType::Normal,
-            Vector2d::new(x as f32, y as f32,),
-        ),);
+    /// Somewhere in the world, not too close to any player unless the players cover it all.
+    fn random_spawn_position(&mut self) -> Vector2d {
+        let safe_radius = tunables().spawn_safe_radius;
+        let mut x = self.rng.range(5.0, self.world_size_x - 5.0,);
+        let mut y = self.rng.range(5.0, self.world_size_y - 5.0,);
+
+        let mut attempts = 1;
+        while self.distance_to_nearest_player(Vector2d::new(x, y,),) < safe_radius
+            && attempts < SPAWN_ATTEMPTS
+        {
+            attempts += 1;
+            x = self.rng.range(5.0, self.world_size_x - 5.0,);
+            y = self.rng.range(5.0, self.world_size_y - 5.0,);
+        }
+        Vector2d::new(x, y,)
+    }
+
+    fn distance_to_nearest_player(&self, pos: Vector2d,) -> f32 {
+        let mut nearest = f32::MAX;
+        for p in self.players.iter().filter(|p| p.is_alive(),) {
+            let mut diff = pos;
+            diff.sub(&p.get_position(),);
+            nearest = nearest.min(diff.len(),);
+        }
+        nearest
     }
 
     fn update_enemies(&mut self) {
+        profile_scope!("enemies");
+        let targets: Vec<Vector2d,> =
+            self.players.iter().filter(|p| p.is_alive(),).map(|p| p.get_position(),).collect();
+        let chasers = self.enemies.iter().filter(|e| e.get_behaviour().is_none(),).count();
+        self.nav.update_flow_fields(&targets, chasers,);
+        let flock: Vec<Agent,> =
+            self.enemies.iter().filter(|e| e.is_swarm(),).map(|e| e.get_agent(),).collect();
+        let mut flock_index = 0;
         for e in &mut self.enemies {
-            e.update(&self.player, self.time.delta,);
+            let own = if e.is_swarm() {
+                flock_index += 1;
+                Some(flock_index - 1,)
+            } else {
+                None
+            };
+            if !run_enemy_script(e, &self.players, self.time.delta, &mut self.bullets,) {
+                e.update(&self.players, &self.nav, &flock, own, self.time.delta,);
+            }
         }
     }
 
     fn update_bullets(&mut self) -> () {
+        profile_scope!("bullets");
         let mut bullets_to_delete: Vec<usize,> = Vec::new();
         let mut index: usize = 0;
 
@@ -125,9 +549,16 @@ impl GameState {
         }
     }
 
-    fn check_player_walls_intersections(&self) -> Option<Vec<Intersection,>,> {
+    fn record_contact(&mut self, position: Vector2d, intersection: &Intersection,) {
+        self.last_contact = Some(Contact {
+            position,
+            side: intersection.hit_side,
+            amount: intersection.amount,
+        },);
+    }
+
+    fn check_player_walls_intersections(&self, player: &Player,) -> Option<Vec<Intersection,>,> {
         let walls = &self.walls;
-        let player = &self.player;
         let mut results = Vec::new();
         for mut other_e in &mut walls.iter() {
             let intersection = check_intersection(player, other_e,);
@@ -152,10 +583,21 @@ impl GameState {
         let mut bullets_to_kill = Vec::new();
         for b in &self.bullets {
             enemy_index = 0;
+            if b.is_from_enemy() {
+                bullets_index += 1;
+                continue;
+            }
             for e in &self.enemies {
                 let intersection = check_intersection(b, e,);
                 match intersection {
                     Some(_,) => {
+                        if !enemies_to_kill.contains(&enemy_index,) {
+                            if let Some(p,) = self.players.get_mut(b.get_owner(),) {
+                                p.add_score(1,);
+                            }
+                            self.sounds
+                                .push(SoundEvent::new(Sound::EnemyKilled, 1.0, 0.8,),);
+                        }
                         enemies_to_kill.push(enemy_index,);
                         bullets_to_kill.push(bullets_index,);
                     }
@@ -166,6 +608,7 @@ impl GameState {
             bullets_index += 1;
         }
         if enemies_to_kill.len() > 0 {
+            enemies_to_kill.sort();
             enemies_to_kill.dedup();
             enemies_to_kill.reverse();
             for index_to_delete in enemies_to_kill {
@@ -173,6 +616,7 @@ impl GameState {
             }
         }
         if bullets_to_kill.len() > 0 {
+            bullets_to_kill.sort();
             bullets_to_kill.dedup();
             bullets_to_kill.reverse();
             for index_to_delete in bullets_to_kill {
@@ -181,8 +625,94 @@ impl GameState {
         }
     }
 
-    pub fn new(size_x: f32, size_y: f32,) -> GameState {
-        let player = Player::new();
+    fn check_bullets_players_intersections(&mut self) {
+        let mut bullets_to_kill = Vec::new();
+        let enemy_bullets = self.bullets.iter().enumerate().filter(|(_, b,)| b.is_from_enemy(),);
+        for (bullet_index, b,) in enemy_bullets {
+            for p in self.players.iter_mut().filter(|p| p.is_alive(),) {
+                if check_intersection(p, b,).is_some() {
+                    bullets_to_kill.push(bullet_index,);
+                    if p.hit() {
+                        let pitch = if p.is_alive() { 1.0 } else { 0.6 };
+                        self.sounds
+                            .push(SoundEvent::new(Sound::PlayerHit, pitch, 1.0,),);
+                    }
+                    break;
+                }
+            }
+        }
+        bullets_to_kill.reverse();
+        for index_to_delete in bullets_to_kill {
+            self.bullets.remove(index_to_delete,);
+        }
+    }
+
+    fn check_enemies_players_intersections(&mut self) {
+        let mut enemies_to_kill = Vec::new();
+        let mut contact = None;
+        for (enemy_index, e,) in self.enemies.iter().enumerate() {
+            for p in self.players.iter_mut().filter(|p| p.is_alive(),) {
+                if let Some(intersection,) = check_intersection(p, e,) {
+                    contact = Some((p.get_position(), intersection,),);
+                    if p.hit() {
+                        enemies_to_kill.push(enemy_index,);
+                        // deeper sound for the last life
+                        let pitch = if p.is_alive() { 1.0 } else { 0.6 };
+                        self.sounds
+                            .push(SoundEvent::new(Sound::PlayerHit, pitch, 1.0,),);
+                    }
+                    break;
+                }
+            }
+        }
+        if let Some((position, intersection,),) = contact {
+            self.record_contact(position, &intersection,);
+        }
+        enemies_to_kill.reverse();
+        for index_to_delete in enemies_to_kill {
+            self.enemies.remove(index_to_delete,);
+        }
+    }
+
+    /// Hash of everything that affects the simulation. Two states that started from the same
+    /// seed and got the same inputs have the same hash.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = StateHash::new();
+        hash.write_u64(self.frame as u64,);
+        hash.write_u64(self.rng.get_state(),);
+        for p in &self.players {
+            hash.write_box(&p.get_bounding_box(),);
+            hash.write_color(&p.get_color(),);
+            hash.write_u64(p.get_lives() as u64,);
+            hash.write_u64(p.get_score() as u64,);
+        }
+        hash.write_u64(self.enemies.len() as u64,);
+        for e in &self.enemies {
+            hash.write_box(&e.get_bounding_box(),);
+            hash.write_u64(e.is_swarm() as u64,);
+        }
+        hash.write_u64(self.bullets.len() as u64,);
+        for b in &self.bullets {
+            hash.write_box(&b.get_bounding_box(),);
+            hash.write_vector(b.get_velocity(),);
+            hash.write_color(&b.get_color(),);
+        }
+        hash.finish()
+    }
+
+    /// `player_count` is clamped to 1..=MAX_PLAYERS. All randomness in the simulation comes
+    /// from `seed`.
+    pub fn new(size_x: f32, size_y: f32, player_count: usize, seed: u64,) -> GameState {
+        let player_count = player_count.clamp(1, MAX_PLAYERS,);
+        let mut players = Vec::new();
+        for slot in 0..player_count {
+            // spread the players out on a line through the middle of the world
+            let offset = (slot as f32 - (player_count - 1) as f32 / 2.0) * 40.0;
+            players.push(Player::new(
+                slot,
+                Vector2d::new(960.0 / 2.0 + offset, 540.0 / 2.0,),
+            ),);
+        }
         let mut walls = Vec::new();
 
         walls.push(Wall::new(Vector2d::new(2.0, 540.0 / 2.0,), 4.0, 540.0,),);
@@ -201,20 +731,130 @@ impl GameState {
         let mut enemies = Vec::new();
 
         enemies.push(Enemy::new(EnemyType::Normal, Vector2d::new(100.0, 100.0,),),);
+        let wall_boxes: Vec<BoundingBox,> = walls.iter().map(|w| w.get_bounding_box(),).collect();
+        let nav = Navigation::new(NavGrid::new(
+            size_x,
+            size_y,
+            NAV_CELL_SIZE,
+            &wall_boxes,
+            NAV_CLEARANCE,
+        ),);
+        let mut timers = Scheduler::new();
+        let spawn_interval = tunables().spawn_interval;
+        let spawn_timer = timers.every(spawn_interval, TimedEvent::SpawnEnemy,);
         GameState {
             frame: 0,
             time: GameTime::new(),
-            player,
+            players,
             walls,
             bullets: Vec::new(),
             enemies,
-            enemy_spawn: Cooldown::new(0.25,),
+            timers,
+            spawn_timer,
+            spawn_interval,
+            nav,
+            #[cfg(feature = "scripting")]
+            wave: 0,
             world_size_x: size_x,
             world_size_y: size_y,
+            camera: Camera::new(size_x, size_y,),
+            rng: Rng::new(seed,),
+            sounds: Vec::new(),
+            last_contact: None,
         }
     }
 }
 
+/// FNV-1a over the raw bits of the values, so it is the same on every platform.
+struct StateHash {
+    hash: u64,
+}
+
+impl StateHash {
+    fn new() -> StateHash {
+        StateHash {
+            hash: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+
+    fn write_u64(&mut self, value: u64,) {
+        for i in 0..8 {
+            self.hash ^= (value >> (i * 8)) & 0xff;
+            self.hash = self.hash.wrapping_mul(0x0100_0000_01b3,);
+        }
+    }
+
+    fn write_f32(&mut self, value: f32,) {
+        self.write_u64(value.to_bits() as u64,);
+    }
+
+    fn write_vector(&mut self, v: Vector2d,) {
+        self.write_f32(v.x,);
+        self.write_f32(v.y,);
+    }
+
+    fn write_box(&mut self, rect: &BoundingBox,) {
+        self.write_f32(rect.left,);
+        self.write_f32(rect.right,);
+        self.write_f32(rect.top,);
+        self.write_f32(rect.bottom,);
+    }
+
+    fn write_color(&mut self, color: &Color,) {
+        self.write_f32(color.r,);
+        self.write_f32(color.g,);
+        self.write_f32(color.b,);
+        self.write_f32(color.a,);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Runs the script behaviour of `enemy`, false when it has none and moves by itself.
+#[cfg(feature = "scripting")]
+fn run_enemy_script(
+    enemy: &mut Enemy,
+    players: &[Player],
+    delta: f32,
+    bullets: &mut Vec<Bullet,>,
+) -> bool {
+    if enemy.get_behaviour().is_none() {
+        return false;
+    }
+    script::with_scripts(|s| s.update_enemy(enemy, players, delta, bullets,),).unwrap_or(false,)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn run_enemy_script(_: &mut Enemy, _: &[Player], _: f32, _: &mut Vec<Bullet,>,) -> bool {
+    false
+}
+
+/// The sprite called `sprite_name` if an atlas has one, otherwise a plain box.
+/// Sprites are only tinted with `color` when `tint` is set, they always take its alpha.
+fn entity_shape(sprite_name: &str, color: Color, tint: bool, flip_horizontal: bool,) -> Shape {
+    let sprite = match find_sprite(sprite_name,) {
+        Some(sprite,) => sprite,
+        None => return Shape::Rectangle { color, },
+    };
+    let tint = if tint {
+        color
+    } else {
+        Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+            a: color.a,
+        }
+    };
+    let flip = Flip {
+        horizontal: flip_horizontal,
+        vertical: false,
+    };
+    Shape::Sprite { sprite, tint, flip, }
+}
+
 fn check_intersection(player: &Collider, other: &Collider,) -> Option<Intersection,> {
     let player_bb = player.get_bounding_box();
     let other_bb = other.get_bounding_box();
@@ -270,3 +910,21 @@ fn check_intersection(player: &Collider, other: &Collider,) -> Option<Intersecti
     }
     None
 }
+
+#[test]
+fn test_state_hash_covers_enemy_type_and_bullet_velocity() {
+    let game = GameState::new(960.0, 540.0, 1, 1,);
+    let pos = Vector2d::new(100.0, 100.0,);
+
+    let mut normal = game.clone();
+    normal.enemies.push(Enemy::new(EnemyType::Normal, pos,),);
+    let mut swarm = game.clone();
+    swarm.enemies.push(Enemy::new(EnemyType::Swarm, pos,),);
+    assert_ne!(normal.state_hash(), swarm.state_hash());
+
+    let mut left = game.clone();
+    left.bullets.push(Bullet::new(pos, Vector2d::new(-1.0, 0.0,), 0,),);
+    let mut right = game;
+    right.bullets.push(Bullet::new(pos, Vector2d::new(1.0, 0.0,), 0,),);
+    assert_ne!(left.state_hash(), right.state_hash());
+}
@@ -0,0 +1,252 @@
+//! Hands a running game over from one build of game-core to the next, for hosts that load it as
+//! a dynamic library and swap it when it is rebuilt, see `linux-host`. The old build moves its
+//! globals into a `SavedGame` with `save`, the new build moves them back in with `load`.
+//!
+//! Both builds must agree on the layout of everything saved. `state_fingerprint` covers the
+//! name, size and fields of `SavedGame` and of every type reached through its fields, derived
+//! with `Layout`. A type saved anywhere inside it does not compile without the derive. Bump
+//! `STATE_VERSION` for changes the fields do not show, like what a value means. When the
+//! fingerprints differ the new build starts a fresh game instead.
+
+use action_map::ActionMap;
+use debug::DebugOverlay;
+use flow::GameFlow;
+use game_state::GameState;
+use sprite::Sprite;
+use std::any::type_name;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::os::raw::c_void;
+use std::path::PathBuf;
+use std::ptr;
+use std::rc::Rc;
+use std::time::SystemTime;
+use tunables::{TunablesFile, TUNABLES};
+use {Platform, ACTION_MAPS, DEBUG_OVERLAY, FLOW, GAME_STATE, PLATFORM, SPRITES};
+
+/// Version of the functions and types shared between a host and the library, `Platform`,
+/// `GameInput` and `GameStatus`. Hosts refuse libraries with another version.
+pub const ABI_VERSION: u64 = 1;
+/// Bump when what a saved field means changes, without its name or type changing.
+pub const STATE_VERSION: u64 = 1;
+
+/// The name, size and fields of a type, `#[derive(Layout)]` from game-derive writes it.
+pub trait Layout {
+    fn layout() -> String;
+    /// Adds `layout` of this type and of every type inside it, each once.
+    fn add_layouts(layouts: &mut BTreeSet<String,>,);
+}
+
+/// Types from std that hold nothing of game-core.
+macro_rules! plain_layout {
+    ($($ty:ty),*) => {$(
+        impl Layout for $ty {
+            fn layout() -> String {
+                type_name::<$ty,>().to_string()
+            }
+
+            fn add_layouts(_: &mut BTreeSet<String,>,) {}
+        }
+    )*};
+}
+
+plain_layout!(bool, u8, u16, u32, u64, usize, i32, i64, f32, f64, String, PathBuf, SystemTime);
+
+/// Containers from std, what they hold can be from game-core.
+macro_rules! container_layout {
+    ($($container:ident<$($param:ident),*>),*) => {$(
+        impl<$($param: Layout,)*> Layout for $container<$($param,)*> {
+            fn layout() -> String {
+                type_name::<$container<$($param,)*>,>().to_string()
+            }
+
+            fn add_layouts(layouts: &mut BTreeSet<String,>,) {
+                $($param::add_layouts(layouts,);)*
+            }
+        }
+    )*};
+}
+
+container_layout!(Option<T>, Vec<T>, VecDeque<T>, Box<T>, Rc<T>, HashSet<T>, HashMap<K, V>);
+
+impl<A: Layout, B: Layout,> Layout for (A, B,) {
+    fn layout() -> String {
+        type_name::<(A, B,),>().to_string()
+    }
+
+    fn add_layouts(layouts: &mut BTreeSet<String,>,) {
+        A::add_layouts(layouts,);
+        B::add_layouts(layouts,);
+    }
+}
+
+impl<T: Layout, const N: usize,> Layout for [T; N] {
+    fn layout() -> String {
+        type_name::<[T; N],>().to_string()
+    }
+
+    fn add_layouts(layouts: &mut BTreeSet<String,>,) {
+        T::add_layouts(layouts,);
+    }
+}
+
+/// The setters of tweens, only a pointer whatever `T` is.
+impl<T,> Layout for fn(&mut T, f32,) {
+    fn layout() -> String {
+        type_name::<fn(&mut T, f32,),>().to_string()
+    }
+
+    fn add_layouts(_: &mut BTreeSet<String,>,) {}
+}
+
+/// First in every `SavedGame`, so any build can read it.
+#[repr(C)]
+#[derive(Layout)]
+struct Header {
+    fingerprint: u64,
+}
+
+#[repr(C)]
+#[derive(Layout)]
+struct SavedGame {
+    header: Header,
+    game_state: Option<GameState,>,
+    flow: Option<GameFlow,>,
+    debug_overlay: Option<DebugOverlay,>,
+    action_maps: Option<Vec<ActionMap,>,>,
+    sprites: Option<Vec<(String, Sprite,),>,>,
+    tunables: Option<TunablesFile,>,
+}
+
+/// Changes when the layout of a saved game changes, or that of anything saved inside it.
+pub fn state_fingerprint() -> u64 {
+    let mut layouts = BTreeSet::new();
+    SavedGame::add_layouts(&mut layouts,);
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let bytes = STATE_VERSION
+        .to_le_bytes()
+        .to_vec()
+        .into_iter()
+        .chain(layouts.iter().flat_map(|layout| layout.bytes(),),);
+    for byte in bytes {
+        hash ^= u64::from(byte,);
+        hash = hash.wrapping_mul(0x0100_0000_01b3,);
+    }
+    hash
+}
+
+/// Moves the game out of the globals, for `load` of the next build. Null when there is no game.
+pub fn save() -> *mut c_void {
+    unsafe {
+        let game_state = take(ptr::addr_of_mut!(GAME_STATE),);
+        if game_state.is_none() {
+            return ptr::null_mut();
+        }
+        let saved = Box::new(SavedGame {
+            header: Header {
+                fingerprint: state_fingerprint(),
+            },
+            game_state,
+            flow: take(ptr::addr_of_mut!(FLOW),),
+            debug_overlay: take(ptr::addr_of_mut!(DEBUG_OVERLAY),),
+            action_maps: take(ptr::addr_of_mut!(ACTION_MAPS),),
+            sprites: take(ptr::addr_of_mut!(SPRITES),),
+            tunables: take(ptr::addr_of_mut!(TUNABLES),),
+        },);
+        Box::into_raw(saved,) as *mut c_void
+    }
+}
+
+/// Starts the game on `platform`, carrying on with `saved` from `save` when its layout matches
+/// this build. Returns false when a new game was started instead. A saved game that does not
+/// match is leaked, dropping it could run the wrong code on it.
+///
+/// # Safety
+///
+/// `saved` must be null or come from `save` of some build of game-core, and be used once.
+pub unsafe fn load(
+    platform: Platform,
+    size_x: f32,
+    size_y: f32,
+    player_count: usize,
+    saved: *mut c_void,
+) -> bool {
+    if !saved.is_null() {
+        let header = &*(saved as *const Header);
+        if header.fingerprint == state_fingerprint() {
+            let saved = *Box::from_raw(saved as *mut SavedGame,);
+            if saved.game_state.is_some() && saved.flow.is_some() && saved.debug_overlay.is_some()
+            {
+                PLATFORM = Some(platform,);
+                GAME_STATE = saved.game_state;
+                FLOW = saved.flow;
+                DEBUG_OVERLAY = saved.debug_overlay;
+                ACTION_MAPS = saved.action_maps;
+                SPRITES = saved.sprites;
+                TUNABLES = saved.tunables;
+                return true;
+            }
+        }
+    }
+    ::game_init_players(size_x, size_y, player_count, platform,);
+    false
+}
+
+unsafe fn take<T,>(global: *mut Option<T,>,) -> Option<T,> {
+    ptr::replace(global, None,)
+}
+
+#[test]
+fn test_layout_names_fields() {
+    use math::vector::Vector2d;
+    use tween::Ease;
+
+    assert_eq!(
+        Vector2d::layout(),
+        "game_core::math::vector::Vector2d{x: f32,y: f32} size 8 align 4"
+    );
+    assert!(GameState::layout().contains("enemies: Vec < Enemy >",));
+    // inside enemies and bullets, not named by any saved type
+    let mut layouts = BTreeSet::new();
+    SavedGame::add_layouts(&mut layouts,);
+    assert!(layouts.contains(&Ease::layout(),));
+    assert!(layouts.iter().any(|l| l.starts_with("game_core::tween::Animation<",),));
+}
+
+#[test]
+fn test_save_and_load() {
+    use entities::Color;
+    use math::vector::Vector2d;
+    use sound::SoundEvent;
+    use sprite::Flip;
+
+    fn platform() -> Platform {
+        fn rectangle(_: f32, _: f32, _: f32, _: f32, _: Color,) {}
+        fn sprite(_: Sprite, _: f32, _: f32, _: f32, _: f32, _: Color, _: Flip,) {}
+        fn quad(_: [Vector2d; 4], _: Color,) {}
+        Platform {
+            random: || 0.5,
+            log: |_| {},
+            start_frame: || {},
+            end_frame: || {},
+            draw_rectangle: rectangle,
+            play_sound: |_: SoundEvent| {},
+            draw_sprite: sprite,
+            draw_quad: quad,
+            now: || 0.0,
+        }
+    }
+
+    let _globals = ::TEST_GLOBALS.lock().unwrap_or_else(|e| e.into_inner(),);
+    unsafe {
+        assert!(!load(platform(), 960.0, 540.0, 2, ptr::null_mut(),));
+        let saved = save();
+        assert!(!saved.is_null());
+        assert!(save().is_null());
+        assert!(load(platform(), 960.0, 540.0, 2, saved,));
+
+        // made by a build with another layout
+        let foreign = Box::into_raw(Box::new(Header { fingerprint: 1, },),);
+        assert!(!load(platform(), 960.0, 540.0, 2, foreign as *mut c_void,));
+    }
+}
@@ -0,0 +1,92 @@
+use math::vector::Vector2d;
+use GameInput;
+
+/// Stick deflection below this magnitude is treated as no input.
+pub const STICK_DEADZONE: f32 = 0.2;
+
+/// Radial deadzone. Values inside the deadzone become zero, values outside are rescaled so the
+/// output still goes smoothly from 0 to 1. The result is clamped to the unit circle.
+pub fn apply_deadzone(x: f32, y: f32, deadzone: f32,) -> Vector2d {
+    let mut v = Vector2d::new(x, y,);
+    let len = v.len();
+    if !len.is_finite() || len <= deadzone {
+        return Vector2d::new(0.0, 0.0,);
+    }
+    let scaled = ((len - deadzone) / (1.0 - deadzone)).min(1.0,);
+    v.normalize();
+    v.mul(scaled,)
+}
+
+/// Turns four digital keys into a vector with the same range as a stick at full deflection.
+pub fn digital_vector(up: bool, down: bool, left: bool, right: bool,) -> Vector2d {
+    let mut v = Vector2d::new(0.0, 0.0,);
+    if up {
+        v.y += 1.0;
+    }
+    if down {
+        v.y -= 1.0;
+    }
+    if left {
+        v.x -= 1.0;
+    }
+    if right {
+        v.x += 1.0;
+    }
+    if v.len() > 0.0 {
+        v.normalize();
+    }
+    v
+}
+
+impl GameInput {
+    /// Movement direction, with length between 0 and 1.
+    /// The analog stick wins when it is outside the deadzone, otherwise the keys are used.
+    pub fn move_vector(&self) -> Vector2d {
+        let stick = apply_deadzone(self.move_x, self.move_y, STICK_DEADZONE,);
+        if stick.len() > 0.0 {
+            return stick;
+        }
+        digital_vector(self.up_key, self.down_key, self.left_key, self.right_key,)
+    }
+
+    /// Aim direction, with length between 0 and 1.
+    /// The analog stick wins when it is outside the deadzone, otherwise the keys are used.
+    pub fn aim_vector(&self) -> Vector2d {
+        let stick = apply_deadzone(self.aim_x, self.aim_y, STICK_DEADZONE,);
+        if stick.len() > 0.0 {
+            return stick;
+        }
+        digital_vector(
+            self.shoot_up,
+            self.shoot_down,
+            self.shoot_left,
+            self.shoot_right,
+        )
+    }
+}
+
+#[test]
+fn test_deadzone() {
+    let v = apply_deadzone(0.1, 0.1, 0.2,);
+    assert_eq!(v.len(), 0.0);
+    let v = apply_deadzone(1.0, 0.0, 0.2,);
+    assert_eq!(v.x, 1.0);
+    let v = apply_deadzone(0.6, 0.0, 0.2,);
+    assert!((v.x - 0.5).abs() < 0.0001);
+    let v = apply_deadzone(1.0, 1.0, 0.2,);
+    assert!((v.len() - 1.0).abs() < 0.0001);
+}
+
+#[test]
+fn test_keys_and_stick_share_path() {
+    let mut input = GameInput::new();
+    input.up_key = true;
+    input.right_key = true;
+    let keys = input.move_vector();
+    assert!((keys.len() - 1.0).abs() < 0.0001);
+
+    input.move_x = -1.0;
+    let stick = input.move_vector();
+    assert_eq!(stick.x, -1.0);
+    assert_eq!(stick.y, 0.0);
+}
@@ -1,10 +1,67 @@
-use entities::{BoundingBox, Color};
+#[cfg(test)]
+extern crate proptest;
+#[cfg(feature = "hot-reload")]
+#[macro_use]
+extern crate game_derive;
+#[cfg(feature = "scripting")]
+extern crate rhai;
+
+/// Times the rest of the enclosing block under `$name`, see `profile`. Expands to nothing
+/// without the `profiling` feature.
+#[cfg(feature = "profiling")]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profile::Scope::new($name,);
+    };
+}
+
+#[cfg(not(feature = "profiling"))]
+macro_rules! profile_scope {
+    ($name:expr) => {};
+}
+
+use action_map::{Action, ActionMap, ActionMapError, Key, KeyState};
+use debug::DebugOverlay;
+use draw::DrawList;
+use entities::{player::MAX_PLAYERS, BoundingBox, Color};
+use flow::{FlowCommand, GameFlow, GameStatus, Screen};
 use game_state::GameState;
+use math::vector::Vector2d;
+use net::{NetSession, StepResult};
+use sound::SoundEvent;
+use sprite::{Atlas, AtlasError, Flip, Sprite};
+use std::ptr;
 
+pub mod action_map;
+#[cfg(any(test, feature = "benchmarks"))]
+pub mod bench;
+pub mod camera;
+pub mod debug;
+pub mod draw;
 pub mod entities;
+pub mod flow;
+pub mod font;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzz;
 mod game_state;
-
-mod math;
+#[cfg(feature = "hot-reload")]
+pub mod hot;
+pub mod input;
+pub mod math;
+pub mod nav;
+pub mod net;
+#[cfg(feature = "profiling")]
+pub mod profile;
+mod rng;
+pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod sound;
+pub mod sprite;
+pub mod steering;
+pub mod transform;
+pub mod tunables;
+pub mod tween;
 
 #[derive(Debug)]
 pub struct Renderable {
@@ -12,6 +69,7 @@ pub struct Renderable {
     pub color: Color,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct GameInput {
     pub up_key: bool,
     pub down_key: bool,
@@ -23,6 +81,21 @@ pub struct GameInput {
     pub shoot_down: bool,
     pub quit_key: bool,
     pub space: bool,
+    /// Shows or hides the debug overlay, see `debug::DebugOverlay`.
+    pub debug_key: bool,
+    /// Analog movement stick, each axis in -1.0..1.0. Positive y is up.
+    pub move_x: f32,
+    pub move_y: f32,
+    /// Analog aim stick, each axis in -1.0..1.0. Positive y is up.
+    pub aim_x: f32,
+    pub aim_y: f32,
+    /// Cursor position in screen pixels, origin top left, y down.
+    pub mouse_x: f32,
+    pub mouse_y: f32,
+    pub mouse_left: bool,
+    pub mouse_right: bool,
+    /// Raw keys held this frame, turned into the action flags above by the action map.
+    pub keys: KeyState,
 }
 
 pub struct Platform {
@@ -31,31 +104,283 @@ pub struct Platform {
     pub start_frame: fn(),
     pub end_frame: fn(),
     pub draw_rectangle: fn(min_x: f32, min_y: f32, max_x: f32, max_y: f32, color: Color,),
+    pub play_sound: fn(event: SoundEvent,),
+    /// Same coordinates as `draw_rectangle`. The top of the source rect ends up at `max_y`.
+    pub draw_sprite: fn(
+        sprite: Sprite,
+        min_x: f32,
+        min_y: f32,
+        max_x: f32,
+        max_y: f32,
+        tint: Color,
+        flip: Flip,
+    ),
+    /// Fills a convex quad, corners in order around it. Same coordinates as `draw_rectangle`.
+    pub draw_quad: fn(corners: [Vector2d; 4], color: Color,),
+    /// Seconds from any fixed point, only used by the profiler.
+    pub now: fn() -> f64,
 }
 
 static mut GAME_STATE: Option<GameState,> = None;
 static mut PLATFORM: Option<Platform,> = None;
+static mut ACTION_MAPS: Option<Vec<ActionMap,>,> = None;
+static mut NET_SESSION: Option<NetSession,> = None;
+static mut SPRITES: Option<Vec<(String, Sprite,),>,> = None;
+static mut FLOW: Option<GameFlow,> = None;
+static mut DEBUG_OVERLAY: Option<DebugOverlay,> = None;
+/// Held by tests that start games, they share the globals above.
+#[cfg(test)]
+static TEST_GLOBALS: std::sync::Mutex<(),> = std::sync::Mutex::new((),);
 
 pub fn game_init(size_x: f32, size_y: f32, platform: Platform,) {
+    game_init_players(size_x, size_y, 1, platform,);
+}
+
+/// Starts a local game for `player_count` players, clamped to 1..=MAX_PLAYERS.
+pub fn game_init_players(size_x: f32, size_y: f32, player_count: usize, platform: Platform,) {
+    let seed = ((platform.random)() as f64 * u32::MAX as f64) as u64;
+    start_game(GameState::new(size_x, size_y, player_count, seed,), platform,);
+}
+
+/// Makes `game_state` the running game, on a fresh title screen.
+fn start_game(game_state: GameState, platform: Platform,) {
     unsafe {
         PLATFORM = Some(platform,);
-        GAME_STATE = Some(GameState::new(size_x, size_y,),)
+        GAME_STATE = Some(game_state,);
+        FLOW = Some(GameFlow::new(),);
+        DEBUG_OVERLAY = Some(DebugOverlay::new(),);
+        if (*ptr::addr_of!(ACTION_MAPS)).is_none() {
+            let maps = (0..MAX_PLAYERS).map(ActionMap::default_bindings_for,).collect();
+            ACTION_MAPS = Some(maps,);
+        }
     }
 }
 
-pub fn game_loop(input: GameInput, time_elapsed: f32, delta: f32,) -> bool {
+/// Single player version of `game_loop_players`.
+pub fn game_loop(input: GameInput, time_elapsed: f32, delta: f32,) -> GameStatus {
+    game_loop_players(vec![input], time_elapsed, delta,)
+}
+
+/// Runs one frame. `inputs` has one entry per player slot, the action map of each slot is
+/// applied to its input. The game starts on the title screen, only the playing screen moves
+/// the game forward, the others draw their menu over it.
+pub fn game_loop_players(
+    mut inputs: Vec<GameInput,>,
+    time_elapsed: f32,
+    delta: f32,
+) -> GameStatus {
     unsafe {
         assert!(GAME_STATE.is_some());
+        if let Some(ref maps,) = ACTION_MAPS {
+            for (input, map,) in inputs.iter_mut().zip(maps.iter(),) {
+                map.apply(input,);
+            }
+        }
+        match GAME_STATE {
+            Some(ref mut gs,) => match FLOW {
+                Some(ref mut flow,) => match DEBUG_OVERLAY {
+                    Some(ref mut overlay,) => {
+                        run_frame(gs, flow, overlay, &inputs, time_elapsed, delta,)
+                    }
+                    None => GameStatus::Quit,
+                },
+                None => GameStatus::Quit,
+            },
+            None => GameStatus::Quit,
+        }
+    }
+}
+
+/// Moves `flow` along on `inputs`, then plays a frame of `gs` and draws it with the menu and
+/// debug overlay on top.
+fn run_frame(
+    gs: &mut GameState,
+    flow: &mut GameFlow,
+    overlay: &mut DebugOverlay,
+    inputs: &[GameInput],
+    time_elapsed: f32,
+    delta: f32,
+) -> GameStatus {
+    profile_scope!("frame");
+    match flow.update(inputs,) {
+        Some(FlowCommand::NewGame,) => {
+            let seed = (get_random(0.0, 1.0,) as f64 * u32::MAX as f64) as u64;
+            *gs = gs.restarted(seed,);
+        }
+        Some(FlowCommand::Quit,) => return GameStatus::Quit,
+        None => {}
+    }
+    overlay.update(inputs, delta,);
+
+    if *flow.get_screen() == Screen::Playing {
+        // only local games, a changed script on one peer would desync a network game
+        #[cfg(feature = "scripting")]
+        script::with_scripts(|s| s.reload_if_changed(),);
+        tunables::with_tunables_file(|t| t.reload_if_changed(),);
+        gs.simulate(inputs, time_elapsed, delta,);
+        gs.play_sounds();
+        if gs.is_game_over() {
+            flow.game_over(gs.summary(),);
+        }
+    }
+    let mut list = match *flow.get_screen() {
+        Screen::Title => DrawList::new(),
+        _ => gs.draw_list(&[],),
+    };
+    if overlay.is_enabled() {
+        gs.draw_debug(&mut list,);
+        overlay.draw(&mut list, &gs.debug_stats(),);
+    }
+    let size = gs.get_world_size();
+    flow.draw(&mut list, size.x, size.y,);
+    {
+        profile_scope!("submit");
+        start_frame();
+        list.submit();
+        end_frame();
+    }
+    flow.status()
+}
+
+/// Shows or hides the debug overlay of local games. The debug key toggles it too.
+pub fn set_debug_overlay(enabled: bool) {
+    unsafe {
+        if let Some(ref mut overlay,) = DEBUG_OVERLAY {
+            overlay.set_enabled(enabled,);
+        }
+    }
+}
+
+/// Starts a networked game from a session made by `net::lobby::Lobby`. Both peers get the
+/// seed from the lobby, so their simulations start out identical. Loaded tunables are dropped
+/// for the defaults, the peer may have loaded others.
+pub fn game_init_network(size_x: f32, size_y: f32, session: NetSession, platform: Platform,) {
+    let had_tunables = tunables::unload_tunables();
+    let game_state = GameState::new(size_x, size_y, session.player_count(), session.seed(),);
+    start_game(game_state, platform,);
+    if had_tunables {
+        log(String::from("Networked games use the default tunables",),);
+    }
+    unsafe {
+        NET_SESSION = Some(session,);
+    }
+}
+
+/// Networked version of `game_loop`. The local player always uses the bindings of slot 0.
+/// The simulation always moves by `net::FIXED_DELTA`, and waits for the peer when it gets
+/// too far ahead. There is no title or pause screen, quitting ends the game for both peers.
+/// Nor is there a way to restart, the platform should close the game on `GameOver`.
+pub fn game_loop_network(mut input: GameInput) -> GameStatus {
+    unsafe {
+        if let Some(ref maps,) = ACTION_MAPS {
+            maps[0].apply(&mut input,);
+        }
+        if input.quit_key {
+            if let Some(ref mut session,) = NET_SESSION {
+                session.quit();
+            }
+            return GameStatus::Quit;
+        }
         match GAME_STATE {
-            Some(ref mut gs,) => {
-                if input.quit_key {
-                    return true;
-                }
+            Some(ref mut gs,) => match NET_SESSION {
+                Some(ref mut session,) => match session.advance(gs, &input,) {
+                    StepResult::Desync { frame, } => {
+                        log(format!("Desync detected at frame {}", frame),);
+                        GameStatus::Quit
+                    }
+                    StepResult::PeerQuit => {
+                        log(String::from("The other player left",),);
+                        GameStatus::Quit
+                    }
+                    _ if gs.is_game_over() => GameStatus::GameOver,
+                    _ => GameStatus::Playing,
+                },
+                None => GameStatus::Quit,
+            },
+            None => GameStatus::Quit,
+        }
+    }
+}
+
+pub fn set_action_map(slot: usize, map: ActionMap,) {
+    unsafe {
+        if (*ptr::addr_of!(ACTION_MAPS)).is_none() {
+            ACTION_MAPS = Some((0..MAX_PLAYERS).map(ActionMap::default_bindings_for,).collect(),);
+        }
+        if let Some(ref mut maps,) = ACTION_MAPS {
+            if slot < maps.len() {
+                maps[slot] = map;
+            }
+        }
+    }
+}
+
+/// Replaces the bindings of a player slot with the ones in the file.
+/// On error the old bindings are kept.
+pub fn load_action_map(slot: usize, path: &str,) -> Result<(), ActionMapError,> {
+    let map = ActionMap::load(path,)?;
+    set_action_map(slot, map,);
+    Ok((),)
+}
+
+/// Current bindings of a player slot, in the format `load_action_map` reads.
+pub fn action_map_config(slot: usize) -> String {
+    unsafe {
+        match ACTION_MAPS {
+            Some(ref maps,) if slot < maps.len() => maps[slot].to_config_string(),
+            _ => ActionMap::default_bindings_for(slot,).to_config_string(),
+        }
+    }
+}
 
-                gs.update(input, time_elapsed, delta,);
-                return false;
+pub fn rebind_key(slot: usize, action: Action, old: Key, new: Key,) {
+    let mut map = ActionMap::default_bindings_for(slot,);
+    unsafe {
+        if let Some(ref maps,) = ACTION_MAPS {
+            if slot < maps.len() {
+                map = maps[slot].clone();
+            }
+        }
+    }
+    map.rebind(action, old, new,);
+    set_action_map(slot, map,);
+}
+
+/// Makes the sprites of an atlas available to the game under their names. The platform has to
+/// load the texture itself and draw it when `draw_sprite` is called with `atlas`.
+pub fn set_atlas(atlas: u32, metadata: &Atlas,) {
+    unsafe {
+        if (*ptr::addr_of!(SPRITES)).is_none() {
+            SPRITES = Some(Vec::new(),);
+        }
+        if let Some(ref mut sprites,) = SPRITES {
+            for (name, source,) in metadata.sprites() {
+                sprites.retain(|(n, _,)| n != name,);
+                sprites.push((
+                    name.clone(),
+                    Sprite {
+                        atlas,
+                        source: *source,
+                    },
+                ),);
             }
-            None => return true,
+        }
+    }
+}
+
+/// Reads atlas metadata written by `atlas-packer` and passes it to `set_atlas`.
+pub fn load_atlas(atlas: u32, path: &str,) -> Result<Atlas, AtlasError,> {
+    let metadata = Atlas::load(path,)?;
+    set_atlas(atlas, &metadata,);
+    Ok(metadata,)
+}
+
+/// Entities without a sprite are drawn as plain rectangles.
+pub fn find_sprite(name: &str) -> Option<Sprite,> {
+    unsafe {
+        match SPRITES {
+            Some(ref sprites,) => sprites.iter().find(|(n, _,)| n == name,).map(|(_, s,)| *s,),
+            None => None,
         }
     }
 }
@@ -73,6 +398,16 @@ pub fn get_random(min: f32, max: f32,) -> f32 {
     }
 }
 
+/// The platform clock in seconds, 0 before the game is started.
+pub fn now() -> f64 {
+    unsafe {
+        match PLATFORM {
+            Some(ref pf,) => (pf.now)(),
+            None => 0.0,
+        }
+    }
+}
+
 pub fn log(text: String) {
     unsafe {
         match PLATFORM {
@@ -117,6 +452,38 @@ pub fn draw_rectangle(min_x: f32, min_y: f32, max_x: f32, max_y: f32, color: Col
     }
 }
 
+pub fn draw_sprite(
+    sprite: Sprite,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+    tint: Color,
+    flip: Flip,
+) {
+    unsafe {
+        if let Some(ref pf,) = PLATFORM {
+            (pf.draw_sprite)(sprite, min_x, min_y, max_x, max_y, tint, flip,);
+        }
+    }
+}
+
+pub fn draw_quad(corners: [Vector2d; 4], color: Color,) {
+    unsafe {
+        if let Some(ref pf,) = PLATFORM {
+            (pf.draw_quad)(corners, color,);
+        }
+    }
+}
+
+pub fn play_sound(event: SoundEvent) {
+    unsafe {
+        if let Some(ref pf,) = PLATFORM {
+            (pf.play_sound)(event,);
+        }
+    }
+}
+
 impl GameInput {
     pub fn new() -> GameInput {
         GameInput {
@@ -130,10 +497,22 @@ impl GameInput {
             shoot_down: false,
             quit_key: false,
             space: false,
+            debug_key: false,
+            move_x: 0.0,
+            move_y: 0.0,
+            aim_x: 0.0,
+            aim_y: 0.0,
+            mouse_x: 0.0,
+            mouse_y: 0.0,
+            mouse_left: false,
+            mouse_right: false,
+            keys: KeyState::new(),
         }
     }
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 struct GameTime {
     pub time_elapsed: f32,
     pub delta: f32,
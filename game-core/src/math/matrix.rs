@@ -0,0 +1,162 @@
+use math::vector::Vector2d;
+use std::ops::Mul;
+
+/// 3x3 matrix for 2d affine transforms, row major. Points are column vectors `(x, y, 1)`, so
+/// `a * b` applies `b` first and then `a`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat3 {
+    pub m: [[f32; 3]; 3],
+}
+
+impl Mat3 {
+    pub fn identity() -> Mat3 {
+        Mat3 {
+            m: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    pub fn translation(offset: Vector2d) -> Mat3 {
+        Mat3 {
+            m: [[1.0, 0.0, offset.x], [0.0, 1.0, offset.y], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Counter clockwise by `angle` radians around the origin.
+    pub fn rotation(angle: f32) -> Mat3 {
+        let (sin, cos,) = angle.sin_cos();
+        Mat3 {
+            m: [[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    pub fn scale(x: f32, y: f32,) -> Mat3 {
+        Mat3 {
+            m: [[x, 0.0, 0.0], [0.0, y, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Scale, then rotate, then move. The usual transform of an entity.
+    pub fn from_parts(position: Vector2d, angle: f32, scale: Vector2d,) -> Mat3 {
+        Mat3::translation(position,) * Mat3::rotation(angle,) * Mat3::scale(scale.x, scale.y,)
+    }
+
+    pub fn transform_point(&self, p: Vector2d,) -> Vector2d {
+        let m = &self.m;
+        Vector2d::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2],
+        )
+    }
+
+    /// Like `transform_point` but without the translation, for directions and offsets.
+    pub fn transform_vector(&self, v: Vector2d,) -> Vector2d {
+        let m = &self.m;
+        Vector2d::new(m[0][0] * v.x + m[0][1] * v.y, m[1][0] * v.x + m[1][1] * v.y,)
+    }
+
+    pub fn get_translation(&self) -> Vector2d {
+        Vector2d::new(self.m[0][2], self.m[1][2],)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        // the last row of an affine matrix is 0 0 1
+        self.m[0][0] * self.m[1][1] - self.m[0][1] * self.m[1][0]
+    }
+
+    /// None when the transform squashes everything onto a line or a point.
+    pub fn inverse(&self) -> Option<Mat3,> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let m = &self.m;
+        let (a, b, c, d,) = (m[0][0] / det, m[0][1] / det, m[1][0] / det, m[1][1] / det,);
+        let (tx, ty,) = (m[0][2], m[1][2],);
+        Some(Mat3 {
+            m: [
+                [d, -b, -(d * tx - b * ty)],
+                [-c, a, -(-c * tx + a * ty)],
+                [0.0, 0.0, 1.0],
+            ],
+        },)
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, other: Mat3,) -> Mat3 {
+        let mut m = [[0.0; 3]; 3];
+        for (row, out,) in m.iter_mut().enumerate() {
+            for (col, value,) in out.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.m[row][k] * other.m[k][col],).sum();
+            }
+        }
+        Mat3 { m, }
+    }
+}
+
+#[cfg(test)]
+fn close(a: Vector2d, b: Vector2d,) -> bool {
+    (a - b).len() <= 1e-2 * (1.0 + a.len().max(b.len(),))
+}
+
+#[test]
+fn test_transforms() {
+    let p = Vector2d::new(1.0, 0.0,);
+    let quarter = std::f32::consts::FRAC_PI_2;
+    assert!(close(Mat3::rotation(quarter,).transform_point(p,), Vector2d::new(0.0, 1.0,),));
+    let t = Mat3::from_parts(Vector2d::new(10.0, 5.0,), quarter, Vector2d::new(2.0, 2.0,),);
+    assert!(close(t.transform_point(p,), Vector2d::new(10.0, 7.0,),));
+    assert!(close(t.transform_vector(p,), Vector2d::new(0.0, 2.0,),));
+    assert_eq!(t.get_translation(), Vector2d::new(10.0, 5.0,));
+    assert_eq!(Mat3::scale(0.0, 1.0,).inverse(), None);
+}
+
+#[cfg(test)]
+mod properties {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn vector() -> impl Strategy<Value = Vector2d,> {
+        (-100f32..100.0, -100f32..100.0,).prop_map(|(x, y,)| Vector2d::new(x, y,),)
+    }
+
+    /// Translation, rotation and a scale that may come close to zero.
+    fn transform() -> impl Strategy<Value = Mat3,> {
+        (vector(), -100f32..100.0, vector(),).prop_map(|(translation, angle, scale,)| {
+            Mat3::from_parts(translation, angle, scale * 0.01,)
+        },)
+    }
+
+    proptest! {
+        /// Identity does nothing, products apply right to left.
+        #[test]
+        fn test_products(a in transform(), b in transform(), p in vector()) {
+            prop_assert_eq!(Mat3::identity() * a, a);
+            prop_assert_eq!(a * Mat3::identity(), a);
+            let both = a.transform_point(b.transform_point(p,),);
+            prop_assert!(close((a * b).transform_point(p,), both,));
+        }
+
+        /// Rotation and translation keep distances.
+        #[test]
+        fn test_rigid(
+            translation in vector(),
+            angle in -100f32..100.0,
+            p in vector(),
+            q in vector()
+        ) {
+            let r = Mat3::translation(translation,) * Mat3::rotation(angle,);
+            let moved = r.transform_point(p,).distance(&r.transform_point(q,),);
+            prop_assert!((moved - p.distance(&q,)).abs() < 1e-2);
+        }
+
+        #[test]
+        fn test_inverse(a in transform(), p in vector()) {
+            if let Some(inverse,) = a.inverse() {
+                prop_assert!(close(inverse.transform_point(a.transform_point(p,),), p,));
+            }
+        }
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(Debug, Copy, Clone)]
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
 pub struct Vector2d {
     pub x: f32,
     pub y: f32,
@@ -10,11 +13,28 @@ impl Vector2d {
         Vector2d { x, y, }
     }
 
+    pub fn zero() -> Vector2d {
+        Vector2d::new(0.0, 0.0,)
+    }
+
+    /// Unit vector pointing `angle` radians counter clockwise from the x axis.
+    pub fn from_angle(angle: f32) -> Vector2d {
+        Vector2d::new(angle.cos(), angle.sin(),)
+    }
+
+    /// Makes the vector one long. A zero vector is left as it is.
     pub fn normalize(&mut self) {
-        let sum = self.x.powi(2,) + self.y.powi(2,);
-        let sqrt = sum.sqrt();
-        self.x = self.x / sqrt;
-        self.y = self.y / sqrt;
+        *self = self.normalized();
+    }
+
+    /// Unit vector in the same direction, or zero for a zero (or not finite) vector.
+    pub fn normalized(&self) -> Vector2d {
+        let len = self.len();
+        if len > 0.0 && len.is_finite() {
+            Vector2d::new(self.x / len, self.y / len,)
+        } else {
+            Vector2d::zero()
+        }
     }
 
     pub fn set(&mut self, x: f32, y: f32,) {
@@ -40,6 +60,98 @@ impl Vector2d {
         let sum = self.x.powi(2,) + self.y.powi(2,);
         sum.sqrt()
     }
+
+    pub fn len_squared(&self) -> f32 {
+        self.dot(self,)
+    }
+
+    pub fn dot(&self, other: &Vector2d,) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// z of the 3d cross product. Positive when `other` is counter clockwise from `self`.
+    pub fn perp_dot(&self, other: &Vector2d,) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// The vector turned a quarter counter clockwise.
+    pub fn perp(&self) -> Vector2d {
+        Vector2d::new(-self.y, self.x,)
+    }
+
+    /// Radians counter clockwise from the x axis, in -pi..pi.
+    pub fn angle(&self) -> f32 {
+        self.y.atan2(self.x,)
+    }
+
+    /// Signed radians to turn `self` by to point along `other`, in -pi..pi.
+    pub fn angle_to(&self, other: &Vector2d,) -> f32 {
+        self.perp_dot(other,).atan2(self.dot(other,),)
+    }
+
+    /// Turned `angle` radians counter clockwise.
+    pub fn rotate(&self, angle: f32,) -> Vector2d {
+        let (sin, cos,) = angle.sin_cos();
+        Vector2d::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos,)
+    }
+
+    /// `self` at `t` = 0, `other` at `t` = 1.
+    pub fn lerp(&self, other: &Vector2d, t: f32,) -> Vector2d {
+        Vector2d::new(
+            self.x + (other.x - self.x) * t,
+            self.y + (other.y - self.y) * t,
+        )
+    }
+
+    pub fn distance(&self, other: &Vector2d,) -> f32 {
+        (*other - *self).len()
+    }
+}
+
+impl Add for Vector2d {
+    type Output = Vector2d;
+
+    fn add(self, other: Vector2d,) -> Vector2d {
+        Vector2d::new(self.x + other.x, self.y + other.y,)
+    }
+}
+
+impl Sub for Vector2d {
+    type Output = Vector2d;
+
+    fn sub(self, other: Vector2d,) -> Vector2d {
+        Vector2d::new(self.x - other.x, self.y - other.y,)
+    }
+}
+
+impl Mul<f32,> for Vector2d {
+    type Output = Vector2d;
+
+    fn mul(self, m: f32,) -> Vector2d {
+        Vector2d::new(self.x * m, self.y * m,)
+    }
+}
+
+impl Neg for Vector2d {
+    type Output = Vector2d;
+
+    fn neg(self) -> Vector2d {
+        Vector2d::new(-self.x, -self.y,)
+    }
+}
+
+impl AddAssign for Vector2d {
+    fn add_assign(&mut self, other: Vector2d,) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+impl SubAssign for Vector2d {
+    fn sub_assign(&mut self, other: Vector2d,) {
+        self.x -= other.x;
+        self.y -= other.y;
+    }
 }
 
 #[test]
@@ -49,6 +161,14 @@ fn test_norm() {
     assert_eq!(v.x, 0.70710677);
 }
 
+#[test]
+fn test_normalize_zero() {
+    let mut v = Vector2d::zero();
+    v.normalize();
+    assert_eq!(v, Vector2d::zero());
+    assert_eq!(Vector2d::new(f32::NAN, 1.0,).normalized(), Vector2d::zero());
+}
+
 #[test]
 fn test_set() {
     let mut v = Vector2d::new(10.0, 10.0,);
@@ -56,3 +176,74 @@ fn test_set() {
     assert_eq!(v.x, 1.0);
     assert_eq!(v.y, 2.0);
 }
+
+#[cfg(test)]
+mod properties {
+    use super::*;
+    use proptest::prelude::*;
+    use std::f32::consts::PI;
+
+    fn close(a: f32, b: f32,) -> bool {
+        (a - b).abs() <= 1e-3 * (1.0 + a.abs().max(b.abs(),))
+    }
+
+    /// Anywhere in the world and well around it, now and then zero.
+    fn vector() -> impl Strategy<Value = Vector2d,> {
+        prop_oneof![
+            1 => Just(Vector2d::zero()),
+            9 => (-1000f32..1000.0, -1000f32..1000.0,).prop_map(|(x, y,)| Vector2d::new(x, y,),),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_operators(a in vector(), b in vector()) {
+            prop_assert_eq!(a + b, b + a);
+            prop_assert_eq!(a - b, -(b - a));
+            prop_assert_eq!(a - a, Vector2d::zero());
+            let mut sum = a;
+            sum += b;
+            sum -= b;
+            prop_assert!(close(sum.x, a.x,) && close(sum.y, a.y,));
+            prop_assert_eq!(a * 2.0, a + a);
+        }
+
+        #[test]
+        fn test_products(a in vector(), b in vector()) {
+            prop_assert!(close(a.dot(&b,), b.dot(&a,),));
+            prop_assert!(close(a.perp_dot(&b,), -b.perp_dot(&a,),));
+            prop_assert!(close(a.perp().dot(&a,), 0.0,));
+            prop_assert!(close(a.dot(&a,), a.len() * a.len(),));
+            prop_assert!(close(a.distance(&b,), b.distance(&a,),));
+            // triangle inequality
+            prop_assert!((a + b).len() <= a.len() + b.len() + 1e-2);
+            let n = a.normalized();
+            prop_assert!(n == Vector2d::zero() || close(n.len(), 1.0,));
+        }
+
+        /// Rotating keeps the length and turns by the angle.
+        #[test]
+        fn test_rotate(a in vector(), angle in -10f32..10.0) {
+            let r = a.rotate(angle,);
+            prop_assert!(close(r.len(), a.len(),));
+            if a.len() > 1.0 {
+                let turned = a.angle_to(&r,);
+                let expected = (angle + PI).rem_euclid(2.0 * PI,) - PI;
+                prop_assert!(close(turned.sin(), expected.sin(),));
+                prop_assert!(close(turned.cos(), expected.cos(),));
+                let along = Vector2d::from_angle(a.angle(),);
+                prop_assert!(close(along.perp_dot(&a.normalized(),), 0.0,));
+            }
+        }
+
+        /// Lerp starts at `a`, ends at `b` and moves in a straight line.
+        #[test]
+        fn test_lerp(a in vector(), b in vector(), t in 0f32..1.0) {
+            prop_assert_eq!(a.lerp(&b, 0.0,), a);
+            let end = a.lerp(&b, 1.0,);
+            prop_assert!(close(end.x, b.x,) && close(end.y, b.y,));
+            let mid = a.lerp(&b, t,);
+            prop_assert!(close(a.distance(&mid,) + mid.distance(&b,), a.distance(&b,),));
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use entities::BoundingBox;
+use math::vector::Vector2d;
+use std::{cmp::Reverse, collections::BinaryHeap, rc::Rc};
+
+/// Paths and flow fields are only rebuilt when their target moved further than this.
+pub const REPATH_DISTANCE: f32 = 32.0;
+/// With this many enemies chasing, a flow field per target is cheaper than a path per enemy.
+pub const FLOW_FIELD_CROWD: usize = 40;
+
+// integer step costs keep the search the same on every platform
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Neighbour offsets, straight ones first.
+const NEIGHBOURS: [(i32, i32,); 8] =
+    [(1, 0,), (-1, 0,), (0, 1,), (0, -1,), (1, 1,), (1, -1,), (-1, 1,), (-1, -1,)];
+
+/// The world cut into square cells that are either free or blocked by a wall.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct NavGrid {
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool,>,
+}
+
+impl NavGrid {
+    /// Cells closer than `clearance` to a wall are blocked too, so something that size fits
+    /// through every free cell. Usually half the size of whatever walks the grid.
+    pub fn new(
+        width: f32,
+        height: f32,
+        cell_size: f32,
+        walls: &[BoundingBox],
+        clearance: f32,
+    ) -> NavGrid {
+        let columns = (width / cell_size).ceil().max(1.0,) as usize;
+        let rows = (height / cell_size).ceil().max(1.0,) as usize;
+        let mut grid = NavGrid {
+            cell_size,
+            columns,
+            rows,
+            blocked: vec![false; columns * rows],
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                let left = column as f32 * cell_size;
+                let bottom = row as f32 * cell_size;
+                let hit = walls.iter().any(|w| {
+                    left < w.right + clearance
+                        && left + cell_size > w.left - clearance
+                        && bottom < w.top + clearance
+                        && bottom + cell_size > w.bottom - clearance
+                },);
+                grid.blocked[row * columns + column] = hit;
+            }
+        }
+        grid
+    }
+
+    pub fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Index of the cell `pos` is in, None outside the grid.
+    pub fn cell_index(&self, pos: Vector2d,) -> Option<usize,> {
+        if !(pos.x >= 0.0 && pos.y >= 0.0) {
+            return None;
+        }
+        let column = (pos.x / self.cell_size) as usize;
+        let row = (pos.y / self.cell_size) as usize;
+        if column < self.columns && row < self.rows {
+            Some(row * self.columns + column,)
+        } else {
+            None
+        }
+    }
+
+    pub fn cell_center(&self, index: usize,) -> Vector2d {
+        let column = (index % self.columns) as f32;
+        let row = (index / self.columns) as f32;
+        Vector2d::new((column + 0.5) * self.cell_size, (row + 0.5) * self.cell_size,)
+    }
+
+    /// Outside the grid counts as blocked.
+    pub fn is_blocked(&self, pos: Vector2d,) -> bool {
+        match self.cell_index(pos,) {
+            Some(index,) => self.blocked[index],
+            None => true,
+        }
+    }
+
+    /// Free cells around `index` with the cost of stepping there. Diagonal steps need both
+    /// cells beside them free, so paths never cut wall corners. `passable` is walkable even
+    /// when blocked, for a start or goal standing close to a wall.
+    fn neighbours(&self, index: usize, passable: usize,) -> Vec<(usize, u32,),> {
+        let column = (index % self.columns) as i32;
+        let row = (index / self.columns) as i32;
+        let free = |c: i32, r: i32| -> Option<usize,> {
+            if c < 0 || r < 0 || c >= self.columns as i32 || r >= self.rows as i32 {
+                return None;
+            }
+            let i = r as usize * self.columns + c as usize;
+            if self.blocked[i] && i != passable {
+                None
+            } else {
+                Some(i,)
+            }
+        };
+        let mut result = Vec::with_capacity(8,);
+        for &(dc, dr,) in NEIGHBOURS.iter() {
+            let next = match free(column + dc, row + dr,) {
+                Some(next,) => next,
+                None => continue,
+            };
+            if dc != 0 && dr != 0 {
+                if free(column + dc, row,).is_none() || free(column, row + dr,).is_none() {
+                    continue;
+                }
+                result.push((next, DIAGONAL_COST,),);
+            } else {
+                result.push((next, STRAIGHT_COST,),);
+            }
+        }
+        result
+    }
+
+    /// Octile distance, never more than the real cost so A* finds the shortest path.
+    fn heuristic(&self, a: usize, b: usize,) -> u32 {
+        let dx = (a % self.columns).abs_diff(b % self.columns,) as u32;
+        let dy = (a / self.columns).abs_diff(b / self.columns,) as u32;
+        STRAIGHT_COST * dx.max(dy,) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy,)
+    }
+
+    /// Shortest path with A*, as cell centers from the cell after `from` to `to` itself.
+    /// None when `to` can not be reached or either end is outside the grid.
+    pub fn find_path(&self, from: Vector2d, to: Vector2d,) -> Option<Vec<Vector2d,>,> {
+        let start = self.cell_index(from,)?;
+        let goal = self.cell_index(to,)?;
+        if start == goal {
+            return Some(vec![to],);
+        }
+        let count = self.blocked.len();
+        let mut cost = vec![u32::MAX; count];
+        let mut came_from = vec![usize::MAX; count];
+        let mut closed = vec![false; count];
+        let mut open = BinaryHeap::new();
+        cost[start] = 0;
+        // ties go to the cell closer to the goal, then the lower index, so the result is the
+        // same every time
+        let h = self.heuristic(start, goal,);
+        open.push(Reverse((h, h, start,),),);
+        while let Some(Reverse((_, _, current,),),) = open.pop() {
+            if current == goal {
+                let mut path = vec![to];
+                let mut cell = came_from[goal];
+                while cell != start {
+                    path.push(self.cell_center(cell,),);
+                    cell = came_from[cell];
+                }
+                path.reverse();
+                return Some(path,);
+            }
+            if closed[current] {
+                continue;
+            }
+            closed[current] = true;
+            for (next, step,) in self.neighbours(current, goal,) {
+                let new_cost = cost[current] + step;
+                if new_cost < cost[next] {
+                    cost[next] = new_cost;
+                    came_from[next] = current;
+                    let h = self.heuristic(next, goal,);
+                    open.push(Reverse((new_cost + h, h, next,),),);
+                }
+            }
+        }
+        None
+    }
+
+    /// True if the straight line from `a` to `b` crosses no blocked cell, ignoring the cells
+    /// `a` and `b` are in.
+    pub fn has_line_of_sight(&self, a: Vector2d, b: Vector2d,) -> bool {
+        let (start, end,) = match (self.cell_index(a,), self.cell_index(b,),) {
+            (Some(start,), Some(end,),) => (start, end,),
+            _ => return false,
+        };
+        let step = self.cell_size / 4.0;
+        let samples = (a.distance(&b,) / step).ceil() as usize;
+        (1..samples).all(|i| {
+            let p = a.lerp(&b, i as f32 / samples as f32,);
+            match self.cell_index(p,) {
+                Some(index,) => index == start || index == end || !self.blocked[index],
+                None => false,
+            }
+        },)
+    }
+
+    /// Drops every waypoint that can be skipped by walking straight to a later one.
+    pub fn smooth_path(&self, from: Vector2d, path: &[Vector2d],) -> Vec<Vector2d,> {
+        let mut result = Vec::new();
+        let mut current = from;
+        let mut index = 0;
+        while index < path.len() {
+            let mut furthest = index;
+            while furthest + 1 < path.len() && self.has_line_of_sight(current, path[furthest + 1],)
+            {
+                furthest += 1;
+            }
+            result.push(path[furthest],);
+            current = path[furthest];
+            index = furthest + 1;
+        }
+        result
+    }
+
+    /// Unit direction towards `target` from every cell, for `FlowField`.
+    fn flow_directions(&self, target: Vector2d,) -> Vec<Vector2d,> {
+        let count = self.blocked.len();
+        let mut cost = vec![u32::MAX; count];
+        let mut directions = vec![Vector2d::zero(); count];
+        if let Some(goal,) = self.cell_index(target,) {
+            let mut open = BinaryHeap::new();
+            cost[goal] = 0;
+            open.push(Reverse((0, goal,),),);
+            while let Some(Reverse((current_cost, current,),),) = open.pop() {
+                if current_cost > cost[current] {
+                    continue;
+                }
+                for (next, step,) in self.neighbours(current, goal,) {
+                    if current_cost + step < cost[next] {
+                        cost[next] = current_cost + step;
+                        open.push(Reverse((cost[next], next,),),);
+                    }
+                }
+            }
+            for index in 0..count {
+                if index == goal || cost[index] == u32::MAX {
+                    continue;
+                }
+                // towards the cheapest neighbour, the same way a path from here would go
+                let best = self
+                    .neighbours(index, goal,)
+                    .into_iter()
+                    .min_by_key(|&(next, step,)| (cost[next].saturating_add(step,), next,),);
+                if let Some((next, _,),) = best {
+                    directions[index] =
+                        (self.cell_center(next,) - self.cell_center(index,)).normalized();
+                }
+            }
+        }
+        directions
+    }
+}
+
+/// Which way to go from anywhere on a grid to reach one target.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct FlowField {
+    target: Vector2d,
+    /// Shared with the navigation it was built for.
+    grid: Rc<NavGrid,>,
+    directions: Vec<Vector2d,>,
+}
+
+impl FlowField {
+    /// Directions towards `target` from every cell of `grid`, worked out once for any number
+    /// of chasers.
+    pub fn new(grid: Rc<NavGrid,>, target: Vector2d,) -> FlowField {
+        let directions = grid.flow_directions(target,);
+        FlowField {
+            target,
+            grid,
+            directions,
+        }
+    }
+
+    pub fn get_target(&self) -> Vector2d {
+        self.target
+    }
+
+    /// Unit direction to move in from `pos`. Zero in the target cell, where the target can be
+    /// walked to directly, and where it can not be reached at all.
+    pub fn direction(&self, pos: Vector2d,) -> Vector2d {
+        match self.grid.cell_index(pos,) {
+            Some(index,) => self.directions[index],
+            None => Vector2d::zero(),
+        }
+    }
+}
+
+/// The grid of a level and the flow fields built on it. Clones share both, so snapshots of the
+/// game state stay cheap.
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Navigation {
+    grid: Rc<NavGrid,>,
+    flow_fields: Vec<Rc<FlowField,>,>,
+}
+
+impl Navigation {
+    pub fn new(grid: NavGrid) -> Navigation {
+        Navigation {
+            grid: Rc::new(grid,),
+            flow_fields: Vec::new(),
+        }
+    }
+
+    pub fn get_grid(&self) -> &NavGrid {
+        &self.grid
+    }
+
+    /// Keeps a flow field towards each of `targets` while at least `FLOW_FIELD_CROWD` enemies
+    /// are chasing them. A field is only rebuilt when its target moved `REPATH_DISTANCE`.
+    pub fn update_flow_fields(&mut self, targets: &[Vector2d], chasers: usize,) {
+        profile_scope!("flow_fields");
+        if chasers < FLOW_FIELD_CROWD {
+            self.flow_fields.clear();
+            return;
+        }
+        let mut fields = Vec::with_capacity(targets.len(),);
+        for target in targets {
+            let field = match self.flow_field_to(*target,) {
+                Some(field,) => field.clone(),
+                None => Rc::new(FlowField::new(self.grid.clone(), *target,),),
+            };
+            fields.push(field,);
+        }
+        self.flow_fields = fields;
+    }
+
+    /// The flow field towards `target`, if there is one close enough to it.
+    pub fn flow_field_to(&self, target: Vector2d,) -> Option<&Rc<FlowField,>,> {
+        self.flow_fields.iter().find(|f| f.get_target().distance(&target,) < REPATH_DISTANCE,)
+    }
+}
+
+#[test]
+fn test_paths_go_around_walls() {
+    // a wall across the middle with a gap at the top
+    let wall = BoundingBox {
+        left: 90.0,
+        right: 110.0,
+        top: 160.0,
+        bottom: 0.0,
+    };
+    let closed = BoundingBox { top: 200.0, ..wall };
+    let grid = NavGrid::new(200.0, 200.0, 10.0, &[wall], 5.0,);
+    let from = Vector2d::new(20.0, 20.0,);
+    let to = Vector2d::new(180.0, 20.0,);
+    assert!(!grid.has_line_of_sight(from, to,));
+
+    let path = grid.find_path(from, to,).unwrap();
+    assert_eq!(*path.last().unwrap(), to);
+    assert!(path.iter().all(|p| !grid.is_blocked(*p,) || *p == to,));
+    assert!(path.iter().any(|p| p.y > 160.0,));
+    // the same every time
+    assert_eq!(grid.find_path(from, to,), Some(path.clone()));
+
+    let smooth = grid.smooth_path(from, &path,);
+    assert!(smooth.len() < path.len());
+    assert_eq!(*smooth.last().unwrap(), to);
+    let mut current = from;
+    for p in &smooth {
+        assert!(grid.has_line_of_sight(current, *p,));
+        current = *p;
+    }
+
+    // following the flow field from the left side gets over the wall
+    let field = FlowField::new(Rc::new(grid.clone(),), to,);
+    let mut pos = from;
+    for _ in 0..500 {
+        let direction = field.direction(pos,);
+        if direction == Vector2d::zero() {
+            break;
+        }
+        pos += direction * 2.0;
+        assert!(!grid.is_blocked(pos,) || grid.cell_index(pos,) == grid.cell_index(from,));
+    }
+    assert_eq!(grid.cell_index(pos,), grid.cell_index(to,));
+
+    // fully walled in
+    let boxed = NavGrid::new(200.0, 200.0, 10.0, &[closed], 5.0,);
+    assert_eq!(boxed.find_path(from, to,), None);
+}
@@ -0,0 +1,112 @@
+use net::{
+    lockstep::LockstepSession,
+    message::{Message, PROTOCOL_VERSION},
+    rollback::RollbackSession,
+    Transport,
+};
+
+enum Role {
+    Host { seed: u64, },
+    Client,
+}
+
+/// Handshake before a game. The host picks the seed, the client keeps saying hello until the
+/// host answers with it.
+pub struct Lobby<T: Transport,> {
+    transport: T,
+    role: Role,
+    seed: Option<u64,>,
+    version_mismatch: bool,
+}
+
+impl<T: Transport,> Lobby<T,> {
+    pub fn host(transport: T, seed: u64,) -> Lobby<T,> {
+        Lobby {
+            transport,
+            role: Role::Host { seed, },
+            seed: None,
+            version_mismatch: false,
+        }
+    }
+
+    pub fn join(transport: T) -> Lobby<T,> {
+        Lobby {
+            transport,
+            role: Role::Client,
+            seed: None,
+            version_mismatch: false,
+        }
+    }
+
+    /// Call once per frame. Returns true once both sides have agreed on a seed.
+    pub fn poll(&mut self) -> bool {
+        if let Role::Client = self.role {
+            if self.seed.is_none() {
+                self.transport.send(
+                    &Message::Hello {
+                        version: PROTOCOL_VERSION,
+                    }.encode(),
+                );
+            }
+        }
+        while let Some(bytes,) = self.transport.recv() {
+            match (&self.role, Message::decode(&bytes,),) {
+                (Role::Host { seed, }, Some(Message::Hello { version, },),) => {
+                    let seed = *seed;
+                    self.transport.send(
+                        &Message::Welcome {
+                            version: PROTOCOL_VERSION,
+                            seed,
+                        }.encode(),
+                    );
+                    if version == PROTOCOL_VERSION {
+                        self.seed = Some(seed,);
+                    } else {
+                        self.version_mismatch = true;
+                    }
+                }
+                (Role::Client, Some(Message::Welcome { version, seed, },),) => {
+                    if version == PROTOCOL_VERSION {
+                        self.seed = Some(seed,);
+                    } else {
+                        self.version_mismatch = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.seed.is_some()
+    }
+
+    /// The peer runs a different protocol version. The game can not be played.
+    pub fn version_mismatch(&self) -> bool {
+        self.version_mismatch
+    }
+
+    pub fn seed(&self) -> Option<u64,> {
+        self.seed
+    }
+
+    /// Starts a lockstep session once `poll` has returned true. The host plays slot 0 and the
+    /// client slot 1.
+    pub fn into_lockstep(self, input_delay: u32,) -> Option<LockstepSession<T,>,> {
+        let (transport, is_host, seed,) = self.into_parts()?;
+        Some(LockstepSession::new(transport, is_host, seed, input_delay,),)
+    }
+
+    /// Starts a rollback session once `poll` has returned true. The host plays slot 0 and the
+    /// client slot 1.
+    pub fn into_rollback(self, input_delay: u32,) -> Option<RollbackSession<T,>,> {
+        let (transport, is_host, seed,) = self.into_parts()?;
+        Some(RollbackSession::new(transport, is_host, seed, input_delay,),)
+    }
+
+    fn into_parts(self) -> Option<(T, bool, u64,),> {
+        let seed = self.seed?;
+        let is_host = match self.role {
+            Role::Host { .. } => true,
+            Role::Client => false,
+        };
+        Some((self.transport, is_host, seed,),)
+    }
+}
@@ -0,0 +1,411 @@
+use game_state::GameState;
+use net::{
+    message::{Message, NetInput, MAX_INPUTS_PER_PACKET, PROTOCOL_VERSION, QUIT_PACKETS},
+    StepResult, Transport, FIXED_DELTA,
+};
+use std::collections::BTreeMap;
+use GameInput;
+
+/// Frames between reading an input and simulating it. Hides the round trip to the peer.
+pub const DEFAULT_INPUT_DELAY: u32 = 3;
+/// Hashes older than this many frames are dropped if the peer never sent a matching one.
+const HASH_HISTORY: u32 = 120;
+
+/// Two peer lockstep. Each peer sends its input for frame `n + input_delay` while
+/// simulating frame `n`, and a frame is only simulated once both inputs are in. Inputs are
+/// resent until acknowledged, and state hashes are compared to catch desyncs.
+pub struct LockstepSession<T: Transport,> {
+    transport: T,
+    is_host: bool,
+    seed: u64,
+    input_delay: u32,
+    /// Next frame to simulate.
+    frame: u32,
+    /// Next frame a local input will be read for.
+    next_local_frame: u32,
+    local_inputs: BTreeMap<u32, NetInput,>,
+    remote_inputs: BTreeMap<u32, NetInput,>,
+    /// First frame we have not received a remote input for.
+    remote_next: u32,
+    /// First frame the peer has not received from us.
+    peer_ack: u32,
+    local_hashes: BTreeMap<u32, u64,>,
+    remote_hashes: BTreeMap<u32, u64,>,
+    desync_frame: Option<u32,>,
+    peer_quit: bool,
+}
+
+impl<T: Transport,> LockstepSession<T,> {
+    pub(crate) fn new(
+        transport: T,
+        is_host: bool,
+        seed: u64,
+        input_delay: u32,
+    ) -> LockstepSession<T,> {
+        // the first frames are played with no input on both sides
+        let neutral = NetInput::from_input(&GameInput::new(),);
+        let mut local_inputs = BTreeMap::new();
+        let mut remote_inputs = BTreeMap::new();
+        for frame in 0..input_delay {
+            local_inputs.insert(frame, neutral,);
+            remote_inputs.insert(frame, neutral,);
+        }
+        LockstepSession {
+            transport,
+            is_host,
+            seed,
+            input_delay,
+            frame: 0,
+            next_local_frame: input_delay,
+            local_inputs,
+            remote_inputs,
+            remote_next: input_delay,
+            peer_ack: input_delay,
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            desync_frame: None,
+            peer_quit: false,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn local_slot(&self) -> usize {
+        if self.is_host {
+            0
+        } else {
+            1
+        }
+    }
+
+    pub fn player_count(&self) -> usize {
+        2
+    }
+
+    /// Next frame to be simulated.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Tells the peer we are leaving. The session should not be advanced after this.
+    pub fn quit(&mut self) {
+        for _ in 0..QUIT_PACKETS {
+            self.transport.send(&Message::Quit.encode(),);
+        }
+    }
+
+    /// Call once per rendered frame with this frame's local input. `input` must already have
+    /// its action flags set, the raw keys are not sent.
+    pub(crate) fn advance(&mut self, state: &mut GameState, input: &GameInput,) -> StepResult {
+        self.receive();
+        if self.peer_quit {
+            return StepResult::PeerQuit;
+        }
+
+        if self.next_local_frame <= self.frame + self.input_delay {
+            self.local_inputs
+                .insert(self.next_local_frame, NetInput::from_input(input,),);
+            self.next_local_frame += 1;
+        }
+        self.send_inputs();
+
+        if let Some(frame,) = self.desync_frame {
+            return StepResult::Desync { frame, };
+        }
+
+        let (local, remote,) = match (
+            self.local_inputs.get(&self.frame,),
+            self.remote_inputs.get(&self.frame,),
+        ) {
+            (Some(local,), Some(remote,),) => (local.to_input(), remote.to_input(),),
+            _ => return StepResult::Waiting,
+        };
+        let inputs = if self.is_host {
+            [local, remote]
+        } else {
+            [remote, local]
+        };
+        state.update(
+            &inputs,
+            (self.frame + 1) as f32 * FIXED_DELTA,
+            FIXED_DELTA,
+        );
+
+        let hash = state.state_hash();
+        self.transport.send(
+            &Message::Hash {
+                frame: self.frame,
+                hash,
+            }.encode(),
+        );
+        self.local_hashes.insert(self.frame, hash,);
+        self.compare_hashes();
+
+        self.remote_inputs.remove(&self.frame,);
+        self.frame += 1;
+        self.forget_acked_inputs();
+
+        match self.desync_frame {
+            Some(frame,) => StepResult::Desync { frame, },
+            None => StepResult::Advanced,
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Some(bytes,) = self.transport.recv() {
+            match Message::decode(&bytes,) {
+                Some(Message::Hello { .. },) if self.is_host => {
+                    // our welcome got lost, the client is still in the lobby
+                    self.transport.send(
+                        &Message::Welcome {
+                            version: PROTOCOL_VERSION,
+                            seed: self.seed,
+                        }.encode(),
+                    );
+                }
+                Some(Message::Inputs {
+                    ack,
+                    first_frame,
+                    inputs,
+                },) => {
+                    self.peer_ack = self.peer_ack.max(ack,);
+                    for (i, input,) in inputs.iter().enumerate() {
+                        let frame = first_frame + i as u32;
+                        if frame >= self.frame {
+                            self.remote_inputs.entry(frame,).or_insert(*input,);
+                        }
+                    }
+                    while self.remote_inputs.contains_key(&self.remote_next,)
+                        || self.remote_next < self.frame
+                    {
+                        self.remote_next += 1;
+                    }
+                }
+                Some(Message::Hash { frame, hash, },) => {
+                    self.remote_hashes.insert(frame, hash,);
+                    self.compare_hashes();
+                }
+                Some(Message::Quit,) => self.peer_quit = true,
+                _ => {}
+            }
+        }
+        self.forget_acked_inputs();
+    }
+
+    fn send_inputs(&mut self) {
+        let first_frame = match self.local_inputs.range(self.peer_ack..,).next() {
+            Some((frame, _,),) => *frame,
+            None => return,
+        };
+        let inputs: Vec<NetInput,> = self
+            .local_inputs
+            .range(first_frame..,)
+            .take(MAX_INPUTS_PER_PACKET,)
+            .map(|(_, input,)| *input,)
+            .collect();
+        self.transport.send(
+            &Message::Inputs {
+                ack: self.remote_next,
+                first_frame,
+                inputs,
+            }.encode(),
+        );
+    }
+
+    /// Local inputs are kept until they are both simulated and received by the peer.
+    fn forget_acked_inputs(&mut self) {
+        let keep_from = self.peer_ack.min(self.frame,);
+        self.local_inputs = self.local_inputs.split_off(&keep_from,);
+    }
+
+    fn compare_hashes(&mut self) {
+        let frames: Vec<u32,> = self
+            .local_hashes
+            .keys()
+            .filter(|f| self.remote_hashes.contains_key(f,),)
+            .cloned()
+            .collect();
+        for frame in frames {
+            let differs = self.local_hashes[&frame] != self.remote_hashes[&frame];
+            if differs && self.desync_frame.is_none() {
+                self.desync_frame = Some(frame,);
+            }
+            self.local_hashes.remove(&frame,);
+            self.remote_hashes.remove(&frame,);
+        }
+        let oldest = self.frame.saturating_sub(HASH_HISTORY,);
+        self.local_hashes = self.local_hashes.split_off(&oldest,);
+        self.remote_hashes = self.remote_hashes.split_off(&oldest,);
+    }
+}
+
+#[cfg(test)]
+fn scripted_input(slot: usize, frame: u32,) -> GameInput {
+    let mut input = GameInput::new();
+    let step = frame / 20 + slot as u32;
+    input.up_key = step.is_multiple_of(4,);
+    input.left_key = step % 4 == 1;
+    input.down_key = step % 4 == 2;
+    input.right_key = step % 4 == 3;
+    input.shoot_up = step.is_multiple_of(3,);
+    input.shoot_right = step.is_multiple_of(2,);
+    input.aim_x = if frame % 50 < 10 { 0.8 } else { 0.0 };
+    input
+}
+
+#[cfg(test)]
+fn run_peers<T: Transport,>(
+    mut host: LockstepSession<T,>,
+    mut client: LockstepSession<T,>,
+    frames: usize,
+    mut tick: impl FnMut(),
+) -> (Vec<u64,>, Vec<u64,>,) {
+    let mut host_state = GameState::new(960.0, 540.0, 2, host.seed(),);
+    let mut client_state = GameState::new(960.0, 540.0, 2, client.seed(),);
+    let mut host_hashes = Vec::new();
+    let mut client_hashes = Vec::new();
+    let mut ticks = 0;
+    while host_hashes.len() < frames || client_hashes.len() < frames {
+        ticks += 1;
+        assert!(ticks < frames * 50, "lockstep stalled");
+        let input = scripted_input(0, host.next_local_frame,);
+        match host.advance(&mut host_state, &input,) {
+            StepResult::Advanced => host_hashes.push(host_state.state_hash(),),
+            StepResult::Waiting => {}
+            StepResult::Desync { frame, } => panic!("host desync at {}", frame),
+            StepResult::PeerQuit => panic!("client quit"),
+        }
+        let input = scripted_input(1, client.next_local_frame,);
+        match client.advance(&mut client_state, &input,) {
+            StepResult::Advanced => client_hashes.push(client_state.state_hash(),),
+            StepResult::Waiting => {}
+            StepResult::Desync { frame, } => panic!("client desync at {}", frame),
+            StepResult::PeerQuit => panic!("host quit"),
+        }
+        tick();
+    }
+    host_hashes.truncate(frames,);
+    client_hashes.truncate(frames,);
+    (host_hashes, client_hashes,)
+}
+
+#[test]
+fn test_lockstep_over_bad_link() {
+    use net::{
+        lobby::Lobby,
+        sim::{LinkConfig, SimulatedTransport},
+    };
+    let (a, b,) = SimulatedTransport::pair(LinkConfig {
+        loss: 0.2,
+        latency: 3,
+        jitter: 3,
+        seed: 7,
+    },);
+    let clock = a.clock();
+    let mut host = Lobby::host(a, 1234,);
+    let mut client = Lobby::join(b,);
+    let mut connected = false;
+    for _ in 0..200 {
+        let c = client.poll();
+        let h = host.poll();
+        clock.tick();
+        if c && h {
+            connected = true;
+            break;
+        }
+    }
+    assert!(connected);
+    assert_eq!(client.seed(), Some(1234));
+    let host = host.into_lockstep(DEFAULT_INPUT_DELAY,).unwrap();
+    let client = client.into_lockstep(DEFAULT_INPUT_DELAY,).unwrap();
+    assert_eq!(host.local_slot(), 0);
+    assert_eq!(client.local_slot(), 1);
+
+    let (host_hashes, client_hashes,) = run_peers(host, client, 300, || clock.tick(),);
+    assert_eq!(host_hashes, client_hashes);
+}
+
+#[test]
+fn test_lockstep_detects_desync() {
+    use net::sim::{LinkConfig, SimulatedTransport};
+    let (a, b,) = SimulatedTransport::pair(LinkConfig::perfect(),);
+    let mut host = LockstepSession::new(a, true, 1, DEFAULT_INPUT_DELAY,);
+    let mut client = LockstepSession::new(b, false, 1, DEFAULT_INPUT_DELAY,);
+    let mut host_state = GameState::new(960.0, 540.0, 2, 1,);
+    // different seed, so enemies spawn in different places
+    let mut client_state = GameState::new(960.0, 540.0, 2, 2,);
+    let input = GameInput::new();
+    let mut desync = None;
+    for _ in 0..200 {
+        if let StepResult::Desync { frame, } = host.advance(&mut host_state, &input,) {
+            desync = Some(frame,);
+            break;
+        }
+        client.advance(&mut client_state, &input,);
+    }
+    assert!(desync.is_some());
+}
+
+#[test]
+fn test_lockstep_on_localhost() {
+    use net::{lobby::Lobby, UdpTransport};
+    use std::{net::UdpSocket, thread, time::Duration};
+    let host_socket = UdpSocket::bind("127.0.0.1:0",).unwrap();
+    let client_socket = UdpSocket::bind("127.0.0.1:0",).unwrap();
+    let host_addr = host_socket.local_addr().unwrap();
+    let client_addr = client_socket.local_addr().unwrap();
+    let mut host = Lobby::host(UdpTransport::new(host_socket, client_addr,).unwrap(), 99,);
+    let mut client = Lobby::join(UdpTransport::new(client_socket, host_addr,).unwrap(),);
+    let mut connected = false;
+    for _ in 0..500 {
+        let c = client.poll();
+        let h = host.poll();
+        if c && h {
+            connected = true;
+            break;
+        }
+        thread::sleep(Duration::from_millis(1,),);
+    }
+    assert!(connected);
+    let host = host.into_lockstep(DEFAULT_INPUT_DELAY,).unwrap();
+    let client = client.into_lockstep(DEFAULT_INPUT_DELAY,).unwrap();
+    let (host_hashes, client_hashes,) = run_peers(host, client, 60, || {
+        thread::sleep(Duration::from_millis(1,),)
+    },);
+    assert_eq!(host_hashes, client_hashes);
+}
+
+#[test]
+fn test_lockstep_peer_quit() {
+    use net::sim::{LinkConfig, SimulatedTransport};
+    let (a, b,) = SimulatedTransport::pair(LinkConfig {
+        loss: 0.2,
+        latency: 3,
+        jitter: 3,
+        seed: 5,
+    },);
+    let clock = a.clock();
+    let mut host = LockstepSession::new(a, true, 1, DEFAULT_INPUT_DELAY,);
+    let mut client = LockstepSession::new(b, false, 1, DEFAULT_INPUT_DELAY,);
+    let mut host_state = GameState::new(960.0, 540.0, 2, 1,);
+    let mut client_state = GameState::new(960.0, 540.0, 2, 1,);
+    let input = GameInput::new();
+    for _ in 0..30 {
+        host.advance(&mut host_state, &input,);
+        client.advance(&mut client_state, &input,);
+        clock.tick();
+    }
+    host.quit();
+    // the client runs out of host inputs and would wait forever without the quit message
+    let mut result = StepResult::Advanced;
+    for _ in 0..30 {
+        clock.tick();
+        result = client.advance(&mut client_state, &input,);
+        if result == StepResult::PeerQuit {
+            break;
+        }
+    }
+    assert_eq!(result, StepResult::PeerQuit);
+}
@@ -0,0 +1,282 @@
+use GameInput;
+
+/// Bumped whenever the wire format or the simulation changes in a way that breaks lockstep.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Inputs are sent with this many older frames repeated, so a lost packet is covered by the
+/// next one.
+pub const MAX_INPUTS_PER_PACKET: usize = 32;
+/// Copies of `Quit` sent at once. Nothing is sent after it, so it can not be resent later.
+pub const QUIT_PACKETS: usize = 5;
+
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    /// Client asking to join. Repeated until a `Welcome` arrives.
+    Hello { version: u16, },
+    /// Host accepting a client, with the seed both simulations start from.
+    Welcome { version: u16, seed: u64, },
+    /// Inputs of the sender for `first_frame` and the frames after it. `ack` is the first
+    /// frame the sender has not received from us yet.
+    Inputs {
+        ack: u32,
+        first_frame: u32,
+        inputs: Vec<NetInput,>,
+    },
+    /// State hash of the sender after simulating `frame`.
+    Hash { frame: u32, hash: u64, },
+    /// The sender left the game.
+    Quit,
+}
+
+/// The part of `GameInput` that is sent over the network. Keys are already turned into action
+/// flags on the sending side, so the raw key state is left out.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NetInput {
+    pub buttons: u16,
+    pub move_x: f32,
+    pub move_y: f32,
+    pub aim_x: f32,
+    pub aim_y: f32,
+    pub mouse_x: f32,
+    pub mouse_y: f32,
+}
+
+impl NetInput {
+    pub fn from_input(input: &GameInput) -> NetInput {
+        let flags = [
+            input.up_key,
+            input.down_key,
+            input.left_key,
+            input.right_key,
+            input.shoot_right,
+            input.shoot_left,
+            input.shoot_up,
+            input.shoot_down,
+            input.quit_key,
+            input.space,
+            input.mouse_left,
+            input.mouse_right,
+        ];
+        let mut buttons = 0;
+        for (i, flag,) in flags.iter().enumerate() {
+            if *flag {
+                buttons |= 1 << i;
+            }
+        }
+        NetInput {
+            buttons,
+            move_x: input.move_x,
+            move_y: input.move_y,
+            aim_x: input.aim_x,
+            aim_y: input.aim_y,
+            mouse_x: input.mouse_x,
+            mouse_y: input.mouse_y,
+        }
+    }
+
+    pub fn to_input(&self) -> GameInput {
+        let bit = |i: u16| self.buttons & (1 << i) != 0;
+        let mut input = GameInput::new();
+        input.up_key = bit(0,);
+        input.down_key = bit(1,);
+        input.left_key = bit(2,);
+        input.right_key = bit(3,);
+        input.shoot_right = bit(4,);
+        input.shoot_left = bit(5,);
+        input.shoot_up = bit(6,);
+        input.shoot_down = bit(7,);
+        input.quit_key = bit(8,);
+        input.space = bit(9,);
+        input.mouse_left = bit(10,);
+        input.mouse_right = bit(11,);
+        input.move_x = self.move_x;
+        input.move_y = self.move_y;
+        input.aim_x = self.aim_x;
+        input.aim_y = self.aim_y;
+        input.mouse_x = self.mouse_x;
+        input.mouse_y = self.mouse_y;
+        input
+    }
+}
+
+const TAG_HELLO: u8 = 1;
+const TAG_WELCOME: u8 = 2;
+const TAG_INPUTS: u8 = 3;
+const TAG_HASH: u8 = 4;
+const TAG_QUIT: u8 = 5;
+
+impl Message {
+    /// Little endian, a tag byte followed by the fields in declaration order.
+    pub fn encode(&self) -> Vec<u8,> {
+        let mut out = Vec::new();
+        match self {
+            Message::Hello { version, } => {
+                out.push(TAG_HELLO,);
+                write_u16(&mut out, *version,);
+            }
+            Message::Welcome { version, seed, } => {
+                out.push(TAG_WELCOME,);
+                write_u16(&mut out, *version,);
+                write_u64(&mut out, *seed,);
+            }
+            Message::Inputs {
+                ack,
+                first_frame,
+                inputs,
+            } => {
+                out.push(TAG_INPUTS,);
+                write_u32(&mut out, *ack,);
+                write_u32(&mut out, *first_frame,);
+                out.push(inputs.len().min(255,) as u8,);
+                for input in inputs.iter().take(255,) {
+                    write_u16(&mut out, input.buttons,);
+                    write_f32(&mut out, input.move_x,);
+                    write_f32(&mut out, input.move_y,);
+                    write_f32(&mut out, input.aim_x,);
+                    write_f32(&mut out, input.aim_y,);
+                    write_f32(&mut out, input.mouse_x,);
+                    write_f32(&mut out, input.mouse_y,);
+                }
+            }
+            Message::Hash { frame, hash, } => {
+                out.push(TAG_HASH,);
+                write_u32(&mut out, *frame,);
+                write_u64(&mut out, *hash,);
+            }
+            Message::Quit => out.push(TAG_QUIT,),
+        }
+        out
+    }
+
+    /// Returns None for truncated or unknown packets.
+    pub fn decode(bytes: &[u8]) -> Option<Message,> {
+        let mut r = Reader { bytes, pos: 0, };
+        match r.u8()? {
+            TAG_HELLO => Some(Message::Hello { version: r.u16()?, },),
+            TAG_WELCOME => Some(Message::Welcome {
+                version: r.u16()?,
+                seed: r.u64()?,
+            },),
+            TAG_INPUTS => {
+                let ack = r.u32()?;
+                let first_frame = r.u32()?;
+                let count = r.u8()?;
+                let mut inputs = Vec::new();
+                for _ in 0..count {
+                    inputs.push(NetInput {
+                        buttons: r.u16()?,
+                        move_x: r.f32()?,
+                        move_y: r.f32()?,
+                        aim_x: r.f32()?,
+                        aim_y: r.f32()?,
+                        mouse_x: r.f32()?,
+                        mouse_y: r.f32()?,
+                    },);
+                }
+                Some(Message::Inputs {
+                    ack,
+                    first_frame,
+                    inputs,
+                },)
+            }
+            TAG_HASH => Some(Message::Hash {
+                frame: r.u32()?,
+                hash: r.u64()?,
+            },),
+            TAG_QUIT => Some(Message::Quit,),
+            _ => None,
+        }
+    }
+}
+
+fn write_u16(out: &mut Vec<u8,>, v: u16,) {
+    out.extend_from_slice(&v.to_le_bytes(),);
+}
+
+fn write_u32(out: &mut Vec<u8,>, v: u32,) {
+    out.extend_from_slice(&v.to_le_bytes(),);
+}
+
+fn write_u64(out: &mut Vec<u8,>, v: u64,) {
+    out.extend_from_slice(&v.to_le_bytes(),);
+}
+
+fn write_f32(out: &mut Vec<u8,>, v: f32,) {
+    write_u32(out, v.to_bits(),);
+}
+
+struct Reader<'a,> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a,> Reader<'a,> {
+    fn take(&mut self, n: usize,) -> Option<&'a [u8],> {
+        if self.pos + n > self.bytes.len() {
+            return None;
+        }
+        let slice = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Some(slice,)
+    }
+
+    fn u8(&mut self) -> Option<u8,> {
+        self.take(1,).map(|b| b[0],)
+    }
+
+    fn u16(&mut self) -> Option<u16,> {
+        self.take(2,).map(|b| u16::from_le_bytes([b[0], b[1]],),)
+    }
+
+    fn u32(&mut self) -> Option<u32,> {
+        self.take(4,)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]],),)
+    }
+
+    fn u64(&mut self) -> Option<u64,> {
+        self.take(8,).map(|b| {
+            let mut a = [0; 8];
+            a.copy_from_slice(b,);
+            u64::from_le_bytes(a,)
+        },)
+    }
+
+    fn f32(&mut self) -> Option<f32,> {
+        self.u32().map(f32::from_bits,)
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let mut input = GameInput::new();
+    input.up_key = true;
+    input.mouse_right = true;
+    input.aim_x = -0.25;
+    let messages = vec![
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+        },
+        Message::Welcome {
+            version: PROTOCOL_VERSION,
+            seed: 0xdead_beef_1234,
+        },
+        Message::Inputs {
+            ack: 7,
+            first_frame: 3,
+            inputs: vec![NetInput::from_input(&input,), NetInput::from_input(&GameInput::new(),)],
+        },
+        Message::Hash {
+            frame: 99,
+            hash: 12345678901,
+        },
+    ];
+    for m in messages {
+        let bytes = m.encode();
+        assert_eq!(Message::decode(&bytes,), Some(m));
+        assert_eq!(Message::decode(&bytes[..bytes.len() - 1],), None);
+    }
+    assert_eq!(Message::decode(&Message::Quit.encode(),), Some(Message::Quit));
+    let back = NetInput::from_input(&input,).to_input();
+    assert!(back.up_key && back.mouse_right && !back.down_key);
+    assert_eq!(back.aim_x, -0.25);
+}
@@ -0,0 +1,124 @@
+use game_state::GameState;
+use net::{lockstep::LockstepSession, rollback::RollbackSession};
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+use GameInput;
+
+pub mod lobby;
+pub mod lockstep;
+pub mod message;
+pub mod rollback;
+pub mod sim;
+
+/// Every networked frame simulates exactly this much time, so both peers do the same math.
+pub const FIXED_DELTA: f32 = 1.0 / 60.0;
+
+#[derive(Debug, PartialEq)]
+pub enum StepResult {
+    /// One frame was simulated.
+    Advanced,
+    /// Still waiting for the peer's input for the next frame.
+    Waiting,
+    /// The peer's state hash for `frame` did not match ours.
+    Desync { frame: u32, },
+    /// The peer left the game.
+    PeerQuit,
+}
+
+/// A networked game over UDP, made from a `lobby::Lobby`.
+pub enum NetSession {
+    Lockstep(LockstepSession<UdpTransport,>,),
+    Rollback(RollbackSession<UdpTransport,>,),
+}
+
+impl NetSession {
+    pub fn seed(&self) -> u64 {
+        match self {
+            NetSession::Lockstep(session,) => session.seed(),
+            NetSession::Rollback(session,) => session.seed(),
+        }
+    }
+
+    pub fn player_count(&self) -> usize {
+        match self {
+            NetSession::Lockstep(session,) => session.player_count(),
+            NetSession::Rollback(session,) => session.player_count(),
+        }
+    }
+
+    pub(crate) fn advance(&mut self, state: &mut GameState, input: &GameInput,) -> StepResult {
+        match self {
+            NetSession::Lockstep(session,) => session.advance(state, input,),
+            NetSession::Rollback(session,) => session.advance(state, input,),
+        }
+    }
+
+    /// Tells the peer we are leaving, so it stops waiting for our inputs.
+    pub fn quit(&mut self) {
+        match self {
+            NetSession::Lockstep(session,) => session.quit(),
+            NetSession::Rollback(session,) => session.quit(),
+        }
+    }
+}
+
+/// Unreliable, unordered packet delivery to a single peer. Both calls must not block.
+pub trait Transport {
+    fn send(&mut self, bytes: &[u8]);
+    /// Next packet that has arrived, if any.
+    fn recv(&mut self) -> Option<Vec<u8,>,>;
+}
+
+/// Non blocking UDP socket talking to one peer. Packets from other addresses are ignored.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+impl UdpTransport {
+    pub fn new(socket: UdpSocket, peer: SocketAddr,) -> io::Result<UdpTransport,> {
+        socket.set_nonblocking(true,)?;
+        Ok(UdpTransport { socket, peer, },)
+    }
+
+    /// `bind("0.0.0.0:7000", "192.168.1.20:7000")`
+    pub fn bind(local: &str, peer: &str,) -> io::Result<UdpTransport,> {
+        let socket = UdpSocket::bind(local,)?;
+        let peer = match peer.parse() {
+            Ok(peer,) => peer,
+            Err(_,) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("bad peer address `{}`", peer),
+                ),)
+            }
+        };
+        UdpTransport::new(socket, peer,)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr,> {
+        self.socket.local_addr()
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, bytes: &[u8]) {
+        // UDP is allowed to lose packets, the protocol resends what matters
+        let _ = self.socket.send_to(bytes, self.peer,);
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8,>,> {
+        let mut buffer = [0u8; 2048];
+        loop {
+            match self.socket.recv_from(&mut buffer,) {
+                Ok((len, from,),) if from == self.peer => return Some(buffer[..len].to_vec(),),
+                Ok(_,) => continue,
+                // WouldBlock means nothing is waiting. Other errors, like the ICMP port
+                // unreachable Windows reports as ConnectionReset, are treated the same.
+                Err(_,) => return None,
+            }
+        }
+    }
+}
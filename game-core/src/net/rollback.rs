@@ -0,0 +1,425 @@
+use game_state::GameState;
+use math::vector::Vector2d;
+use net::{
+    message::{Message, NetInput, MAX_INPUTS_PER_PACKET, PROTOCOL_VERSION, QUIT_PACKETS},
+    StepResult, Transport, FIXED_DELTA,
+};
+use std::collections::BTreeMap;
+use GameInput;
+
+/// Rollback hides latency by predicting, so only a small input delay is needed.
+pub const DEFAULT_ROLLBACK_INPUT_DELAY: u32 = 1;
+/// How many frames we may run ahead of the last confirmed remote input before waiting.
+pub const MAX_PREDICTION_FRAMES: u32 = 8;
+/// Part of a rollback correction still shown after one rendered frame.
+const SMOOTHING: f32 = 0.8;
+
+/// Two peer rollback in the GGPO style. Missing remote inputs are predicted by repeating the
+/// last confirmed one, and the game runs on. A snapshot of the state is kept for every
+/// frame that is not confirmed yet. When a remote input arrives that differs from the
+/// prediction, the state is restored to that frame and simulated forward again. The jump this
+/// causes for players is smoothed out over the next frames when drawing.
+pub struct RollbackSession<T: Transport,> {
+    transport: T,
+    is_host: bool,
+    seed: u64,
+    input_delay: u32,
+    /// Next frame to simulate.
+    frame: u32,
+    /// Next frame a local input will be read for.
+    next_local_frame: u32,
+    local_inputs: BTreeMap<u32, NetInput,>,
+    /// Confirmed remote inputs.
+    remote_inputs: BTreeMap<u32, NetInput,>,
+    /// Remote input each simulated frame was run with, predicted or confirmed.
+    used_remote: BTreeMap<u32, NetInput,>,
+    /// First frame we have not received a remote input for.
+    remote_next: u32,
+    /// First frame the peer has not received from us.
+    peer_ack: u32,
+    /// State before each unconfirmed frame.
+    snapshots: BTreeMap<u32, GameState,>,
+    /// State hash after each simulated frame.
+    hashes: BTreeMap<u32, u64,>,
+    /// First confirmed frame whose hash is not sent yet.
+    next_hash_frame: u32,
+    remote_hashes: BTreeMap<u32, u64,>,
+    /// Oldest frame that was simulated with a wrong prediction.
+    rollback_from: Option<u32,>,
+    desync_frame: Option<u32,>,
+    peer_quit: bool,
+    /// How far each player is drawn from its real position, indexed by slot.
+    render_offsets: Vec<Vector2d,>,
+    rollbacks: u32,
+}
+
+impl<T: Transport,> RollbackSession<T,> {
+    pub(crate) fn new(
+        transport: T,
+        is_host: bool,
+        seed: u64,
+        input_delay: u32,
+    ) -> RollbackSession<T,> {
+        let neutral = NetInput::from_input(&GameInput::new(),);
+        let mut local_inputs = BTreeMap::new();
+        let mut remote_inputs = BTreeMap::new();
+        for frame in 0..input_delay {
+            local_inputs.insert(frame, neutral,);
+            remote_inputs.insert(frame, neutral,);
+        }
+        RollbackSession {
+            transport,
+            is_host,
+            seed,
+            input_delay,
+            frame: 0,
+            next_local_frame: input_delay,
+            local_inputs,
+            remote_inputs,
+            used_remote: BTreeMap::new(),
+            remote_next: input_delay,
+            peer_ack: input_delay,
+            snapshots: BTreeMap::new(),
+            hashes: BTreeMap::new(),
+            next_hash_frame: 0,
+            remote_hashes: BTreeMap::new(),
+            rollback_from: None,
+            desync_frame: None,
+            peer_quit: false,
+            render_offsets: vec![Vector2d::new(0.0, 0.0,); 2],
+            rollbacks: 0,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn local_slot(&self) -> usize {
+        if self.is_host {
+            0
+        } else {
+            1
+        }
+    }
+
+    pub fn player_count(&self) -> usize {
+        2
+    }
+
+    /// Next frame to be simulated.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Frames up to this one (not included) are simulated with confirmed inputs only.
+    pub fn confirmed_frame(&self) -> u32 {
+        self.remote_next.min(self.frame,)
+    }
+
+    /// Number of times the state was rolled back and simulated again.
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    /// Tells the peer we are leaving. The session should not be advanced after this.
+    pub fn quit(&mut self) {
+        for _ in 0..QUIT_PACKETS {
+            self.transport.send(&Message::Quit.encode(),);
+        }
+    }
+
+    /// Call once per rendered frame with this frame's local input. `input` must already have
+    /// its action flags set, the raw keys are not sent.
+    pub(crate) fn advance(&mut self, state: &mut GameState, input: &GameInput,) -> StepResult {
+        let result = self.step(state, input,);
+        if result == StepResult::Advanced {
+            state.play_sounds();
+        }
+        state.draw(&self.render_offsets,);
+        for offset in &mut self.render_offsets {
+            *offset = offset.mul(SMOOTHING,);
+        }
+        result
+    }
+
+    /// `advance` without drawing.
+    pub(crate) fn step(&mut self, state: &mut GameState, input: &GameInput,) -> StepResult {
+        self.receive();
+        if self.peer_quit {
+            return StepResult::PeerQuit;
+        }
+        if let Some(from,) = self.rollback_from.take() {
+            self.rollback(state, from,);
+        }
+
+        if self.next_local_frame <= self.frame + self.input_delay {
+            self.local_inputs
+                .insert(self.next_local_frame, NetInput::from_input(input,),);
+            self.next_local_frame += 1;
+        }
+        self.send_inputs();
+
+        let mut result = StepResult::Waiting;
+        let can_predict = self.frame < self.remote_next + MAX_PREDICTION_FRAMES;
+        if self.local_inputs.contains_key(&self.frame,) && can_predict {
+            let frame = self.frame;
+            self.simulate_frame(state, frame,);
+            self.frame += 1;
+            result = StepResult::Advanced;
+        }
+
+        self.send_hashes();
+        self.forget_old_frames();
+        match self.desync_frame {
+            Some(frame,) => StepResult::Desync { frame, },
+            None => result,
+        }
+    }
+
+    fn simulate_frame(&mut self, state: &mut GameState, frame: u32,) {
+        self.snapshots.insert(frame, state.clone(),);
+        let remote = match self.remote_inputs.get(&frame,) {
+            Some(input,) => *input,
+            None => self.predict_remote(frame,),
+        };
+        self.used_remote.insert(frame, remote,);
+        let local = self.local_inputs[&frame].to_input();
+        let remote = remote.to_input();
+        let inputs = if self.is_host {
+            [local, remote]
+        } else {
+            [remote, local]
+        };
+        state.simulate(&inputs, (frame + 1) as f32 * FIXED_DELTA, FIXED_DELTA,);
+        self.hashes.insert(frame, state.state_hash(),);
+    }
+
+    /// The remote player probably keeps doing what they did last.
+    fn predict_remote(&self, frame: u32,) -> NetInput {
+        match self.remote_inputs.range(..frame,).next_back() {
+            Some((_, input,),) => *input,
+            None => NetInput::from_input(&GameInput::new(),),
+        }
+    }
+
+    fn rollback(&mut self, state: &mut GameState, from: u32,) {
+        let snapshot = match self.snapshots.get(&from,) {
+            Some(snapshot,) => snapshot.clone(),
+            None => return,
+        };
+        let before = state.player_positions();
+        *state = snapshot;
+        for frame in from..self.frame {
+            self.simulate_frame(state, frame,);
+        }
+        // draw players where they were, and let them slide to where they should be
+        let after = state.player_positions();
+        for (slot, (old, new,),) in before.iter().zip(after.iter(),).enumerate() {
+            if let Some(offset,) = self.render_offsets.get_mut(slot,) {
+                let mut jump = *old;
+                jump.sub(new,);
+                offset.add(&jump,);
+            }
+        }
+        self.rollbacks += 1;
+    }
+
+    fn receive(&mut self) {
+        while let Some(bytes,) = self.transport.recv() {
+            match Message::decode(&bytes,) {
+                Some(Message::Hello { .. },) if self.is_host => {
+                    // our welcome got lost, the client is still in the lobby
+                    self.transport.send(
+                        &Message::Welcome {
+                            version: PROTOCOL_VERSION,
+                            seed: self.seed,
+                        }.encode(),
+                    );
+                }
+                Some(Message::Inputs {
+                    ack,
+                    first_frame,
+                    inputs,
+                },) => {
+                    self.peer_ack = self.peer_ack.max(ack,);
+                    for (i, input,) in inputs.iter().enumerate() {
+                        let frame = first_frame + i as u32;
+                        if frame < self.remote_next || self.remote_inputs.contains_key(&frame,) {
+                            continue;
+                        }
+                        self.remote_inputs.insert(frame, *input,);
+                        let mispredicted = match self.used_remote.get(&frame,) {
+                            Some(used,) => used != input,
+                            None => false,
+                        };
+                        if mispredicted {
+                            let from = self.rollback_from.unwrap_or(frame,).min(frame,);
+                            self.rollback_from = Some(from,);
+                        }
+                    }
+                    while self.remote_inputs.contains_key(&self.remote_next,) {
+                        self.remote_next += 1;
+                    }
+                }
+                Some(Message::Hash { frame, hash, },) => {
+                    self.remote_hashes.insert(frame, hash,);
+                    self.compare_hashes();
+                }
+                Some(Message::Quit,) => self.peer_quit = true,
+                _ => {}
+            }
+        }
+    }
+
+    fn send_inputs(&mut self) {
+        let first_frame = match self.local_inputs.range(self.peer_ack..,).next() {
+            Some((frame, _,),) => *frame,
+            None => return,
+        };
+        let inputs: Vec<NetInput,> = self
+            .local_inputs
+            .range(first_frame..,)
+            .take(MAX_INPUTS_PER_PACKET,)
+            .map(|(_, input,)| *input,)
+            .collect();
+        self.transport.send(
+            &Message::Inputs {
+                ack: self.remote_next,
+                first_frame,
+                inputs,
+            }.encode(),
+        );
+    }
+
+    /// Only hashes of confirmed frames are sent, predicted ones are allowed to differ.
+    fn send_hashes(&mut self) {
+        while self.next_hash_frame < self.confirmed_frame() {
+            let frame = self.next_hash_frame;
+            if let Some(hash,) = self.hashes.get(&frame,) {
+                self.transport
+                    .send(&Message::Hash { frame, hash: *hash, }.encode(),);
+            }
+            self.next_hash_frame += 1;
+        }
+        self.compare_hashes();
+    }
+
+    fn compare_hashes(&mut self) {
+        let confirmed = self.confirmed_frame();
+        let frames: Vec<u32,> = self
+            .remote_hashes
+            .keys()
+            .filter(|f| **f < confirmed,)
+            .cloned()
+            .collect();
+        for frame in frames {
+            let remote = self.remote_hashes.remove(&frame,);
+            if let (Some(local,), Some(remote,),) = (self.hashes.get(&frame,), remote,) {
+                if *local != remote && self.desync_frame.is_none() {
+                    self.desync_frame = Some(frame,);
+                }
+            }
+        }
+    }
+
+    /// Frames before the confirmed frame can never be rolled back to.
+    fn forget_old_frames(&mut self) {
+        let confirmed = self.confirmed_frame();
+        self.snapshots = self.snapshots.split_off(&confirmed,);
+        self.used_remote = self.used_remote.split_off(&confirmed,);
+        // keep the last confirmed remote input around for predictions
+        self.remote_inputs = self
+            .remote_inputs
+            .split_off(&confirmed.saturating_sub(1,),);
+        self.local_inputs = self.local_inputs.split_off(&confirmed.min(self.peer_ack,),);
+        // the peer may still send hashes for frames we already confirmed
+        let oldest_hash = confirmed.saturating_sub(MAX_PREDICTION_FRAMES * 16,);
+        self.hashes = self.hashes.split_off(&oldest_hash,);
+        self.remote_hashes = self.remote_hashes.split_off(&oldest_hash,);
+    }
+}
+
+#[cfg(test)]
+fn scripted_input(slot: usize, frame: u32,) -> GameInput {
+    let mut input = GameInput::new();
+    let step = frame / 7 + slot as u32 * 3;
+    input.up_key = step.is_multiple_of(4,);
+    input.left_key = step % 5 == 1;
+    input.down_key = step % 4 == 2;
+    input.right_key = step.is_multiple_of(3,);
+    input.shoot_up = step % 3 == 1;
+    input.shoot_left = step.is_multiple_of(2,);
+    input
+}
+
+#[test]
+fn test_rollback_converges_with_latency_and_jitter() {
+    use net::sim::{LinkConfig, SimulatedTransport};
+    let (a, b,) = SimulatedTransport::pair(LinkConfig {
+        loss: 0.1,
+        latency: 4,
+        jitter: 4,
+        seed: 3,
+    },);
+    let clock = a.clock();
+    let mut host = RollbackSession::new(a, true, 55, DEFAULT_ROLLBACK_INPUT_DELAY,);
+    let mut client = RollbackSession::new(b, false, 55, DEFAULT_ROLLBACK_INPUT_DELAY,);
+    let mut host_state = GameState::new(960.0, 540.0, 2, 55,);
+    let mut client_state = GameState::new(960.0, 540.0, 2, 55,);
+
+    // a frame's hash is final once it is confirmed, record them as they get there
+    let frames = 400;
+    let mut host_hashes = Vec::new();
+    let mut client_hashes = Vec::new();
+    let mut ticks = 0;
+    while host_hashes.len() < frames || client_hashes.len() < frames {
+        ticks += 1;
+        assert!(ticks < 4000, "rollback stalled");
+        let input = scripted_input(0, host.next_local_frame,);
+        host.step(&mut host_state, &input,);
+        let input = scripted_input(1, client.next_local_frame,);
+        client.step(&mut client_state, &input,);
+        assert!(host.desync_frame.is_none() && client.desync_frame.is_none());
+        while (host_hashes.len() as u32) < host.confirmed_frame() {
+            host_hashes.push(host.hashes[&(host_hashes.len() as u32)],);
+        }
+        while (client_hashes.len() as u32) < client.confirmed_frame() {
+            client_hashes.push(client.hashes[&(client_hashes.len() as u32)],);
+        }
+        clock.tick();
+    }
+    assert!(host.rollbacks() > 0 && client.rollbacks() > 0);
+    host_hashes.truncate(frames,);
+    client_hashes.truncate(frames,);
+    assert_eq!(host_hashes, client_hashes);
+}
+
+#[test]
+fn test_rollback_restores_mispredicted_frames() {
+    use net::sim::{LinkConfig, SimulatedTransport};
+    // inputs take 6 ticks to arrive, so the host runs ahead on predictions of the client
+    let (a, b,) = SimulatedTransport::pair(LinkConfig {
+        loss: 0.0,
+        latency: 6,
+        jitter: 0,
+        seed: 1,
+    },);
+    let clock = a.clock();
+    let mut host = RollbackSession::new(a, true, 9, 0,);
+    let mut client = RollbackSession::new(b, false, 9, 0,);
+    let mut host_state = GameState::new(960.0, 540.0, 2, 9,);
+    let mut client_state = GameState::new(960.0, 540.0, 2, 9,);
+    let mut moving = GameInput::new();
+    moving.right_key = true;
+    for _ in 0..60 {
+        host.step(&mut host_state, &GameInput::new(),);
+        client.step(&mut client_state, &moving,);
+        clock.tick();
+    }
+    // the host predicted the client standing still, and had to correct it
+    assert!(host.rollbacks() > 0);
+    let confirmed = host.confirmed_frame().min(client.confirmed_frame(),);
+    assert!(confirmed > 0);
+    assert_eq!(host.hashes.get(&(confirmed - 1)), client.hashes.get(&(confirmed - 1)));
+}
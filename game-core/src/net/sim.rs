@@ -0,0 +1,111 @@
+use net::Transport;
+use rng::Rng;
+use std::{cell::RefCell, rc::Rc};
+
+/// How bad the simulated network is. Times are in ticks, see `SimulatedTransport::tick`.
+#[derive(Clone, Copy)]
+pub struct LinkConfig {
+    /// Chance from 0.0 to 1.0 that a packet is dropped.
+    pub loss: f32,
+    pub latency: u32,
+    /// Extra random delay from 0 to `jitter` ticks, packets can arrive out of order.
+    pub jitter: u32,
+    pub seed: u64,
+}
+
+impl LinkConfig {
+    pub fn perfect() -> LinkConfig {
+        LinkConfig {
+            loss: 0.0,
+            latency: 0,
+            jitter: 0,
+            seed: 1,
+        }
+    }
+}
+
+struct InFlight {
+    arrives: u32,
+    to: usize,
+    bytes: Vec<u8,>,
+}
+
+struct Link {
+    config: LinkConfig,
+    rng: Rng,
+    now: u32,
+    in_flight: Vec<InFlight,>,
+}
+
+/// One end of an in-process link. Used in tests to run several peers in the same process
+/// with loss, latency and jitter that repeat exactly for a given seed.
+pub struct SimulatedTransport {
+    link: Rc<RefCell<Link,>,>,
+    side: usize,
+}
+
+impl SimulatedTransport {
+    pub fn pair(config: LinkConfig) -> (SimulatedTransport, SimulatedTransport,) {
+        let link = Rc::new(RefCell::new(Link {
+            config,
+            rng: Rng::new(config.seed,),
+            now: 0,
+            in_flight: Vec::new(),
+        },),);
+        (
+            SimulatedTransport {
+                link: link.clone(),
+                side: 0,
+            },
+            SimulatedTransport { link, side: 1, },
+        )
+    }
+
+    /// Handle for moving time on the link, stays usable after the transport is moved away.
+    pub fn clock(&self) -> LinkClock {
+        LinkClock {
+            link: self.link.clone(),
+        }
+    }
+}
+
+pub struct LinkClock {
+    link: Rc<RefCell<Link,>,>,
+}
+
+impl LinkClock {
+    /// Moves time on the link forward by one tick.
+    pub fn tick(&self) {
+        self.link.borrow_mut().now += 1;
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn send(&mut self, bytes: &[u8]) {
+        let mut link = self.link.borrow_mut();
+        if link.rng.next_f32() < link.config.loss {
+            return;
+        }
+        let mut delay = link.config.latency;
+        if link.config.jitter > 0 {
+            delay += (link.rng.next_u64() % (link.config.jitter as u64 + 1)) as u32;
+        }
+        let arrives = link.now + delay;
+        link.in_flight.push(InFlight {
+            arrives,
+            to: 1 - self.side,
+            bytes: bytes.to_vec(),
+        },);
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8,>,> {
+        let mut link = self.link.borrow_mut();
+        let now = link.now;
+        let side = self.side;
+        let index = link
+            .in_flight
+            .iter()
+            .position(|p| p.to == side && p.arrives <= now,)?;
+        Some(link.in_flight.remove(index,).bytes,)
+    }
+}
@@ -0,0 +1,240 @@
+//! Scoped timers around the game systems, only built with the `profiling` feature. Use the
+//! `profile_scope!` macro, it expands to nothing without the feature. Timings are kept in a ring
+//! buffer and can be written out in the Chrome trace format, which `chrome://tracing` and
+//! Perfetto open.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+
+/// Events kept before the oldest ones are overwritten, a few seconds of frames.
+pub const DEFAULT_CAPACITY: usize = 16384;
+
+/// One finished scope. Times are seconds on the profiler's clock.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Event {
+    pub name: &'static str,
+    pub start: f64,
+    pub duration: f64,
+    /// Scopes open around this one when it started.
+    pub depth: u32,
+}
+
+/// Total time spent in scopes with one name.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Total {
+    pub name: &'static str,
+    pub duration: f64,
+    pub count: u32,
+}
+
+pub struct Profiler {
+    clock: fn() -> f64,
+    events: Vec<Event,>,
+    capacity: usize,
+    next: usize,
+    depth: u32,
+}
+
+impl Profiler {
+    /// `clock` returns seconds from any fixed point, `Platform::now` in the game.
+    pub fn new(capacity: usize, clock: fn() -> f64,) -> Profiler {
+        Profiler {
+            clock,
+            events: Vec::new(),
+            capacity: capacity.max(1,),
+            next: 0,
+            depth: 0,
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: fn() -> f64,) {
+        self.clock = clock;
+    }
+
+    pub fn now(&self) -> f64 {
+        (self.clock)()
+    }
+
+    /// Opens a scope, returns its start time and depth for `end`.
+    pub fn begin(&mut self) -> (f64, u32,) {
+        let depth = self.depth;
+        self.depth += 1;
+        (self.now(), depth,)
+    }
+
+    /// Closes the scope `begin` opened.
+    pub fn end(&mut self, name: &'static str, start: f64, depth: u32,) {
+        self.depth = self.depth.saturating_sub(1,);
+        let event = Event {
+            name,
+            start,
+            duration: (self.now() - start).max(0.0,),
+            depth,
+        };
+        if self.events.len() < self.capacity {
+            self.events.push(event,);
+        } else {
+            self.events[self.next] = event;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    /// Kept events, oldest first.
+    pub fn events(&self) -> Vec<Event,> {
+        if self.events.len() < self.capacity {
+            return self.events.clone();
+        }
+        let mut events = self.events[self.next..].to_vec();
+        events.extend_from_slice(&self.events[..self.next],);
+        events
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.next = 0;
+    }
+
+    /// Time per scope name over the kept events, the most expensive first.
+    pub fn totals(&self) -> Vec<Total,> {
+        let mut totals: Vec<Total,> = Vec::new();
+        for event in &self.events {
+            match totals.iter_mut().find(|t| t.name == event.name,) {
+                Some(total,) => {
+                    total.duration += event.duration;
+                    total.count += 1;
+                }
+                None => totals.push(Total {
+                    name: event.name,
+                    duration: event.duration,
+                    count: 1,
+                },),
+            }
+        }
+        totals.sort_by(|a, b| b.duration.partial_cmp(&a.duration,).unwrap(),);
+        totals
+    }
+
+    /// Kept events as Chrome trace JSON, complete events in microseconds.
+    pub fn chrome_trace(&self) -> String {
+        let mut json = String::from("{\"traceEvents\":[",);
+        for (index, event,) in self.events().iter().enumerate() {
+            if index > 0 {
+                json.push(',',);
+            }
+            json.push_str(&format!(
+                "{{\"name\":\"{}\",\"cat\":\"game\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\
+                 \"pid\":1,\"tid\":1}}",
+                escape(event.name,),
+                event.start * 1_000_000.0,
+                event.duration * 1_000_000.0
+            ),);
+        }
+        json.push_str("],\"displayTimeUnit\":\"ms\"}",);
+        json
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\"",),
+            '\\' => escaped.push_str("\\\\",),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32),),
+            c => escaped.push(c,),
+        }
+    }
+    escaped
+}
+
+thread_local!(static PROFILER: RefCell<Option<Profiler,>,> = const { RefCell::new(None,) });
+
+/// The game's profiler, made on first use with the platform clock. One per thread, so tests
+/// running side by side do not mix their timings.
+fn with_profiler<R, F: FnOnce(&mut Profiler,) -> R,>(f: F,) -> R {
+    PROFILER.with(|cell| {
+        let mut profiler = cell.borrow_mut();
+        f(profiler.get_or_insert_with(|| Profiler::new(DEFAULT_CAPACITY, ::now,),),)
+    },)
+}
+
+/// Times from when it is made until it is dropped. Made by `profile_scope!`.
+pub struct Scope {
+    name: &'static str,
+    start: f64,
+    depth: u32,
+}
+
+impl Scope {
+    pub fn new(name: &'static str) -> Scope {
+        let (start, depth,) = with_profiler(|p| p.begin(),);
+        Scope { name, start, depth, }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        with_profiler(|p| p.end(self.name, self.start, self.depth,),);
+    }
+}
+
+/// Uses `clock` instead of the platform's, for headless runs without a platform.
+pub fn set_clock(clock: fn() -> f64) {
+    with_profiler(|p| p.set_clock(clock,),);
+}
+
+pub fn chrome_trace() -> String {
+    with_profiler(|p| p.chrome_trace(),)
+}
+
+pub fn write_chrome_trace(path: &str) -> io::Result<(),> {
+    fs::write(path, chrome_trace(),)
+}
+
+pub fn totals() -> Vec<Total,> {
+    with_profiler(|p| p.totals(),)
+}
+
+pub fn clear() {
+    with_profiler(|p| p.clear(),);
+}
+
+#[test]
+fn test_scopes_and_trace() {
+    use std::cell::Cell;
+    thread_local!(static TIME: Cell<f64> = const { Cell::new(0.0,) });
+    fn clock() -> f64 {
+        TIME.with(|t| t.get(),)
+    }
+    fn advance(seconds: f64) {
+        TIME.with(|t| t.set(t.get() + seconds,),);
+    }
+
+    let mut profiler = Profiler::new(3, clock,);
+    let (frame, frame_depth,) = profiler.begin();
+    let (inner, inner_depth,) = profiler.begin();
+    advance(0.002,);
+    profiler.end("collisions", inner, inner_depth,);
+    advance(0.001,);
+    profiler.end("frame", frame, frame_depth,);
+
+    let events = profiler.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!((events[0].name, events[0].depth,), ("collisions", 1,));
+    assert_eq!((events[1].name, events[1].depth,), ("frame", 0,));
+    assert!((events[1].duration - 0.003).abs() < 1e-9);
+    assert_eq!(profiler.totals()[0].name, "frame");
+
+    let trace = profiler.chrome_trace();
+    assert!(trace.starts_with("{\"traceEvents\":[{\"name\":\"collisions\""));
+    assert!(trace.contains("\"ph\":\"X\",\"ts\":0.000,\"dur\":2000.000"));
+
+    // the ring keeps the newest events
+    for name in &["a", "b", "c"] {
+        let (start, depth,) = profiler.begin();
+        profiler.end(name, start, depth,);
+    }
+    let names: Vec<&str,> = profiler.events().iter().map(|e| e.name,).collect();
+    assert_eq!(names, vec!["a", "b", "c"]);
+}
@@ -0,0 +1,52 @@
+/// Small seeded random number generator (xorshift64*). Gives the same sequence on every
+/// platform, so two simulations started from the same seed stay identical.
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck on zero
+        let state = if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed };
+        Rng { state, }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D,)
+    }
+
+    /// Value in 0.0..1.0.
+    pub fn next_f32(&mut self) -> f32 {
+        // top 24 bits fit exactly in the f32 mantissa
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Value in min..max.
+    pub fn range(&mut self, min: f32, max: f32,) -> f32 {
+        self.next_f32() * (max - min) + min
+    }
+
+    pub fn get_state(&self) -> u64 {
+        self.state
+    }
+}
+
+#[test]
+fn test_rng_is_repeatable() {
+    let mut a = Rng::new(42,);
+    let mut b = Rng::new(42,);
+    for _ in 0..100 {
+        let v = a.next_f32();
+        assert_eq!(v, b.next_f32());
+        assert!((0.0..1.0).contains(&v));
+    }
+    let mut c = Rng::new(43,);
+    assert!(a.next_u64() != c.next_u64());
+}
@@ -0,0 +1,238 @@
+/// Repeating timers never fire more often than this many times in one update, the rest of a
+/// long frame is skipped instead of spawning a flood of enemies at once.
+pub const MAX_REPEATS_PER_UPDATE: u32 = 16;
+
+/// Shortest interval of a repeating timer, in seconds.
+pub const MIN_INTERVAL: f32 = 0.001;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct TimerId(u64,);
+
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub(crate) struct Timer<E,> {
+    id: TimerId,
+    event: E,
+    /// Scheduler time it fires at next.
+    due: f64,
+    /// Some for repeating timers.
+    interval: Option<f64,>,
+}
+
+/// One-shot and repeating timers that hand back an event of type `E` when they fire, the owner
+/// decides what to do with it. Time only moves in `update`, so a test can drive it like a clock
+/// by hand.
+///
+/// Timers that fire in the same update come out in the order they were due, ties in the order
+/// they were added, so every peer sees the same order. Time is kept in f64 so adding up small
+/// deltas does not drift over a long game.
+#[derive(Clone)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Scheduler<E,> {
+    timers: Vec<Timer<E,>,>,
+    time: f64,
+    time_scale: f32,
+    paused: bool,
+    next_id: u64,
+}
+
+impl<E: Clone,> Scheduler<E,> {
+    pub fn new() -> Scheduler<E,> {
+        Scheduler {
+            timers: Vec::new(),
+            time: 0.0,
+            time_scale: 1.0,
+            paused: false,
+            next_id: 0,
+        }
+    }
+
+    /// Fires `event` once, `delay` seconds from now.
+    pub fn after(&mut self, delay: f32, event: E,) -> TimerId {
+        self.add(delay, None, event,)
+    }
+
+    /// Fires `event` every `interval` seconds, the first time one interval from now.
+    pub fn every(&mut self, interval: f32, event: E,) -> TimerId {
+        let interval = if interval.is_finite() {
+            interval.max(MIN_INTERVAL,)
+        } else {
+            MIN_INTERVAL
+        };
+        self.add(interval, Some(interval,), event,)
+    }
+
+    fn add(&mut self, delay: f32, interval: Option<f32,>, event: E,) -> TimerId {
+        let id = TimerId(self.next_id,);
+        self.next_id += 1;
+        let delay = if delay.is_nan() { 0.0 } else { delay.max(0.0,) };
+        self.timers.push(Timer {
+            id,
+            event,
+            due: self.time + f64::from(delay,),
+            interval: interval.map(f64::from,),
+        },);
+        id
+    }
+
+    /// Returns false if the timer already fired or was cancelled.
+    pub fn cancel(&mut self, id: TimerId,) -> bool {
+        let count = self.timers.len();
+        self.timers.retain(|t| t.id != id,);
+        self.timers.len() != count
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.timers.clear();
+    }
+
+    /// Seconds of scheduler time until the timer fires next.
+    pub fn remaining(&self, id: TimerId,) -> Option<f32,> {
+        self.timers.iter().find(|t| t.id == id,).map(|t| (t.due - self.time).max(0.0,) as f32,)
+    }
+
+    pub fn is_pending(&self, id: TimerId,) -> bool {
+        self.timers.iter().any(|t| t.id == id,)
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// 2 runs timers twice as fast, 0.5 half as fast. Negative scales count as 0.
+    pub fn set_time_scale(&mut self, scale: f32,) {
+        self.time_scale = if scale.is_finite() { scale.max(0.0,) } else { 1.0 };
+    }
+
+    pub fn get_time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Seconds of scheduler time so far, scaled and without the paused parts.
+    pub fn get_time(&self) -> f32 {
+        self.time as f32
+    }
+
+    /// Moves time forward by `delta` real seconds and returns the events of every timer that
+    /// fired. Deltas that are negative or not finite are ignored.
+    pub fn update(&mut self, delta: f32,) -> Vec<E,> {
+        let mut fired = Vec::new();
+        if self.paused || !delta.is_finite() || delta <= 0.0 {
+            return fired;
+        }
+        self.time += f64::from(delta * self.time_scale,);
+
+        // how often each repeating timer fired in this update
+        let mut repeats: Vec<(TimerId, u32,),> = Vec::new();
+        loop {
+            let time = self.time;
+            let next = self
+                .timers
+                .iter()
+                .enumerate()
+                .filter(|(_, t,)| {
+                    t.due <= time
+                        && !repeats
+                            .iter()
+                            .any(|r| r.0 == t.id && r.1 >= MAX_REPEATS_PER_UPDATE,)
+                },)
+                .min_by(|(_, a,), (_, b,)| {
+                    a.due.partial_cmp(&b.due,).unwrap().then(a.id.0.cmp(&b.id.0,),)
+                },)
+                .map(|(index, _,)| index,);
+            let index = match next {
+                Some(index,) => index,
+                None => break,
+            };
+            fired.push(self.timers[index].event.clone(),);
+            let interval = match self.timers[index].interval {
+                Some(interval,) => interval,
+                None => {
+                    self.timers.remove(index,);
+                    continue;
+                }
+            };
+
+            let id = self.timers[index].id;
+            let count = match repeats.iter_mut().find(|r| r.0 == id,) {
+                Some(r,) => {
+                    r.1 += 1;
+                    r.1
+                }
+                None => {
+                    repeats.push((id, 1,),);
+                    1
+                }
+            };
+            let timer = &mut self.timers[index];
+            timer.due += interval;
+            if count >= MAX_REPEATS_PER_UPDATE && timer.due <= self.time {
+                // drop the backlog, next one is a whole interval after the current time
+                let behind = ((self.time - timer.due) / interval).floor() + 1.0;
+                timer.due += behind * interval;
+            }
+        }
+        fired
+    }
+}
+
+impl<E: Clone,> Default for Scheduler<E,> {
+    fn default() -> Scheduler<E,> {
+        Scheduler::new()
+    }
+}
+
+#[test]
+fn test_timers_fire_in_order() {
+    let mut scheduler = Scheduler::new();
+    scheduler.after(5.0, "wave 3",);
+    let spawn = scheduler.every(1.0, "spawn",);
+    scheduler.after(0.5, "end invulnerability",);
+    let cancelled = scheduler.after(0.5, "never",);
+    assert!(scheduler.cancel(cancelled,));
+    assert!(!scheduler.cancel(cancelled,));
+
+    assert_eq!(scheduler.update(0.25,), Vec::<&str,>::new());
+    assert_eq!(scheduler.update(0.25,), vec!["end invulnerability"]);
+    assert_eq!(scheduler.update(0.5,), vec!["spawn"]);
+    assert_eq!(scheduler.remaining(spawn,), Some(1.0));
+
+    // one long frame fires everything that came due, in order, ties in the order added
+    assert_eq!(
+        scheduler.update(4.0,),
+        vec!["spawn", "spawn", "spawn", "wave 3", "spawn"]
+    );
+
+    // nothing moves while paused
+    scheduler.pause();
+    assert!(scheduler.update(10.0,).is_empty());
+    scheduler.resume();
+
+    // twice as fast
+    scheduler.set_time_scale(2.0,);
+    assert_eq!(scheduler.update(0.5,), vec!["spawn"]);
+    assert_eq!(scheduler.get_time(), 6.0);
+
+    // huge and broken deltas do not flood or break it
+    assert!(scheduler.update(f32::NAN,).is_empty());
+    assert_eq!(scheduler.update(1000.0,).len(), MAX_REPEATS_PER_UPDATE as usize);
+    assert!(scheduler.update(0.1,).is_empty());
+}
+
+#[test]
+fn test_long_game_does_not_drift() {
+    let mut scheduler = Scheduler::new();
+    scheduler.every(1.0, (),);
+    // an hour of frames
+    let fired: usize = (0..60 * 60 * 60).map(|_| scheduler.update(1.0 / 60.0,).len(),).sum();
+    assert_eq!(fired, 60 * 60);
+}
@@ -0,0 +1,450 @@
+//! Enemy behaviours and waves written in [Rhai](https://rhai.rs), behind the `scripting`
+//! feature. One script file holds all of them:
+//!
+//! ```text
+//! // called every frame instead of the built in spawn timer
+//! fn update_waves(waves) {
+//!     if waves.enemy_count == 0 {
+//!         waves.wave += 1;
+//!         for i in 0..waves.wave * 3 {
+//!             waves.spawn_random("circler");
+//!         }
+//!     }
+//! }
+//!
+//! // behaviour of enemies spawned as "circler"
+//! fn circler(enemy) {
+//!     if enemy.has_target {
+//!         enemy.move_towards(enemy.target_x, enemy.target_y, 60.0 * enemy.delta);
+//!         if enemy.life_time % 2.0 < enemy.delta {
+//!             enemy.fire(enemy.target_x - enemy.x, enemy.target_y - enemy.y);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Scripts only see the API below. They can not reach files or modules, and every call is
+//! cut short after `MAX_OPERATIONS` steps. A function that fails is logged once and the built
+//! in behaviour takes over from it until the scripts are loaded again, the other functions keep
+//! running. Debug builds load the file again when it changes. Scripts run in the simulation, so
+//! networked peers need the same script.
+//!
+//! Enemy: `x`, `y`, `life_time`, `delta`, `has_target`, `target_x`, `target_y`,
+//! `move_by(dx, dy)`, `move_towards(x, y, distance)`, `fire(dx, dy)`.
+//!
+//! Waves: `time`, `delta`, `enemy_count`, `wave` (can be set), `world_width`, `world_height`,
+//! `spawn_at(x, y)`, `spawn_at(x, y, behaviour)`, `spawn_random()`, `spawn_random(behaviour)`.
+//!
+//! Numbers are floats, write `1.0` rather than `1`.
+
+use entities::{
+    bullet::{Bullet, ENEMY_OWNER},
+    enemies::Enemy,
+    player::Player,
+    Position,
+};
+use log;
+use math::vector::Vector2d;
+use rhai::{module_resolvers::DummyModuleResolver, Dynamic, Engine, Scope, AST};
+use std::{
+    cell::RefCell, collections::HashSet, fmt, fs, path::PathBuf, ptr, rc::Rc, time::SystemTime,
+};
+
+/// Steps a single script call may take, so an endless loop can not hang the game.
+pub const MAX_OPERATIONS: u64 = 100_000;
+/// Bullets one enemy can fire in a frame.
+pub const MAX_SHOTS_PER_FRAME: usize = 4;
+/// Enemies the wave script can spawn in a frame.
+pub const MAX_SPAWNS_PER_FRAME: usize = 32;
+/// Frames between looking for changes to the script file in debug builds.
+const RELOAD_CHECK_FRAMES: u32 = 30;
+
+static mut SCRIPTS: Option<ScriptHost,> = None;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(String,),
+    Compile(String,),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match self {
+            ScriptError::Io(message,) => write!(f, "could not read script: {}", message),
+            ScriptError::Compile(message,) => write!(f, "script error: {}", message),
+        }
+    }
+}
+
+/// What an enemy script sees and does. Clones share the state, so changes made by the script
+/// are seen here afterwards.
+#[derive(Clone)]
+struct EnemyApi(Rc<RefCell<EnemyControl,>,>,);
+
+struct EnemyControl {
+    pos: Vector2d,
+    life_time: f32,
+    delta: f32,
+    target: Option<Vector2d,>,
+    shots: Vec<Vector2d,>,
+}
+
+#[derive(Clone)]
+struct WavesApi(Rc<RefCell<WaveControl,>,>,);
+
+struct WaveControl {
+    time: f32,
+    delta: f32,
+    enemy_count: usize,
+    wave: i64,
+    world_width: f32,
+    world_height: f32,
+    spawns: Vec<Spawn,>,
+}
+
+/// An enemy the wave script asked for. No position means somewhere random, away from players.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spawn {
+    pub position: Option<Vector2d,>,
+    pub behaviour: Option<String,>,
+}
+
+/// What `update_waves` reads, and the wave number it may change.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WaveInfo {
+    pub time: f32,
+    pub delta: f32,
+    pub enemy_count: usize,
+    pub wave: i64,
+    pub world_width: f32,
+    pub world_height: f32,
+}
+
+pub struct ScriptHost {
+    engine: Engine,
+    ast: Option<AST,>,
+    /// File the scripts came from, checked for changes in debug builds.
+    path: Option<PathBuf,>,
+    modified: Option<SystemTime,>,
+    /// Functions that failed, they are not called again so the error is only logged once.
+    failed: HashSet<String,>,
+    frames_since_check: u32,
+}
+
+impl ScriptHost {
+    pub fn new() -> ScriptHost {
+        ScriptHost {
+            engine: new_engine(),
+            ast: None,
+            path: None,
+            modified: None,
+            failed: HashSet::new(),
+            frames_since_check: 0,
+        }
+    }
+
+    pub fn load_str(&mut self, source: &str,) -> Result<(), ScriptError,> {
+        let ast =
+            self.engine.compile(source,).map_err(|e| ScriptError::Compile(e.to_string(),),)?;
+        self.ast = Some(ast,);
+        self.failed.clear();
+        Ok((),)
+    }
+
+    pub fn load(&mut self, path: &str,) -> Result<(), ScriptError,> {
+        let source = fs::read_to_string(path,).map_err(|e| ScriptError::Io(e.to_string(),),)?;
+        self.path = Some(PathBuf::from(path,),);
+        self.modified = modified_time(path,);
+        self.load_str(&source,)
+    }
+
+    /// Loads the file again if it changed since it was loaded. Does nothing in release builds.
+    /// A broken script is logged and the old one kept.
+    pub fn reload_if_changed(&mut self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        self.frames_since_check += 1;
+        if self.frames_since_check < RELOAD_CHECK_FRAMES {
+            return;
+        }
+        self.frames_since_check = 0;
+        let path = match self.path {
+            Some(ref path,) => path.to_string_lossy().to_string(),
+            None => return,
+        };
+        let modified = modified_time(&path,);
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        match self.load(&path,) {
+            Ok((),) => log(format!("Reloaded {}", path),),
+            Err(e,) => log(e.to_string(),),
+        }
+    }
+
+    /// True if the script has a function `name` taking one argument.
+    pub fn has_function(&self, name: &str,) -> bool {
+        match self.ast {
+            Some(ref ast,) if !self.failed.contains(name,) => {
+                ast.iter_functions().any(|f| f.name == name && f.params.len() == 1,)
+            }
+            _ => false,
+        }
+    }
+
+    fn call(&mut self, name: &str, arg: Dynamic,) -> bool {
+        if !self.has_function(name,) {
+            return false;
+        }
+        let result = match self.ast {
+            Some(ref ast,) => {
+                self.engine.call_fn::<Dynamic,>(&mut Scope::new(), ast, name, (arg,),)
+            }
+            None => return false,
+        };
+        match result {
+            Ok(_,) => true,
+            Err(e,) => {
+                log(format!("script {} failed, using the built in behaviour: {}", name, e),);
+                self.failed.insert(name.to_string(),);
+                false
+            }
+        }
+    }
+
+    /// Runs the behaviour function of a scripted enemy. Returns false, leaving the enemy
+    /// alone, when there is no such function.
+    pub fn update_enemy(
+        &mut self,
+        enemy: &mut Enemy,
+        players: &[Player],
+        delta: f32,
+        bullets: &mut Vec<Bullet,>,
+    ) -> bool {
+        let behaviour = match enemy.get_behaviour() {
+            Some(behaviour,) => behaviour.to_string(),
+            None => return false,
+        };
+        let api = EnemyApi(Rc::new(RefCell::new(EnemyControl {
+            pos: enemy.get_position(),
+            life_time: enemy.get_life_time(),
+            delta,
+            target: enemy.nearest_player(players,).map(|p| p.get_position(),),
+            shots: Vec::new(),
+        },),),);
+        if !self.call(&behaviour, Dynamic::from(api.clone(),),) {
+            return false;
+        }
+        let control = api.0.borrow();
+        enemy.animate(delta,);
+        if control.pos.x.is_finite() && control.pos.y.is_finite() {
+            enemy.move_to(control.pos,);
+        }
+        for direction in control.shots.iter().take(MAX_SHOTS_PER_FRAME,) {
+            bullets.push(Bullet::new(enemy.get_position(), *direction, ENEMY_OWNER,),);
+        }
+        true
+    }
+
+    /// Runs `update_waves`. None when the script has no such function, the built in spawning
+    /// should be used then.
+    pub fn update_waves(&mut self, info: &mut WaveInfo,) -> Option<Vec<Spawn,>,> {
+        let api = WavesApi(Rc::new(RefCell::new(WaveControl {
+            time: info.time,
+            delta: info.delta,
+            enemy_count: info.enemy_count,
+            wave: info.wave,
+            world_width: info.world_width,
+            world_height: info.world_height,
+            spawns: Vec::new(),
+        },),),);
+        if !self.call("update_waves", Dynamic::from(api.clone(),),) {
+            return None;
+        }
+        let mut control = api.0.borrow_mut();
+        info.wave = control.wave;
+        control.spawns.truncate(MAX_SPAWNS_PER_FRAME,);
+        Some(control.spawns.drain(..,).collect(),)
+    }
+}
+
+impl Default for ScriptHost {
+    fn default() -> ScriptHost {
+        ScriptHost::new()
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime,> {
+    fs::metadata(path,).and_then(|m| m.modified(),).ok()
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new(),);
+    engine.set_max_operations(MAX_OPERATIONS,);
+    engine.set_max_call_levels(32,);
+    engine.set_max_expr_depths(64, 32,);
+    engine.set_max_string_size(4096,);
+    engine.set_max_array_size(1024,);
+    engine.set_max_map_size(256,);
+    engine.on_print(|s| log(s.to_string(),),);
+    engine.on_debug(|s, _, _| log(s.to_string(),),);
+
+    engine
+        .register_type_with_name::<EnemyApi,>("Enemy",)
+        .register_get("x", |e: &mut EnemyApi| e.0.borrow().pos.x as f64,)
+        .register_get("y", |e: &mut EnemyApi| e.0.borrow().pos.y as f64,)
+        .register_get("life_time", |e: &mut EnemyApi| e.0.borrow().life_time as f64,)
+        .register_get("delta", |e: &mut EnemyApi| e.0.borrow().delta as f64,)
+        .register_get("has_target", |e: &mut EnemyApi| e.0.borrow().target.is_some(),)
+        .register_get("target_x", |e: &mut EnemyApi| {
+            let control = e.0.borrow();
+            control.target.unwrap_or(control.pos,).x as f64
+        },)
+        .register_get("target_y", |e: &mut EnemyApi| {
+            let control = e.0.borrow();
+            control.target.unwrap_or(control.pos,).y as f64
+        },)
+        .register_fn("move_by", |e: &mut EnemyApi, dx: f64, dy: f64| {
+            e.0.borrow_mut().pos += Vector2d::new(dx as f32, dy as f32,);
+        },)
+        .register_fn("move_towards", |e: &mut EnemyApi, x: f64, y: f64, distance: f64| {
+            let mut control = e.0.borrow_mut();
+            let to_target = Vector2d::new(x as f32, y as f32,) - control.pos;
+            let step = (distance as f32).min(to_target.len(),);
+            control.pos += to_target.normalized() * step;
+        },)
+        .register_fn("fire", |e: &mut EnemyApi, dx: f64, dy: f64| {
+            let direction = Vector2d::new(dx as f32, dy as f32,).normalized();
+            let mut control = e.0.borrow_mut();
+            if direction.len() > 0.0 && control.shots.len() < MAX_SHOTS_PER_FRAME {
+                control.shots.push(direction,);
+            }
+        },);
+
+    engine
+        .register_type_with_name::<WavesApi,>("Waves",)
+        .register_get("time", |w: &mut WavesApi| w.0.borrow().time as f64,)
+        .register_get("delta", |w: &mut WavesApi| w.0.borrow().delta as f64,)
+        .register_get("enemy_count", |w: &mut WavesApi| w.0.borrow().enemy_count as i64,)
+        .register_get("world_width", |w: &mut WavesApi| w.0.borrow().world_width as f64,)
+        .register_get("world_height", |w: &mut WavesApi| w.0.borrow().world_height as f64,)
+        .register_get_set(
+            "wave",
+            |w: &mut WavesApi| w.0.borrow().wave,
+            |w: &mut WavesApi, wave: i64| w.0.borrow_mut().wave = wave,
+        )
+        .register_fn("spawn_at", |w: &mut WavesApi, x: f64, y: f64| {
+            push_spawn(w, Some(Vector2d::new(x as f32, y as f32,),), None,);
+        },)
+        .register_fn("spawn_at", |w: &mut WavesApi, x: f64, y: f64, behaviour: &str| {
+            let position = Some(Vector2d::new(x as f32, y as f32,),);
+            push_spawn(w, position, Some(behaviour,),);
+        },)
+        .register_fn("spawn_random", |w: &mut WavesApi| push_spawn(w, None, None,),)
+        .register_fn("spawn_random", |w: &mut WavesApi, behaviour: &str| {
+            push_spawn(w, None, Some(behaviour,),);
+        },);
+    engine
+}
+
+fn push_spawn(waves: &mut WavesApi, position: Option<Vector2d,>, behaviour: Option<&str,>,) {
+    let mut control = waves.0.borrow_mut();
+    let position = position.filter(|p| p.x.is_finite() && p.y.is_finite(),);
+    if control.spawns.len() < MAX_SPAWNS_PER_FRAME {
+        control.spawns.push(Spawn {
+            position,
+            behaviour: behaviour.map(|b| b.to_string(),),
+        },);
+    }
+}
+
+/// Loads the scripts in `path`, replacing any loaded before. On error the old ones are kept.
+pub fn load_scripts(path: &str) -> Result<(), ScriptError,> {
+    let mut host = ScriptHost::new();
+    host.load(path,)?;
+    unsafe {
+        SCRIPTS = Some(host,);
+    }
+    Ok((),)
+}
+
+/// Like `load_scripts` for platforms without files, the web frontend fetches the text itself.
+pub fn load_scripts_from_str(source: &str) -> Result<(), ScriptError,> {
+    let mut host = ScriptHost::new();
+    host.load_str(source,)?;
+    unsafe {
+        SCRIPTS = Some(host,);
+    }
+    Ok((),)
+}
+
+pub fn unload_scripts() {
+    unsafe {
+        SCRIPTS = None;
+    }
+}
+
+/// Runs `f` with the loaded scripts, None when there are none.
+pub fn with_scripts<T, F: FnOnce(&mut ScriptHost,) -> T,>(f: F,) -> Option<T,> {
+    unsafe { (*ptr::addr_of_mut!(SCRIPTS)).as_mut().map(f,) }
+}
+
+#[test]
+fn test_scripted_enemy_and_waves() {
+    let mut host = ScriptHost::new();
+    host.load_str(
+        r#"
+        fn chaser(enemy) {
+            enemy.move_towards(enemy.target_x, enemy.target_y, 2.0);
+            enemy.fire(1.0, 0.0);
+        }
+        fn update_waves(waves) {
+            if waves.enemy_count == 0 {
+                waves.wave += 1;
+                waves.spawn_at(10.0, 20.0, "chaser");
+                waves.spawn_random();
+            }
+        }
+        fn forever(enemy) {
+            loop {}
+        }
+        "#,
+    ).unwrap();
+
+    let players = vec![Player::new(0, Vector2d::new(10.0, 0.0,),)];
+    let mut enemy = Enemy::scripted(Vector2d::new(0.0, 0.0,), "chaser",);
+    let mut bullets = Vec::new();
+    assert!(host.update_enemy(&mut enemy, &players, 0.1, &mut bullets,));
+    assert_eq!(enemy.get_position(), Vector2d::new(2.0, 0.0,));
+    assert_eq!(bullets.len(), 1);
+    assert!(bullets[0].is_from_enemy());
+
+    let mut info = WaveInfo {
+        time: 0.0,
+        delta: 0.1,
+        enemy_count: 0,
+        wave: 0,
+        world_width: 960.0,
+        world_height: 540.0,
+    };
+    let spawns = host.update_waves(&mut info,).unwrap();
+    assert_eq!(info.wave, 1);
+    assert_eq!(spawns.len(), 2);
+    assert_eq!(spawns[0].position, Some(Vector2d::new(10.0, 20.0,)));
+    assert_eq!(spawns[0].behaviour, Some("chaser".to_string()));
+    assert_eq!(spawns[1].position, None);
+
+    // unknown behaviours and endless loops fall back to the built in behaviour
+    let mut unknown = Enemy::scripted(Vector2d::zero(), "missing",);
+    assert!(!host.update_enemy(&mut unknown, &players, 0.1, &mut bullets,));
+    let mut stuck = Enemy::scripted(Vector2d::zero(), "forever",);
+    assert!(!host.update_enemy(&mut stuck, &players, 0.1, &mut bullets,));
+    assert!(!host.has_function("forever"));
+    // only the failed one is turned off
+    assert!(host.update_enemy(&mut enemy, &players, 0.1, &mut bullets,));
+
+    assert!(host.load_str("fn broken( {",).is_err());
+}
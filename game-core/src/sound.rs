@@ -0,0 +1,29 @@
+/// Sounds the game asks the platform to play. How they sound is up to the platform, the
+/// `mixer` crate has a synthesized version of each.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub enum Sound {
+    Shot,
+    EnemyKilled,
+    PlayerHit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct SoundEvent {
+    pub sound: Sound,
+    /// Playback speed, 1.0 is the sound as designed.
+    pub pitch: f32,
+    /// 0.0 to 1.0.
+    pub volume: f32,
+}
+
+impl SoundEvent {
+    pub fn new(sound: Sound, pitch: f32, volume: f32,) -> SoundEvent {
+        SoundEvent {
+            sound,
+            pitch,
+            volume,
+        }
+    }
+}
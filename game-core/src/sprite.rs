@@ -0,0 +1,183 @@
+use std::{fmt, fs};
+
+/// Pixel rectangle inside an atlas texture. Origin is the top left corner of the texture.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct SourceRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Mirrors the source image inside the destination rectangle.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Flip {
+    pub horizontal: bool,
+    pub vertical: bool,
+}
+
+/// A picture in one of the atlases the platform has loaded.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Sprite {
+    pub atlas: u32,
+    pub source: SourceRect,
+}
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Io(String,),
+    Parse { line: usize, message: String, },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match self {
+            AtlasError::Io(message,) => write!(f, "could not read atlas: {}", message),
+            AtlasError::Parse { line, message, } => write!(f, "atlas line {}: {}", line, message),
+        }
+    }
+}
+
+/// Metadata of a texture atlas, written by `atlas-packer` next to the packed PNG:
+///
+/// ```text
+/// texture = atlas.png
+/// size = 64 32
+/// player = 0 0 16 16
+/// bullet = 16 0 4 4
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atlas {
+    /// File name of the texture, relative to the metadata file.
+    pub texture: String,
+    pub width: u32,
+    pub height: u32,
+    sprites: Vec<(String, SourceRect,),>,
+}
+
+impl Atlas {
+    pub fn new(texture: &str, width: u32, height: u32,) -> Atlas {
+        Atlas {
+            texture: texture.to_string(),
+            width,
+            height,
+            sprites: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, source: SourceRect,) {
+        self.sprites.retain(|(n, _,)| n != name,);
+        self.sprites.push((name.to_string(), source,),);
+    }
+
+    pub fn get(&self, name: &str,) -> Option<SourceRect,> {
+        self.sprites.iter().find(|(n, _,)| n == name,).map(|(_, s,)| *s,)
+    }
+
+    pub fn sprites(&self) -> &[(String, SourceRect,)] {
+        &self.sprites
+    }
+
+    pub fn load(path: &str) -> Result<Atlas, AtlasError,> {
+        let text = fs::read_to_string(path,).map_err(|e| AtlasError::Io(e.to_string(),),)?;
+        Atlas::parse(&text,)
+    }
+
+    /// Empty lines and lines starting with `#` are skipped. Sprites must fit in `size`.
+    pub fn parse(text: &str) -> Result<Atlas, AtlasError,> {
+        let mut atlas = Atlas::new("", 0, 0,);
+        for (index, line,) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#',) {
+                continue;
+            }
+            let error = |message: String| AtlasError::Parse {
+                line: line_number,
+                message,
+            };
+            let mut parts = line.splitn(2, '=',);
+            let name = parts.next().unwrap_or("",).trim();
+            let value = match parts.next() {
+                Some(value,) => value.trim(),
+                None => return Err(error(format!("expected `name = value`, got `{}`", line),),),
+            };
+            let numbers = || -> Result<Vec<u32,>, AtlasError,> {
+                value
+                    .split_whitespace()
+                    .map(|n| n.parse().map_err(|_| error(format!("`{}` is not a number", n),),),)
+                    .collect()
+            };
+            match name {
+                "texture" => atlas.texture = value.to_string(),
+                "size" => match numbers()?.as_slice() {
+                    [width, height] => {
+                        atlas.width = *width;
+                        atlas.height = *height;
+                    }
+                    _ => return Err(error("expected `size = width height`".to_string(),),),
+                },
+                _ => match numbers()?.as_slice() {
+                    [x, y, width, height] => {
+                        let source = SourceRect {
+                            x: *x,
+                            y: *y,
+                            width: *width,
+                            height: *height,
+                        };
+                        let inside = match (x.checked_add(*width,), y.checked_add(*height,),) {
+                            (Some(right,), Some(bottom,),) => {
+                                right <= atlas.width && bottom <= atlas.height
+                            }
+                            _ => false,
+                        };
+                        if !inside {
+                            return Err(error(format!("`{}` is outside the texture", name),),);
+                        }
+                        atlas.add(name, source,);
+                    }
+                    _ => return Err(error(format!("expected `{} = x y width height`", name),),),
+                },
+            }
+        }
+        Ok(atlas,)
+    }
+
+    /// The atlas in the format `parse` reads.
+    pub fn to_config_string(&self) -> String {
+        let mut text = format!("texture = {}\n", self.texture);
+        text += &format!("size = {} {}\n", self.width, self.height);
+        for (name, s,) in &self.sprites {
+            text += &format!("{} = {} {} {} {}\n", name, s.x, s.y, s.width, s.height);
+        }
+        text
+    }
+}
+
+#[test]
+fn test_parse_round_trip() {
+    let atlas = Atlas::parse(
+        "# packed\ntexture = art.png\nsize = 32 16\nplayer = 0 0 16 16\nbullet = 16 0 4 4\n",
+    ).unwrap();
+    assert_eq!(atlas.texture, "art.png");
+    assert_eq!(
+        atlas.get("bullet"),
+        Some(SourceRect {
+            x: 16,
+            y: 0,
+            width: 4,
+            height: 4,
+        })
+    );
+    assert_eq!(atlas.get("enemy"), None);
+    assert_eq!(Atlas::parse(&atlas.to_config_string()).unwrap(), atlas);
+
+    for text in &["size = 8 8\nplayer = 4 4 8 8\n", "size = 8 8\nplayer = 4294967295 0 1 1\n"] {
+        match Atlas::parse(text,) {
+            Err(AtlasError::Parse { line: 2, .. }) => {}
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+}
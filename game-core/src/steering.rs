@@ -0,0 +1,351 @@
+//! Steering behaviours. Each one returns the acceleration that would turn the agent's velocity
+//! towards what it wants, `combine` weighs several of them and `Agent::apply` moves the agent.
+
+use math::vector::Vector2d;
+
+/// Something that steers, with its limits.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Agent {
+    pub position: Vector2d,
+    pub velocity: Vector2d,
+    /// Units per second.
+    pub max_speed: f32,
+    /// Units per second per second.
+    pub max_acceleration: f32,
+}
+
+impl Agent {
+    pub fn new(position: Vector2d, max_speed: f32, max_acceleration: f32,) -> Agent {
+        Agent {
+            position,
+            velocity: Vector2d::zero(),
+            max_speed,
+            max_acceleration,
+        }
+    }
+
+    /// Accelerates by `steering`, cut to the max acceleration, then moves. The speed never goes
+    /// above the max speed.
+    pub fn apply(&mut self, steering: Vector2d, delta: f32,) {
+        if !delta.is_finite() || delta <= 0.0 {
+            return;
+        }
+        self.velocity += truncate(steering, self.max_acceleration,) * delta;
+        self.velocity = truncate(self.velocity, self.max_speed,);
+        self.position += self.velocity * delta;
+    }
+}
+
+/// `v` made no longer than `max`.
+pub fn truncate(v: Vector2d, max: f32,) -> Vector2d {
+    if v.len() > max {
+        v.normalized() * max
+    } else {
+        v
+    }
+}
+
+/// Steering that changes the velocity to `desired`.
+fn steer(agent: &Agent, desired: Vector2d,) -> Vector2d {
+    desired - agent.velocity
+}
+
+/// Full speed straight at `target`.
+pub fn seek(agent: &Agent, target: Vector2d,) -> Vector2d {
+    steer(agent, (target - agent.position).normalized() * agent.max_speed,)
+}
+
+/// Full speed straight away from `threat`.
+pub fn flee(agent: &Agent, threat: Vector2d,) -> Vector2d {
+    steer(agent, (agent.position - threat).normalized() * agent.max_speed,)
+}
+
+/// Like seek, but slows down inside `slowing_radius` to stop on the target.
+pub fn arrive(agent: &Agent, target: Vector2d, slowing_radius: f32,) -> Vector2d {
+    let offset = target - agent.position;
+    let distance = offset.len();
+    let speed = if distance < slowing_radius {
+        agent.max_speed * distance / slowing_radius
+    } else {
+        agent.max_speed
+    };
+    steer(agent, offset.normalized() * speed,)
+}
+
+/// Seeks where the target will be if it keeps going, further ahead the further away it is.
+pub fn pursue(agent: &Agent, target: Vector2d, target_velocity: Vector2d,) -> Vector2d {
+    seek(agent, predict(agent, target, target_velocity,),)
+}
+
+/// Flees from where the threat will be if it keeps going.
+pub fn evade(agent: &Agent, threat: Vector2d, threat_velocity: Vector2d,) -> Vector2d {
+    flee(agent, predict(agent, threat, threat_velocity,),)
+}
+
+fn predict(agent: &Agent, target: Vector2d, target_velocity: Vector2d,) -> Vector2d {
+    let time = if agent.max_speed > 0.0 {
+        (target.distance(&agent.position,) / agent.max_speed).min(MAX_PREDICTION,)
+    } else {
+        0.0
+    };
+    target + target_velocity * time
+}
+
+/// Seconds pursue and evade look ahead at most.
+const MAX_PREDICTION: f32 = 2.0;
+
+/// Where a wandering agent is heading, kept between frames so it turns smoothly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Wander {
+    /// Radians around the wander circle.
+    pub angle: f32,
+    /// Size of the circle in front of the agent the target moves on.
+    pub radius: f32,
+    /// How far in front of the agent the circle is.
+    pub distance: f32,
+    /// Radians the target can move each call.
+    pub jitter: f32,
+}
+
+impl Default for Wander {
+    fn default() -> Wander {
+        Wander {
+            angle: 0.0,
+            radius: 20.0,
+            distance: 40.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+/// Random but smooth turning. `random` is in -1..1, taken from the game's seeded random
+/// numbers so wandering is the same on every peer.
+pub fn wander(agent: &Agent, wander: &mut Wander, random: f32,) -> Vector2d {
+    wander.angle += random * wander.jitter;
+    let heading = if agent.velocity.len() > 0.0 {
+        agent.velocity.normalized()
+    } else {
+        Vector2d::new(1.0, 0.0,)
+    };
+    let circle = agent.position + heading * wander.distance;
+    let target = circle + Vector2d::from_angle(heading.angle() + wander.angle,) * wander.radius;
+    seek(agent, target,)
+}
+
+/// Neighbours within `radius` with their index, leaving out the one at `own`. `own` is the
+/// index of the agent itself in `neighbours`, None when it is not in there.
+fn near<'a,>(
+    agent: &'a Agent,
+    neighbours: &'a [Agent],
+    own: Option<usize,>,
+    radius: f32,
+) -> impl Iterator<Item = (usize, &'a Agent,),> + 'a {
+    neighbours.iter().enumerate().filter(move |&(index, n,)| {
+        Some(index,) != own && n.position.distance(&agent.position,) < radius
+    },)
+}
+
+/// Which way to push the agent at `own` away from the neighbour at `other` when they are at
+/// the same spot. The two get opposite directions, so stacked agents come apart.
+fn split_direction(own: Option<usize,>, other: usize,) -> Vector2d {
+    match own {
+        Some(own,) if own > other => -Vector2d::from_angle((own + other) as f32,),
+        Some(own,) => Vector2d::from_angle((own + other) as f32,),
+        None => Vector2d::from_angle(other as f32,),
+    }
+}
+
+/// Away from neighbours closer than `radius`, harder the closer they are.
+pub fn separation(
+    agent: &Agent,
+    neighbours: &[Agent],
+    own: Option<usize,>,
+    radius: f32,
+) -> Vector2d {
+    let mut away = Vector2d::zero();
+    for (index, n,) in near(agent, neighbours, own, radius,) {
+        let offset = agent.position - n.position;
+        let direction = if offset.len() > 0.0 {
+            offset.normalized()
+        } else {
+            split_direction(own, index,)
+        };
+        away += direction * (1.0 - offset.len() / radius);
+    }
+    if away.len() > 0.0 {
+        steer(agent, away.normalized() * agent.max_speed,)
+    } else {
+        Vector2d::zero()
+    }
+}
+
+/// Towards the average heading of neighbours within `radius`.
+pub fn alignment(
+    agent: &Agent,
+    neighbours: &[Agent],
+    own: Option<usize,>,
+    radius: f32,
+) -> Vector2d {
+    let mut heading = Vector2d::zero();
+    let mut count = 0;
+    for (_, n,) in near(agent, neighbours, own, radius,) {
+        heading += n.velocity;
+        count += 1;
+    }
+    if count == 0 || heading.len() == 0.0 {
+        return Vector2d::zero();
+    }
+    steer(agent, heading.normalized() * agent.max_speed,)
+}
+
+/// Towards the middle of neighbours within `radius`.
+pub fn cohesion(
+    agent: &Agent,
+    neighbours: &[Agent],
+    own: Option<usize,>,
+    radius: f32,
+) -> Vector2d {
+    let mut center = Vector2d::zero();
+    let mut count = 0;
+    for (_, n,) in near(agent, neighbours, own, radius,) {
+        center += n.position;
+        count += 1;
+    }
+    if count == 0 {
+        return Vector2d::zero();
+    }
+    seek(agent, center * (1.0 / count as f32),)
+}
+
+/// Weighted sum of steering forces. Forces that are not finite are left out.
+pub fn combine(forces: &[(Vector2d, f32,)]) -> Vector2d {
+    let mut total = Vector2d::zero();
+    for &(force, weight,) in forces {
+        let weighted = force * weight;
+        if weighted.x.is_finite() && weighted.y.is_finite() {
+            total += weighted;
+        }
+    }
+    total
+}
+
+/// How much each behaviour counts for a flock member.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FlockWeights {
+    pub seek: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub wander: f32,
+}
+
+impl Default for FlockWeights {
+    fn default() -> FlockWeights {
+        FlockWeights {
+            seek: 1.0,
+            separation: 1.5,
+            alignment: 0.5,
+            cohesion: 0.4,
+            wander: 0.3,
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_agent(x: f32, y: f32,) -> Agent {
+    Agent::new(Vector2d::new(x, y,), 10.0, 100.0,)
+}
+
+#[test]
+fn test_seek_and_flee() {
+    let agent = test_agent(0.0, 0.0,);
+    let target = Vector2d::new(5.0, 0.0,);
+    assert_eq!(seek(&agent, target,), Vector2d::new(10.0, 0.0,));
+    assert_eq!(flee(&agent, target,), Vector2d::new(-10.0, 0.0,));
+
+    // already going the right way at full speed, nothing to change
+    let mut moving = agent;
+    moving.velocity = Vector2d::new(10.0, 0.0,);
+    assert_eq!(seek(&moving, target,), Vector2d::zero());
+}
+
+#[test]
+fn test_arrive_stops_on_target() {
+    let mut agent = test_agent(0.0, 0.0,);
+    let target = Vector2d::new(50.0, 0.0,);
+    // half way into the slowing radius, half speed
+    assert_eq!(arrive(&test_agent(45.0, 0.0,), target, 10.0,), Vector2d::new(5.0, 0.0,));
+    for _ in 0..2000 {
+        let force = arrive(&agent, target, 10.0,);
+        agent.apply(force, 0.01,);
+    }
+    assert!(agent.position.distance(&target,) < 0.5);
+    assert!(agent.velocity.len() < 0.5);
+}
+
+#[test]
+fn test_pursue_and_evade_lead_the_target() {
+    let agent = test_agent(0.0, 0.0,);
+    let target = Vector2d::new(10.0, 0.0,);
+    let velocity = Vector2d::new(0.0, 5.0,);
+    // a second away at full speed, so aim where the target is in a second
+    let pursuit = pursue(&agent, target, velocity,);
+    assert!(pursuit.y > 0.0 && pursuit.x > 0.0);
+    assert_eq!(pursuit, seek(&agent, Vector2d::new(10.0, 5.0,),));
+    assert_eq!(evade(&agent, target, velocity,), -pursuit);
+}
+
+#[test]
+fn test_wander_is_smooth_and_repeatable() {
+    let run = |seed: u64| {
+        let mut rng = ::rng::Rng::new(seed,);
+        let mut agent = test_agent(0.0, 0.0,);
+        let mut state = Wander::default();
+        let mut positions = Vec::new();
+        for _ in 0..100 {
+            let force = wander(&agent, &mut state, rng.range(-1.0, 1.0,),);
+            let before = agent.velocity;
+            agent.apply(force, 0.1,);
+            assert!(agent.velocity.len() <= agent.max_speed + 1e-3);
+            assert!((agent.velocity - before).len() <= agent.max_acceleration * 0.1 + 1e-3);
+            positions.push(agent.position,);
+        }
+        positions
+    };
+    assert_eq!(run(1,), run(1,));
+    assert!(run(1,) != run(2,));
+}
+
+#[test]
+fn test_flocking() {
+    let agent = test_agent(0.0, 0.0,);
+    let mut left = test_agent(-2.0, 0.0,);
+    left.velocity = Vector2d::new(0.0, 10.0,);
+    let mut far = test_agent(100.0, 0.0,);
+    far.velocity = Vector2d::new(0.0, -10.0,);
+    let flock = [agent, left, far];
+
+    // pushed right, away from the close neighbour, the far one and itself do not count
+    let apart = separation(&agent, &flock, Some(0,), 5.0,);
+    assert!(apart.x > 0.0 && apart.y == 0.0);
+    // lines up with the close neighbour going up
+    assert_eq!(alignment(&agent, &flock, Some(0,), 5.0,), Vector2d::new(0.0, 10.0,));
+    // pulled left towards the middle of the close ones
+    assert_eq!(cohesion(&agent, &flock, Some(0,), 5.0,), Vector2d::new(-10.0, 0.0,));
+    assert_eq!(cohesion(&agent, &[agent], Some(0,), 5.0,), Vector2d::zero());
+
+    // two at the same spot are pushed opposite ways
+    let stacked = [agent, agent];
+    let first = separation(&agent, &stacked, Some(0,), 5.0,);
+    let second = separation(&agent, &stacked, Some(1,), 5.0,);
+    assert!(first.len() > 0.0);
+    assert_eq!(first, -second);
+
+    let total = combine(&[
+        (Vector2d::new(1.0, 0.0,), 2.0,),
+        (Vector2d::new(0.0, 1.0,), 0.5,),
+        (Vector2d::new(f32::NAN, 0.0,), 1.0,),
+    ],);
+    assert_eq!(total, Vector2d::new(2.0, 0.5,));
+}
@@ -0,0 +1,152 @@
+use math::{matrix::Mat3, vector::Vector2d};
+
+/// Position, rotation in radians (counter clockwise) and scale, applied scale first.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Transform {
+    pub position: Vector2d,
+    pub rotation: f32,
+    pub scale: Vector2d,
+}
+
+impl Transform {
+    pub fn new(position: Vector2d, rotation: f32, scale: Vector2d,) -> Transform {
+        Transform {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(Vector2d::zero(), 0.0, Vector2d::new(1.0, 1.0,),)
+    }
+
+    /// Only moves, no rotation or scale.
+    pub fn at(position: Vector2d) -> Transform {
+        Transform::new(position, 0.0, Vector2d::new(1.0, 1.0,),)
+    }
+
+    pub fn to_matrix(&self) -> Mat3 {
+        Mat3::from_parts(self.position, self.rotation, self.scale,)
+    }
+}
+
+/// Corners of the unit square centered on the origin after `matrix`, counter clockwise from
+/// the bottom left. With a `Transform` whose scale is the size of a box this gives the box.
+pub fn quad_corners(matrix: &Mat3) -> [Vector2d; 4] {
+    [
+        matrix.transform_point(Vector2d::new(-0.5, -0.5,),),
+        matrix.transform_point(Vector2d::new(0.5, -0.5,),),
+        matrix.transform_point(Vector2d::new(0.5, 0.5,),),
+        matrix.transform_point(Vector2d::new(-0.5, 0.5,),),
+    ]
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NodeId(usize,);
+
+struct Node {
+    local: Transform,
+    parent: Option<NodeId,>,
+}
+
+/// Transforms attached to each other. A child's local transform is relative to its parent, so
+/// a gun barrel added as a child of the player moves and turns with the player.
+pub struct Hierarchy {
+    nodes: Vec<Node,>,
+}
+
+impl Hierarchy {
+    pub fn new() -> Hierarchy {
+        Hierarchy { nodes: Vec::new(), }
+    }
+
+    pub fn add_root(&mut self, local: Transform,) -> NodeId {
+        self.nodes.push(Node {
+            local,
+            parent: None,
+        },);
+        NodeId(self.nodes.len() - 1,)
+    }
+
+    /// Parents always exist before their children, so there can be no loops.
+    pub fn add_child(&mut self, parent: NodeId, local: Transform,) -> NodeId {
+        self.nodes.push(Node {
+            local,
+            parent: Some(parent,),
+        },);
+        NodeId(self.nodes.len() - 1,)
+    }
+
+    pub fn local(&self, node: NodeId,) -> &Transform {
+        &self.nodes[node.0].local
+    }
+
+    pub fn local_mut(&mut self, node: NodeId,) -> &mut Transform {
+        &mut self.nodes[node.0].local
+    }
+
+    pub fn parent(&self, node: NodeId,) -> Option<NodeId,> {
+        self.nodes[node.0].parent
+    }
+
+    /// Walks up to the root, combining the local transforms on the way.
+    pub fn world_matrix(&self, node: NodeId,) -> Mat3 {
+        let mut matrix = self.nodes[node.0].local.to_matrix();
+        let mut parent = self.nodes[node.0].parent;
+        while let Some(id,) = parent {
+            matrix = self.nodes[id.0].local.to_matrix() * matrix;
+            parent = self.nodes[id.0].parent;
+        }
+        matrix
+    }
+
+    /// World matrix of every node, indexed like the nodes. Cheaper than calling `world_matrix`
+    /// for each node since every parent is only computed once.
+    pub fn world_matrices(&self) -> Vec<Mat3,> {
+        let mut matrices: Vec<Mat3,> = Vec::with_capacity(self.nodes.len(),);
+        for node in &self.nodes {
+            let local = node.local.to_matrix();
+            let world = match node.parent {
+                Some(parent,) => matrices[parent.0] * local,
+                None => local,
+            };
+            matrices.push(world,);
+        }
+        matrices
+    }
+}
+
+impl Default for Hierarchy {
+    fn default() -> Hierarchy {
+        Hierarchy::new()
+    }
+}
+
+#[test]
+fn test_child_follows_parent() {
+    let mut hierarchy = Hierarchy::new();
+    let player = hierarchy.add_root(Transform::at(Vector2d::new(100.0, 50.0,),),);
+    let pivot = hierarchy.add_child(player, Transform::identity(),);
+    let barrel = hierarchy.add_child(pivot, Transform::at(Vector2d::new(10.0, 0.0,),),);
+
+    let close = |a: Vector2d, b: Vector2d| (a - b).len() < 1e-3;
+    let tip = |h: &Hierarchy| h.world_matrix(barrel,).transform_point(Vector2d::zero(),);
+    assert!(close(tip(&hierarchy), Vector2d::new(110.0, 50.0,),));
+
+    // aiming up turns the barrel around the player
+    hierarchy.local_mut(pivot,).rotation = std::f32::consts::FRAC_PI_2;
+    assert!(close(tip(&hierarchy), Vector2d::new(100.0, 60.0,),));
+
+    // moving the player takes the barrel along
+    hierarchy.local_mut(player,).position = Vector2d::new(0.0, 0.0,);
+    assert!(close(tip(&hierarchy), Vector2d::new(0.0, 10.0,),));
+
+    let all = hierarchy.world_matrices();
+    for (index, matrix,) in all.iter().enumerate() {
+        let walked = hierarchy.world_matrix(NodeId(index,),);
+        let p = Vector2d::new(3.0, -2.0,);
+        assert!(close(matrix.transform_point(p,), walked.transform_point(p,),));
+    }
+}
@@ -0,0 +1,355 @@
+//! Gameplay numbers, loaded from a file so the game can be balanced without a rebuild. Lines
+//! are `name = value`, colours are `r, g, b` or `r, g, b, a` from 0 to 1. Empty lines and lines
+//! starting with `#` are skipped, names that are left out keep their defaults.
+//!
+//! The file is checked for changes while a local game runs. Player steps, the shooting cooldown
+//! and spawning apply at once. Sizes, colours and the enemy, swarm and bullet speeds are copied
+//! into each entity, so they only apply to entities made after a reload. A broken file is
+//! logged and rejected as a whole, the values loaded before stay in use. Networked games always
+//! use the defaults, the peers would drift apart otherwise.
+
+use entities::Color;
+use log;
+use std::{fmt, fs, path::PathBuf, ptr, time::SystemTime};
+
+/// Frames between checks of the file for changes.
+const RELOAD_CHECK_FRAMES: u32 = 30;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Tunables {
+    /// Width and height of a player.
+    pub player_size: f32,
+    /// Units a player walks per frame.
+    pub player_step: f32,
+    /// Units a player moves per frame while boosting.
+    pub boost_step: f32,
+    /// Seconds between shots.
+    pub shoot_cooldown: f32,
+    /// Units per second.
+    pub bullet_speed: f32,
+    pub bullet_size: f32,
+    pub bullet_color: Color,
+    /// Size of an enemy before it starts to pulse.
+    pub enemy_size: f32,
+    /// Units per second.
+    pub enemy_speed: f32,
+    pub enemy_color: Color,
+    /// Units per second.
+    pub swarm_speed: f32,
+    pub swarm_color: Color,
+    pub wall_color: Color,
+    /// Seconds between spawns.
+    pub spawn_interval: f32,
+    /// Enemies do not spawn closer than this to a player.
+    pub spawn_safe_radius: f32,
+    /// Chance that a spawn is a swarm instead of a single enemy.
+    pub swarm_chance: f32,
+    /// Enemies in a swarm.
+    pub swarm_size: usize,
+}
+
+/// What `tunables` returns until a file is loaded.
+static DEFAULTS: Tunables = Tunables {
+    player_size: 10.0,
+    player_step: 1.5,
+    boost_step: 10.0,
+    shoot_cooldown: 0.1,
+    bullet_speed: 300.0,
+    bullet_size: 4.0,
+    bullet_color: color(1.0, 0.7, 0.7,),
+    enemy_size: 10.0,
+    enemy_speed: 60.0,
+    enemy_color: color(0.1, 1.0, 0.1,),
+    swarm_speed: 80.0,
+    swarm_color: color(0.1, 0.8, 1.0,),
+    wall_color: color(1.0, 0.1, 0.1,),
+    spawn_interval: 0.25,
+    spawn_safe_radius: 100.0,
+    swarm_chance: 0.1,
+    swarm_size: 5,
+};
+
+impl Default for Tunables {
+    fn default() -> Tunables {
+        DEFAULTS
+    }
+}
+
+#[derive(Debug)]
+pub enum TunablesError {
+    Io(String,),
+    Parse { line: usize, message: String, },
+}
+
+impl fmt::Display for TunablesError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match self {
+            TunablesError::Io(message,) => write!(f, "could not read tunables: {}", message),
+            TunablesError::Parse { line, message, } => {
+                write!(f, "tunables line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl Tunables {
+    pub fn load(path: &str) -> Result<Tunables, TunablesError,> {
+        let text = fs::read_to_string(path,).map_err(|e| TunablesError::Io(e.to_string(),),)?;
+        Tunables::parse(&text,)
+    }
+
+    /// Reads lines of the form `bullet_speed = 300` over the defaults.
+    pub fn parse(text: &str) -> Result<Tunables, TunablesError,> {
+        let mut tunables = Tunables::default();
+        for (index, line,) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#',) {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=',);
+            let name = parts.next().unwrap_or("",).trim();
+            let result = match parts.next() {
+                Some(value,) => tunables.set(name, value.trim(),),
+                None => Err(format!("expected `name = value`, got `{}`", line),),
+            };
+            if let Err(message,) = result {
+                return Err(TunablesError::Parse {
+                    line: index + 1,
+                    message,
+                },);
+            }
+        }
+        Ok(tunables,)
+    }
+
+    /// Sets one value, checking that it is in range.
+    pub fn set(&mut self, name: &str, value: &str,) -> Result<(), String,> {
+        match name {
+            "player_size" => self.player_size = number(name, value, 1.0, 200.0,)?,
+            "player_step" => self.player_step = number(name, value, 0.0, 100.0,)?,
+            "boost_step" => self.boost_step = number(name, value, 0.0, 100.0,)?,
+            "shoot_cooldown" => self.shoot_cooldown = number(name, value, 0.01, 10.0,)?,
+            "bullet_speed" => self.bullet_speed = number(name, value, 0.0, 10000.0,)?,
+            "bullet_size" => self.bullet_size = number(name, value, 1.0, 200.0,)?,
+            "bullet_color" => self.bullet_color = parse_color(name, value,)?,
+            "enemy_size" => self.enemy_size = number(name, value, 1.0, 200.0,)?,
+            "enemy_speed" => self.enemy_speed = number(name, value, 0.0, 10000.0,)?,
+            "enemy_color" => self.enemy_color = parse_color(name, value,)?,
+            "swarm_speed" => self.swarm_speed = number(name, value, 0.0, 10000.0,)?,
+            "swarm_color" => self.swarm_color = parse_color(name, value,)?,
+            "wall_color" => self.wall_color = parse_color(name, value,)?,
+            "spawn_interval" => self.spawn_interval = number(name, value, 0.01, 60.0,)?,
+            "spawn_safe_radius" => self.spawn_safe_radius = number(name, value, 0.0, 1000.0,)?,
+            "swarm_chance" => self.swarm_chance = number(name, value, 0.0, 1.0,)?,
+            "swarm_size" => self.swarm_size = whole_number(name, value, 1, 50,)?,
+            _ => return Err(format!("unknown tunable `{}`", name),),
+        }
+        Ok((),)
+    }
+
+    /// Writes every value in the format `parse` reads.
+    pub fn to_config_string(&self) -> String {
+        let lines = [
+            ("player_size", self.player_size.to_string(),),
+            ("player_step", self.player_step.to_string(),),
+            ("boost_step", self.boost_step.to_string(),),
+            ("shoot_cooldown", self.shoot_cooldown.to_string(),),
+            ("bullet_speed", self.bullet_speed.to_string(),),
+            ("bullet_size", self.bullet_size.to_string(),),
+            ("bullet_color", color_string(&self.bullet_color,),),
+            ("enemy_size", self.enemy_size.to_string(),),
+            ("enemy_speed", self.enemy_speed.to_string(),),
+            ("enemy_color", color_string(&self.enemy_color,),),
+            ("swarm_speed", self.swarm_speed.to_string(),),
+            ("swarm_color", color_string(&self.swarm_color,),),
+            ("wall_color", color_string(&self.wall_color,),),
+            ("spawn_interval", self.spawn_interval.to_string(),),
+            ("spawn_safe_radius", self.spawn_safe_radius.to_string(),),
+            ("swarm_chance", self.swarm_chance.to_string(),),
+            ("swarm_size", self.swarm_size.to_string(),),
+        ];
+        let mut out = String::new();
+        for (name, value,) in lines.iter() {
+            out.push_str(&format!("{} = {}\n", name, value),);
+        }
+        out
+    }
+}
+
+const fn color(r: f32, g: f32, b: f32,) -> Color {
+    Color { r, g, b, a: 1.0, }
+}
+
+fn number(name: &str, value: &str, min: f32, max: f32,) -> Result<f32, String,> {
+    let number: f32 =
+        value.parse().map_err(|_| format!("`{}` must be a number, got `{}`", name, value),)?;
+    if !(min..=max).contains(&number,) {
+        return Err(format!("`{}` must be between {} and {}, got {}", name, min, max, number),);
+    }
+    Ok(number,)
+}
+
+fn whole_number(name: &str, value: &str, min: usize, max: usize,) -> Result<usize, String,> {
+    let number: usize = value
+        .parse()
+        .map_err(|_| format!("`{}` must be a whole number, got `{}`", name, value),)?;
+    if !(min..=max).contains(&number,) {
+        return Err(format!("`{}` must be between {} and {}, got {}", name, min, max, number),);
+    }
+    Ok(number,)
+}
+
+fn parse_color(name: &str, value: &str,) -> Result<Color, String,> {
+    let parts: Vec<&str,> = value.split(',',).map(|p| p.trim(),).collect();
+    if parts.len() != 3 && parts.len() != 4 {
+        return Err(format!("`{}` must be `r, g, b` or `r, g, b, a`, got `{}`", name, value),);
+    }
+    let mut channels = [1.0; 4];
+    for (channel, part,) in channels.iter_mut().zip(parts.iter(),) {
+        *channel = number(name, part, 0.0, 1.0,)?;
+    }
+    Ok(Color {
+        r: channels[0],
+        g: channels[1],
+        b: channels[2],
+        a: channels[3],
+    },)
+}
+
+fn color_string(color: &Color) -> String {
+    format!("{}, {}, {}, {}", color.r, color.g, color.b, color.a)
+}
+
+/// The tunables in use and the file they came from.
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct TunablesFile {
+    tunables: Tunables,
+    path: Option<PathBuf,>,
+    modified: Option<SystemTime,>,
+    frames_since_check: u32,
+}
+
+impl TunablesFile {
+    fn new(tunables: Tunables) -> TunablesFile {
+        TunablesFile {
+            tunables,
+            path: None,
+            modified: None,
+            frames_since_check: 0,
+        }
+    }
+
+    /// Loads the file again if it changed since it was loaded.
+    pub fn reload_if_changed(&mut self) {
+        self.frames_since_check += 1;
+        if self.frames_since_check < RELOAD_CHECK_FRAMES {
+            return;
+        }
+        self.frames_since_check = 0;
+        let path = match self.path {
+            Some(ref path,) => path.to_string_lossy().to_string(),
+            None => return,
+        };
+        let modified = modified_time(&path,);
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        match Tunables::load(&path,) {
+            Ok(tunables,) => {
+                self.tunables = tunables;
+                log(format!("Reloaded {}", path),);
+            }
+            Err(e,) => log(e.to_string(),),
+        }
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime,> {
+    fs::metadata(path,).and_then(|m| m.modified(),).ok()
+}
+
+pub(crate) static mut TUNABLES: Option<TunablesFile,> = None;
+
+/// A copy of the tunables in use, the defaults until some are loaded. They are only numbers, so
+/// the copy is cheap, and it stays valid when a reload replaces them.
+pub fn tunables() -> Tunables {
+    unsafe {
+        match *ptr::addr_of!(TUNABLES) {
+            Some(ref file,) => file.tunables,
+            None => DEFAULTS,
+        }
+    }
+}
+
+/// Loads the tunables in `path` and watches it for changes. On error the old ones are kept.
+pub fn load_tunables(path: &str) -> Result<(), TunablesError,> {
+    let mut file = TunablesFile::new(Tunables::load(path,)?,);
+    file.path = Some(PathBuf::from(path,),);
+    file.modified = modified_time(path,);
+    unsafe {
+        TUNABLES = Some(file,);
+    }
+    Ok((),)
+}
+
+/// Like `load_tunables` for platforms without files, the web frontend fetches the text itself.
+pub fn load_tunables_from_str(text: &str) -> Result<(), TunablesError,> {
+    let tunables = Tunables::parse(text,)?;
+    unsafe {
+        TUNABLES = Some(TunablesFile::new(tunables,),);
+    }
+    Ok((),)
+}
+
+/// Goes back to the defaults, true when some tunables were loaded.
+pub fn unload_tunables() -> bool {
+    unsafe { (*ptr::addr_of_mut!(TUNABLES)).take().is_some() }
+}
+
+/// Runs `f` with the loaded tunables file, None when nothing was loaded.
+pub fn with_tunables_file<T, F: FnOnce(&mut TunablesFile,) -> T,>(f: F,) -> Option<T,> {
+    unsafe { (*ptr::addr_of_mut!(TUNABLES)).as_mut().map(f,) }
+}
+
+#[test]
+fn test_parse_and_validate() {
+    let tunables = Tunables::parse(
+        "# faster bullets\n\nbullet_speed = 450\nenemy_color = 1, 0.5, 0\nswarm_size = 8\n",
+    ).unwrap();
+    assert_eq!(tunables.bullet_speed, 450.0);
+    assert_eq!(tunables.enemy_color, color(1.0, 0.5, 0.0,));
+    assert_eq!(tunables.swarm_size, 8);
+    assert_eq!(tunables.shoot_cooldown, Tunables::default().shoot_cooldown);
+
+    let written = Tunables::parse(&tunables.to_config_string(),).unwrap();
+    assert_eq!(written, tunables);
+
+    let error = |text| Tunables::parse(text,).unwrap_err().to_string();
+    assert_eq!(
+        error("\nbullet_speed = -5"),
+        "tunables line 2: `bullet_speed` must be between 0 and 10000, got -5"
+    );
+    assert_eq!(
+        error("bullet_speed = fast"),
+        "tunables line 1: `bullet_speed` must be a number, got `fast`"
+    );
+    assert_eq!(
+        error("shoot_cooldown = NaN"),
+        "tunables line 1: `shoot_cooldown` must be between 0.01 and 10, got NaN"
+    );
+    assert_eq!(
+        error("swarm_size = 2.5"),
+        "tunables line 1: `swarm_size` must be a whole number, got `2.5`"
+    );
+    assert_eq!(error("wall_colour = 1, 0, 0"), "tunables line 1: unknown tunable `wall_colour`");
+    assert_eq!(
+        error("wall_color = 1, 0"),
+        "tunables line 1: `wall_color` must be `r, g, b` or `r, g, b, a`, got `1, 0`"
+    );
+    assert_eq!(
+        error("spawn_interval"),
+        "tunables line 1: expected `name = value`, got `spawn_interval`"
+    );
+}
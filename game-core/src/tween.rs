@@ -0,0 +1,384 @@
+use std::{f32::consts::PI, rc::Rc};
+
+/// Easing curves, mapping progress 0..1 to 0..1. `In` curves start slow, `Out` ones end slow.
+/// Elastic and back overshoot a little before settling.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub enum Ease {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    /// Half a sine wave.
+    SineInOut,
+    ElasticIn,
+    ElasticOut,
+    BounceIn,
+    BounceOut,
+    BackIn,
+    BackOut,
+}
+
+const BACK: f32 = 1.70158;
+
+impl Ease {
+    /// `t` is clamped to 0..1.
+    pub fn apply(self, t: f32,) -> f32 {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0,) };
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Ease::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2,) / 2.0
+                }
+            }
+            Ease::CubicIn => t * t * t,
+            Ease::CubicOut => 1.0 - (1.0 - t).powi(3,),
+            Ease::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3,) / 2.0
+                }
+            }
+            Ease::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Ease::ElasticIn => 1.0 - Ease::ElasticOut.apply(1.0 - t,),
+            Ease::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t,) * ((t * 10.0 - 0.75) * 2.0 * PI / 3.0).sin() + 1.0
+                }
+            }
+            Ease::BounceIn => 1.0 - Ease::BounceOut.apply(1.0 - t,),
+            Ease::BounceOut => {
+                let (n, d,) = (7.5625, 2.75,);
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+            Ease::BackIn => (BACK + 1.0) * t * t * t - BACK * t * t,
+            Ease::BackOut => 1.0 - Ease::BackIn.apply(1.0 - t,),
+        }
+    }
+}
+
+/// Goes from `from` to `to` over `duration` seconds.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Tween {
+    pub from: f32,
+    pub to: f32,
+    pub duration: f32,
+    pub ease: Ease,
+}
+
+impl Tween {
+    pub fn new(from: f32, to: f32, duration: f32, ease: Ease,) -> Tween {
+        Tween {
+            from,
+            to,
+            duration,
+            ease,
+        }
+    }
+
+    /// Value `time` seconds in, staying at `to` once it is done.
+    pub fn value_at(&self, time: f32,) -> f32 {
+        let t = if self.duration > 0.0 {
+            time / self.duration
+        } else {
+            1.0
+        };
+        self.from + (self.to - self.from) * self.ease.apply(t,)
+    }
+}
+
+/// Writes an animated value into a property of `T`, like `|e: &mut Enemy, v| e.width = v`.
+pub type Setter<T,> = fn(&mut T, f32,);
+
+/// Animations of properties of a `T`, built from tweens. Everything is worked out from the time
+/// since the start, so running one is deterministic and needs no state besides that time.
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub enum Animation<T,> {
+    Tween(Tween, Setter<T,>,),
+    /// Does nothing for a while, to space out a sequence.
+    Delay(f32,),
+    /// One after the other. Finished steps are left at their end value.
+    Sequence(Vec<Animation<T,>,>,),
+    /// All at once, done when the longest one is.
+    Parallel(Vec<Animation<T,>,>,),
+    /// Plays `times` times, forever when None. Ping pong plays every second round backwards.
+    Repeat {
+        animation: Box<Animation<T,>,>,
+        times: Option<u32,>,
+        ping_pong: bool,
+    },
+    /// Starts this many seconds into the animation, to put a looping one out of phase.
+    Offset(Box<Animation<T,>,>, f32,),
+}
+
+impl<T,> Animation<T,> {
+    pub fn tween(set: Setter<T,>, from: f32, to: f32, duration: f32, ease: Ease,) -> Animation<T,> {
+        Animation::Tween(Tween::new(from, to, duration, ease,), set,)
+    }
+
+    pub fn looped(self) -> Animation<T,> {
+        self.repeated(None, false,)
+    }
+
+    /// There and back again, forever.
+    pub fn ping_pong(self) -> Animation<T,> {
+        self.repeated(None, true,)
+    }
+
+    pub fn repeated(self, times: Option<u32,>, ping_pong: bool,) -> Animation<T,> {
+        Animation::Repeat {
+            animation: Box::new(self,),
+            times,
+            ping_pong,
+        }
+    }
+
+    pub fn starting_at(self, seconds: f32,) -> Animation<T,> {
+        Animation::Offset(Box::new(self,), seconds,)
+    }
+
+    /// Seconds until it is done, infinite for endless repeats.
+    pub fn duration(&self) -> f32 {
+        match *self {
+            Animation::Tween(ref tween, _,) => tween.duration.max(0.0,),
+            Animation::Delay(seconds,) => seconds.max(0.0,),
+            Animation::Sequence(ref steps,) => steps.iter().map(|s| s.duration(),).sum(),
+            Animation::Parallel(ref parts,) => {
+                parts.iter().map(|p| p.duration(),).fold(0.0, f32::max,)
+            }
+            Animation::Repeat {
+                ref animation,
+                times,
+                ..
+            } => match times {
+                Some(times,) => animation.duration() * times as f32,
+                None => f32::INFINITY,
+            },
+            Animation::Offset(ref animation, seconds,) => {
+                (animation.duration() - seconds.max(0.0,)).max(0.0,)
+            }
+        }
+    }
+
+    /// Sets every property to its value `time` seconds in.
+    pub fn apply(&self, target: &mut T, time: f32,) {
+        let time = time.max(0.0,);
+        match *self {
+            Animation::Tween(ref tween, set,) => set(target, tween.value_at(time,),),
+            Animation::Delay(_,) => {}
+            Animation::Sequence(ref steps,) => {
+                let mut start = 0.0;
+                for step in steps {
+                    let duration = step.duration();
+                    if time < start + duration {
+                        step.apply(target, time - start,);
+                        break;
+                    }
+                    step.apply(target, duration,);
+                    start += duration;
+                }
+            }
+            Animation::Parallel(ref parts,) => {
+                for part in parts {
+                    part.apply(target, time,);
+                }
+            }
+            Animation::Repeat {
+                ref animation,
+                times,
+                ping_pong,
+            } => {
+                let duration = animation.duration();
+                if duration <= 0.0 || !duration.is_finite() {
+                    animation.apply(target, time,);
+                    return;
+                }
+                let mut round = (time / duration).floor();
+                let mut local = time - round * duration;
+                if let Some(times,) = times {
+                    if times == 0 {
+                        return;
+                    }
+                    if round >= times as f32 {
+                        round = (times - 1) as f32;
+                        local = duration;
+                    }
+                }
+                if ping_pong && round % 2.0 == 1.0 {
+                    local = duration - local;
+                }
+                animation.apply(target, local,);
+            }
+            Animation::Offset(ref animation, seconds,) => {
+                animation.apply(target, time + seconds.max(0.0,),)
+            }
+        }
+    }
+}
+
+/// Plays an animation on whatever owns it. The animation is shared, so cloning entities for
+/// snapshots stays cheap.
+#[cfg_attr(feature = "hot-reload", derive(Layout))]
+pub struct Animator<T,> {
+    animation: Rc<Animation<T,>,>,
+    time: f32,
+}
+
+impl<T,> Animator<T,> {
+    pub fn new(animation: Animation<T,>,) -> Animator<T,> {
+        Animator {
+            animation: Rc::new(animation,),
+            time: 0.0,
+        }
+    }
+
+    /// Moves the animation forward and writes the new values into `target`.
+    pub fn update(&mut self, target: &mut T, delta: f32,) {
+        if delta.is_finite() && delta > 0.0 {
+            self.time += delta;
+        }
+        self.animation.apply(target, self.time,);
+    }
+
+    /// Like `update`, for an animator kept in `target` itself, which `animator` picks out.
+    pub fn update_in<F,>(target: &mut T, animator: F, delta: f32,)
+    where
+        F: Fn(&mut T,) -> &mut Animator<T,>,
+    {
+        let (animation, time,) = {
+            let animator = animator(target,);
+            if delta.is_finite() && delta > 0.0 {
+                animator.time += delta;
+            }
+            (animator.animation.clone(), animator.time,)
+        };
+        animation.apply(target, time,);
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.animation.duration()
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+}
+
+impl<T,> Clone for Animator<T,> {
+    fn clone(&self) -> Animator<T,> {
+        Animator {
+            animation: self.animation.clone(),
+            time: self.time,
+        }
+    }
+}
+
+#[test]
+fn test_eases_start_at_zero_and_end_at_one() {
+    let eases = [
+        Ease::Linear,
+        Ease::QuadIn,
+        Ease::QuadOut,
+        Ease::QuadInOut,
+        Ease::CubicIn,
+        Ease::CubicOut,
+        Ease::CubicInOut,
+        Ease::SineInOut,
+        Ease::ElasticIn,
+        Ease::ElasticOut,
+        Ease::BounceIn,
+        Ease::BounceOut,
+        Ease::BackIn,
+        Ease::BackOut,
+    ];
+    for ease in eases.iter() {
+        assert!(ease.apply(0.0,).abs() < 1e-5, "{:?}", ease);
+        assert!((ease.apply(1.0,) - 1.0).abs() < 1e-5, "{:?}", ease);
+        assert_eq!(ease.apply(-3.0,), ease.apply(0.0,));
+        assert_eq!(ease.apply(3.0,), ease.apply(1.0,));
+    }
+    assert_eq!(Ease::QuadIn.apply(0.5,), 0.25);
+    assert!(Ease::BackIn.apply(0.2,) < 0.0);
+    assert!(Ease::ElasticOut.apply(0.2,) > 1.0);
+}
+
+#[test]
+fn test_animation_groups() {
+    #[derive(Default)]
+    struct Box2 {
+        x: f32,
+        y: f32,
+    }
+    let set_x: Setter<Box2,> = |b, v| b.x = v;
+    let set_y: Setter<Box2,> = |b, v| b.y = v;
+
+    // x moves 0 -> 10, waits, then comes back while y goes up
+    let animation = Animation::Sequence(vec![
+        Animation::tween(set_x, 0.0, 10.0, 1.0, Ease::Linear,),
+        Animation::Delay(1.0,),
+        Animation::Parallel(vec![
+            Animation::tween(set_x, 10.0, 0.0, 1.0, Ease::Linear,),
+            Animation::tween(set_y, 0.0, 4.0, 2.0, Ease::Linear,),
+        ],),
+    ],);
+    assert_eq!(animation.duration(), 4.0);
+    let mut b = Box2::default();
+    let mut at = |time: f32| {
+        animation.apply(&mut b, time,);
+        (b.x, b.y,)
+    };
+    assert_eq!(at(0.5), (5.0, 0.0));
+    assert_eq!(at(1.5), (10.0, 0.0));
+    assert_eq!(at(2.5), (5.0, 1.0));
+    assert_eq!(at(10.0), (0.0, 4.0));
+
+    let ping_pong = Animation::tween(set_x, 0.0, 10.0, 1.0, Ease::Linear,).ping_pong();
+    let mut animator = Animator::new(ping_pong,);
+    let mut b = Box2::default();
+    let mut values = Vec::new();
+    for _ in 0..4 {
+        animator.update(&mut b, 0.75,);
+        values.push(b.x,);
+    }
+    assert_eq!(values, vec![7.5, 5.0, 2.5, 10.0]);
+    assert!(!animator.is_finished());
+
+    let twice = Animation::tween(set_x, 0.0, 10.0, 1.0, Ease::Linear,).repeated(Some(2,), false,);
+    assert_eq!(twice.duration(), 2.0);
+    twice.apply(&mut b, 1.5,);
+    assert_eq!(b.x, 5.0);
+    twice.apply(&mut b, 5.0,);
+    assert_eq!(b.x, 10.0);
+
+    let half_way = Animation::tween(set_x, 0.0, 10.0, 1.0, Ease::Linear,).starting_at(0.5,);
+    assert_eq!(half_way.duration(), 0.5);
+    half_way.apply(&mut b, 0.25,);
+    assert_eq!(b.x, 7.5);
+}
@@ -94,3 +94,80 @@ fn generate_struct_field_vertex_attrib_pointer_call(field: &syn::Field) -> quote
         let offset = offset + std::mem::size_of::<#field_ty>();
     }
 }
+
+/// Implements `game_core::hot::Layout` with the name, size and the name and type of every field,
+/// and with the layouts of the field types, so hot reloading can tell when a saved type or
+/// anything inside it changed. Only for types inside game-core.
+#[proc_macro_derive(Layout)]
+pub fn layout_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let s = input.to_string();
+    let mut ast = syn::parse_derive_input(&s,).unwrap();
+    // a field may be of a type parameter, its layout is needed as well
+    for param in &mut ast.generics.ty_params {
+        param.bounds.push(syn::parse_ty_param_bound("::hot::Layout",).unwrap(),);
+    }
+
+    let ident = &ast.ident;
+    let (impl_generics, ty_generics, where_clause,) = ast.generics.split_for_impl();
+    let fields = body_layout(&ast.body,);
+    let field_types = body_types(&ast.body,);
+    let gen = quote! {
+        impl #impl_generics ::hot::Layout for #ident #ty_generics #where_clause {
+            fn layout() -> String {
+                format!(
+                    "{}{} size {} align {}",
+                    ::std::any::type_name::<Self>(),
+                    #fields,
+                    ::std::mem::size_of::<Self>(),
+                    ::std::mem::align_of::<Self>()
+                )
+            }
+
+            fn add_layouts(layouts: &mut ::std::collections::BTreeSet<String>) {
+                if layouts.insert(<Self as ::hot::Layout>::layout()) {
+                    #(<#field_types as ::hot::Layout>::add_layouts(layouts);)*
+                }
+            }
+        }
+    };
+    gen.parse().unwrap()
+}
+
+fn body_layout(body: &syn::Body) -> String {
+    match *body {
+        syn::Body::Struct(ref data,) => fields_layout(data,),
+        syn::Body::Enum(ref variants,) => {
+            let variants: Vec<String,> = variants
+                .iter()
+                .map(|v| format!("{}{}", v.ident, fields_layout(&v.data,)),)
+                .collect();
+            format!("{{{}}}", variants.join(",",))
+        }
+    }
+}
+
+fn fields_layout(data: &syn::VariantData) -> String {
+    let fields: Vec<String,> = data
+        .fields()
+        .iter()
+        .map(|field| {
+            let ty = &field.ty;
+            match field.ident {
+                Some(ref name,) => format!("{}: {}", name, quote!(#ty)),
+                None => format!("{}", quote!(#ty)),
+            }
+        },)
+        .collect();
+    format!("{{{}}}", fields.join(",",))
+}
+
+/// Types of all fields, of every variant for enums.
+fn body_types(body: &syn::Body) -> Vec<&syn::Ty,> {
+    match *body {
+        syn::Body::Struct(ref data,) => data.fields().iter().map(|field| &field.ty,).collect(),
+        syn::Body::Enum(ref variants,) => variants
+            .iter()
+            .flat_map(|v| v.data.fields().iter().map(|field| &field.ty,),)
+            .collect(),
+    }
+}
@@ -0,0 +1,53 @@
+//! game-core built as a dynamic library for `linux-host`, which swaps it for a new build while
+//! the game runs. Only host and library built from the same `game_core::hot::ABI_VERSION` can
+//! talk to each other, the types passed here are not C types.
+#![allow(improper_ctypes_definitions)]
+
+extern crate game_core;
+
+use game_core::{flow::GameStatus, hot, GameInput, Platform};
+use std::{os::raw::c_void, slice};
+
+#[no_mangle]
+pub extern "C" fn hot_abi_version() -> u64 {
+    hot::ABI_VERSION
+}
+
+/// Carries on with `saved` from `hot_unload` of the last build, or starts a new game when there
+/// is none or its layout changed. Returns false when a new game was started.
+///
+/// # Safety
+///
+/// `saved` must be null or come from `hot_unload`, and be used once.
+#[no_mangle]
+pub unsafe extern "C" fn hot_load(
+    platform: Platform,
+    size_x: f32,
+    size_y: f32,
+    player_count: usize,
+    saved: *mut c_void,
+) -> bool {
+    hot::load(platform, size_x, size_y, player_count, saved,)
+}
+
+/// `game_loop_players` over `count` inputs.
+///
+/// # Safety
+///
+/// `inputs` must point to `count` inputs.
+#[no_mangle]
+pub unsafe extern "C" fn hot_loop(
+    inputs: *const GameInput,
+    count: usize,
+    time_elapsed: f32,
+    delta: f32,
+) -> GameStatus {
+    let inputs = slice::from_raw_parts(inputs, count,).to_vec();
+    game_core::game_loop_players(inputs, time_elapsed, delta,)
+}
+
+/// Moves the game out of this build for `hot_load` of the next one.
+#[no_mangle]
+pub extern "C" fn hot_unload() -> *mut c_void {
+    hot::save()
+}
@@ -0,0 +1,104 @@
+use rasterizer::image::Rgba8;
+
+/// How far a frame may be from its golden image and still pass. Anti-aliased edges can come
+/// out a little different with another compiler or CPU.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference in any channel, 0 to 255, that does not count.
+    pub channel: u8,
+    /// Pixels that may differ by more than `channel`.
+    pub pixels: usize,
+}
+
+pub const DEFAULT_TOLERANCE: Tolerance = Tolerance {
+    channel: 4,
+    pixels: 32,
+};
+
+pub struct Comparison {
+    /// Pixels that differ by more than the channel tolerance.
+    pub differing: usize,
+    /// Largest difference in any channel of any pixel.
+    pub max_difference: u8,
+    /// Differing pixels red, the rest the actual frame in dim grey.
+    pub diff: Rgba8,
+}
+
+impl Comparison {
+    pub fn passes(&self, tolerance: Tolerance,) -> bool {
+        self.differing <= tolerance.pixels
+    }
+}
+
+/// Compares `actual` with `expected` pixel by pixel. Fails when the sizes differ.
+pub fn compare(
+    expected: &Rgba8,
+    actual: &Rgba8,
+    tolerance: Tolerance,
+) -> Result<Comparison, String,> {
+    if expected.width != actual.width || expected.height != actual.height {
+        return Err(format!(
+            "size is {}x{}, the golden image is {}x{}",
+            actual.width, actual.height, expected.width, expected.height
+        ),);
+    }
+    let mut differing = 0;
+    let mut max_difference = 0;
+    let mut diff = Vec::with_capacity(actual.pixels.len(),);
+    for (want, got,) in expected.pixels.chunks(4,).zip(actual.pixels.chunks(4,),) {
+        let difference = want
+            .iter()
+            .zip(got.iter(),)
+            .map(|(a, b,)| (i16::from(*a,) - i16::from(*b,)).unsigned_abs() as u8,)
+            .max()
+            .unwrap_or(0,);
+        max_difference = max_difference.max(difference,);
+        if difference > tolerance.channel {
+            differing += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255],);
+        } else {
+            let grey = ((u16::from(got[0],) + u16::from(got[1],) + u16::from(got[2],)) / 9) as u8;
+            diff.extend_from_slice(&[grey, grey, grey, 255],);
+        }
+    }
+    Ok(Comparison {
+        differing,
+        max_difference,
+        diff: Rgba8 {
+            width: actual.width,
+            height: actual.height,
+            pixels: diff,
+        },
+    },)
+}
+
+#[test]
+fn test_compare() {
+    let image = |pixels: Vec<u8,>| Rgba8 {
+        width: 2,
+        height: 1,
+        pixels,
+    };
+    let expected = image(vec![10, 20, 30, 255, 200, 200, 200, 255],);
+    let close = image(vec![12, 20, 30, 255, 200, 200, 197, 255],);
+    let tolerance = Tolerance {
+        channel: 4,
+        pixels: 0,
+    };
+    let comparison = compare(&expected, &close, tolerance,).unwrap();
+    assert_eq!((comparison.differing, comparison.max_difference,), (0, 3,));
+    assert!(comparison.passes(tolerance,));
+
+    let off = image(vec![10, 20, 30, 255, 0, 200, 200, 255],);
+    let comparison = compare(&expected, &off, tolerance,).unwrap();
+    assert_eq!(comparison.differing, 1);
+    assert!(!comparison.passes(tolerance,));
+    assert_eq!(&comparison.diff.pixels[4..], &[255, 0, 0, 255]);
+
+    let small = Rgba8 {
+        width: 1,
+        height: 1,
+        pixels: vec![0; 4],
+    };
+    assert!(compare(&expected, &small, tolerance,).is_err());
+}
@@ -0,0 +1,237 @@
+//! Golden image tests. Seeded scenarios are played through the game loop, drawn with the
+//! software rasterizer and compared with the PNGs in `images/`. `cargo test -p golden` checks
+//! them and writes the frame and a diff image to `target/golden` for each mismatch,
+//! `cargo run -p golden -- bless` writes the golden images again after an intended change.
+
+extern crate game_core;
+extern crate rasterizer;
+
+pub mod compare;
+
+use compare::{compare, DEFAULT_TOLERANCE};
+use game_core::{
+    entities::Color,
+    game_init_players, game_loop_players,
+    math::vector::Vector2d,
+    sound::SoundEvent,
+    sprite::{Flip, Sprite},
+    GameInput, Platform,
+};
+use rasterizer::{
+    image::{load_png, save_png, Rgba8},
+    Canvas,
+};
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    path::PathBuf,
+};
+
+pub const WIDTH: i32 = 960;
+pub const HEIGHT: i32 = 540;
+/// Every frame of a scenario is this long, so the frame time graph is the same on every run.
+const DELTA: f32 = 1.0 / 60.0;
+
+/// A game played with scripted input, its last frame is the image.
+pub struct Scenario {
+    pub name: &'static str,
+    pub players: usize,
+    /// Seeds the platform's random numbers, the game takes its seed from them.
+    pub seed: u64,
+    pub frames: u32,
+    /// Input of a player slot on a frame.
+    pub input: fn(frame: u32, slot: usize,) -> GameInput,
+}
+
+pub const SCENARIOS: [Scenario; 4] = [
+    Scenario {
+        name: "title",
+        players: 1,
+        seed: 1,
+        frames: 1,
+        input: no_input,
+    },
+    Scenario {
+        name: "playing",
+        players: 2,
+        seed: 2,
+        frames: 90,
+        input: walk_and_shoot,
+    },
+    Scenario {
+        name: "paused",
+        players: 1,
+        seed: 3,
+        frames: 41,
+        input: pause_on_40,
+    },
+    Scenario {
+        name: "debug_overlay",
+        players: 1,
+        seed: 4,
+        frames: 60,
+        input: debug_on_10,
+    },
+];
+
+fn no_input(_frame: u32, _slot: usize,) -> GameInput {
+    GameInput::new()
+}
+
+/// Space on the first frame starts the game from the title screen.
+fn start(frame: u32) -> GameInput {
+    let mut input = GameInput::new();
+    input.space = frame == 0;
+    input
+}
+
+fn walk_and_shoot(frame: u32, slot: usize,) -> GameInput {
+    let mut input = start(frame,);
+    input.right_key = slot == 0;
+    input.left_key = slot == 1;
+    input.shoot_up = true;
+    input
+}
+
+fn pause_on_40(frame: u32, _slot: usize,) -> GameInput {
+    let mut input = start(frame,);
+    input.down_key = true;
+    input.shoot_left = true;
+    input.quit_key = frame == 40;
+    input
+}
+
+fn debug_on_10(frame: u32, _slot: usize,) -> GameInput {
+    let mut input = start(frame,);
+    input.up_key = true;
+    input.shoot_right = true;
+    input.debug_key = frame == 10;
+    input
+}
+
+thread_local!(static CANVAS: RefCell<Option<Canvas,>,> = const { RefCell::new(None,) });
+thread_local!(static RANDOM_STATE: Cell<u64> = const { Cell::new(1,) });
+
+fn with_canvas<F: FnOnce(&mut Canvas,),>(f: F,) {
+    CANVAS.with(|canvas| {
+        if let Some(ref mut canvas,) = *canvas.borrow_mut() {
+            f(canvas,);
+        }
+    },);
+}
+
+fn random() -> f32 {
+    // xorshift64*
+    RANDOM_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x,);
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d,) >> 40) as f32 / (1u64 << 24) as f32
+    },)
+}
+
+fn draw_rectangle(min_x: f32, min_y: f32, max_x: f32, max_y: f32, color: Color,) {
+    with_canvas(|canvas| canvas.draw_rectangle(min_x, min_y, max_x, max_y, color,),);
+}
+
+/// No atlas is loaded, so the game never draws sprites. Their tint stands in if it does.
+fn draw_sprite(
+    _sprite: Sprite,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+    tint: Color,
+    _flip: Flip,
+) {
+    draw_rectangle(min_x, min_y, max_x, max_y, tint,);
+}
+
+fn draw_quad(corners: [Vector2d; 4], color: Color,) {
+    with_canvas(|canvas| canvas.fill_convex(&corners, color,),);
+}
+
+fn platform() -> Platform {
+    Platform {
+        random,
+        log: |_| {},
+        start_frame: || with_canvas(|canvas| canvas.clear(0xff00_0000,),),
+        end_frame: || {},
+        draw_rectangle,
+        play_sound: |_: SoundEvent| {},
+        draw_sprite,
+        draw_quad,
+        now: || 0.0,
+    }
+}
+
+/// Plays `scenario` from a new game and returns its last frame.
+pub fn render(scenario: &Scenario) -> Rgba8 {
+    CANVAS.with(|canvas| *canvas.borrow_mut() = Some(Canvas::new(WIDTH, HEIGHT,),),);
+    // xorshift gets stuck on 0
+    RANDOM_STATE.with(|state| state.set(scenario.seed.max(1,),),);
+    game_init_players(WIDTH as f32, HEIGHT as f32, scenario.players, platform(),);
+    for frame in 0..scenario.frames {
+        let inputs = (0..scenario.players).map(|slot| (scenario.input)(frame, slot,),).collect();
+        game_loop_players(inputs, frame as f32 * DELTA, DELTA,);
+    }
+    CANVAS.with(|canvas| match *canvas.borrow() {
+        Some(ref canvas,) => Rgba8::from_canvas(canvas,),
+        None => unreachable!(),
+    },)
+}
+
+pub fn golden_path(scenario: &Scenario) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"),)
+        .join("images",)
+        .join(format!("{}.png", scenario.name),)
+}
+
+/// Where the frames and diff images of failed checks go.
+pub fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"),).join("../target/golden",)
+}
+
+/// Renders `scenario` and compares it with its golden image. On a mismatch the frame and a
+/// diff image are written to `output_dir` and the error says where.
+pub fn check(scenario: &Scenario) -> Result<(), String,> {
+    let actual = render(scenario,);
+    let golden = golden_path(scenario,);
+    let expected = load_png(&golden.to_string_lossy(),).map_err(|e| e.to_string(),)?;
+    let comparison = compare(&expected, &actual, DEFAULT_TOLERANCE,)
+        .map_err(|e| format!("{}: {}", scenario.name, e),)?;
+    if comparison.passes(DEFAULT_TOLERANCE,) {
+        return Ok((),);
+    }
+    let dir = output_dir();
+    fs::create_dir_all(&dir,).map_err(|e| format!("{}: {}", dir.display(), e),)?;
+    let frame_path = dir.join(format!("{}.png", scenario.name),);
+    let diff_path = dir.join(format!("{}.diff.png", scenario.name),);
+    save_png(&frame_path.to_string_lossy(), &actual,).map_err(|e| e.to_string(),)?;
+    save_png(&diff_path.to_string_lossy(), &comparison.diff,).map_err(|e| e.to_string(),)?;
+    Err(format!(
+        "{}: {} pixels differ, by up to {}, see {}",
+        scenario.name,
+        comparison.differing,
+        comparison.max_difference,
+        diff_path.display()
+    ),)
+}
+
+/// Renders `scenario` and makes the frame its golden image.
+pub fn bless(scenario: &Scenario) -> Result<(), String,> {
+    let golden = golden_path(scenario,);
+    save_png(&golden.to_string_lossy(), &render(scenario,),).map_err(|e| e.to_string(),)
+}
+
+#[test]
+fn test_golden_images() {
+    let failures: Vec<String,> = SCENARIOS.iter().filter_map(|s| check(s,).err(),).collect();
+    assert!(
+        failures.is_empty(),
+        "{}\nrun `cargo run -p golden -- bless` if the change is intended",
+        failures.join("\n",)
+    );
+}
@@ -0,0 +1,26 @@
+//! `golden` checks every scenario against its golden image, `golden bless` writes the golden
+//! images again from this build.
+
+extern crate golden;
+
+use golden::{bless, check, golden_path, SCENARIOS};
+use std::{env, process};
+
+fn main() {
+    let blessing = env::args().nth(1,).is_some_and(|a| a == "bless",);
+    let mut failed = false;
+    for scenario in SCENARIOS.iter() {
+        let result = if blessing { bless(scenario,) } else { check(scenario,) };
+        match result {
+            Ok((),) if blessing => println!("wrote {}", golden_path(scenario,).display()),
+            Ok((),) => println!("{}: ok", scenario.name),
+            Err(e,) => {
+                println!("{}", e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1,);
+    }
+}
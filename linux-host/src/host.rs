@@ -0,0 +1,242 @@
+//! The game loop, the platform functions the game draws through and the reloading.
+
+use game_core::{
+    entities::Color,
+    math::vector::Vector2d,
+    sound::SoundEvent,
+    sprite::{Flip, Sprite},
+    GameInput, Platform,
+};
+use library::{GameLibrary, Watcher};
+use rasterizer::Canvas;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process, ptr, thread,
+    time::{Duration, Instant},
+};
+use x11::Window;
+
+const WIDTH: i32 = 960;
+const HEIGHT: i32 = 540;
+
+static mut CANVAS: Option<Canvas,> = None;
+static mut START_TIME: Option<Instant,> = None;
+static mut RANDOM_STATE: u64 = 0x2545_f491_4f6c_dd1d;
+
+fn random() -> f32 {
+    // xorshift64*
+    unsafe {
+        RANDOM_STATE ^= RANDOM_STATE >> 12;
+        RANDOM_STATE ^= RANDOM_STATE << 25;
+        RANDOM_STATE ^= RANDOM_STATE >> 27;
+        (RANDOM_STATE.wrapping_mul(0x2545_f491_4f6c_dd1d,) >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn log(s: String) {
+    println!("{}", s);
+}
+
+fn now() -> f64 {
+    unsafe {
+        match START_TIME {
+            Some(start,) => start.elapsed().as_secs_f64(),
+            None => 0.0,
+        }
+    }
+}
+
+fn with_canvas<F: FnOnce(&mut Canvas,),>(f: F,) {
+    unsafe {
+        if let Some(ref mut canvas,) = *ptr::addr_of_mut!(CANVAS) {
+            f(canvas,);
+        }
+    }
+}
+
+fn start_frame() {
+    with_canvas(|canvas| canvas.clear(0xff00_0000,),);
+}
+
+/// The host presents the canvas itself after the frame, it owns the window.
+fn end_frame() {}
+
+fn draw_rectangle(min_x: f32, min_y: f32, max_x: f32, max_y: f32, color: Color,) {
+    with_canvas(|canvas| canvas.draw_rectangle(min_x, min_y, max_x, max_y, color,),);
+}
+
+/// No atlas is loaded, sprites are drawn as their tint.
+fn draw_sprite(
+    _sprite: Sprite,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+    tint: Color,
+    _flip: Flip,
+) {
+    draw_rectangle(min_x, min_y, max_x, max_y, tint,);
+}
+
+fn draw_quad(corners: [Vector2d; 4], color: Color,) {
+    with_canvas(|canvas| canvas.fill_convex(&corners, color,),);
+}
+
+fn play_sound(_event: SoundEvent) {}
+
+fn platform() -> Platform {
+    Platform {
+        random,
+        log,
+        start_frame,
+        end_frame,
+        draw_rectangle,
+        play_sound,
+        draw_sprite,
+        draw_quad,
+        now,
+    }
+}
+
+fn arg_value(name: &str) -> Option<String,> {
+    let args: Vec<String,> = env::args().collect();
+    args.iter().position(|a| a == name,).and_then(|i| args.get(i + 1,).cloned(),)
+}
+
+/// `libgame_dylib.so` next to the executable, where cargo puts both.
+fn default_library_path() -> PathBuf {
+    let exe = env::current_exe().unwrap_or_default();
+    exe.parent().unwrap_or_else(|| Path::new(".",),).join("libgame_dylib.so",)
+}
+
+/// Hands the game from `current` to a new build of `path`. Keeps running `current` when the new
+/// build cannot be loaded. The old library is returned so it stays loaded.
+fn reload(
+    current: GameLibrary,
+    path: &Path,
+    generation: u32,
+    player_count: usize,
+) -> (GameLibrary, Option<GameLibrary,>,) {
+    match GameLibrary::open(path, generation,) {
+        Ok(next,) => {
+            let saved = (current.unload)();
+            let kept = unsafe {
+                (next.load)(platform(), WIDTH as f32, HEIGHT as f32, player_count, saved,)
+            };
+            if kept {
+                println!("Reloaded {}", path.display());
+            } else {
+                println!(
+                    "Reloaded {}, the saved game does not fit this build, started a new game",
+                    path.display()
+                );
+            }
+            (next, Some(current,),)
+        }
+        Err(e,) => {
+            println!("Could not reload {}: {}", path.display(), e);
+            (current, None,)
+        }
+    }
+}
+
+pub fn run() {
+    let library_path =
+        arg_value("--lib",).map(PathBuf::from,).unwrap_or_else(default_library_path,);
+    let player_count = arg_value("--players",).and_then(|p| p.parse().ok(),).unwrap_or(1,);
+    let headless_frames: Option<u32,> = arg_value("--headless",).and_then(|f| f.parse().ok(),);
+
+    let mut window = match headless_frames {
+        Some(_,) => None,
+        None => match Window::open("Game", WIDTH, HEIGHT,) {
+            Some(window,) => Some(window,),
+            None => {
+                println!("Could not open an X11 window, try --headless <frames>");
+                process::exit(1,);
+            }
+        },
+    };
+
+    unsafe {
+        CANVAS = Some(Canvas::new(WIDTH, HEIGHT,),);
+        START_TIME = Some(Instant::now(),);
+    }
+    let mut library = match GameLibrary::open(&library_path, 0,) {
+        Ok(library,) => library,
+        Err(e,) => {
+            println!("{}: {}", library_path.display(), e);
+            process::exit(1,);
+        }
+    };
+    unsafe {
+        (library.load)(platform(), WIDTH as f32, HEIGHT as f32, player_count, ptr::null_mut(),);
+    }
+
+    let mut watcher = Watcher::new(&library_path,);
+    // never unloaded, see `GameLibrary`
+    let mut retired = Vec::new();
+    let mut generation = 1;
+    let mut frame = 0;
+    let frame_time = Duration::from_millis(16,);
+    let start = Instant::now();
+    let mut last_frame = start;
+    loop {
+        if watcher.changed() {
+            let (current, old,) = reload(library, &library_path, generation, player_count,);
+            library = current;
+            retired.extend(old,);
+            generation += 1;
+        }
+
+        let input = match window {
+            Some(ref mut window,) => {
+                window.poll();
+                if window.is_closed() {
+                    break;
+                }
+                window.input()
+            }
+            None => {
+                // start the game from the title screen
+                let mut input = GameInput::new();
+                input.space = frame == 1;
+                input
+            }
+        };
+        let inputs = vec![input; player_count];
+
+        let now = Instant::now();
+        let delta = (now - last_frame).as_secs_f32();
+        last_frame = now;
+        let status = unsafe {
+            (library.game_loop)(
+                inputs.as_ptr(),
+                inputs.len(),
+                (now - start).as_secs_f32(),
+                delta,
+            )
+        };
+        if let Some(ref mut window,) = window {
+            unsafe {
+                if let Some(ref canvas,) = *ptr::addr_of!(CANVAS) {
+                    window.present(canvas,);
+                }
+            }
+        }
+        if status.should_quit() {
+            break;
+        }
+
+        frame += 1;
+        if headless_frames.is_some_and(|frames| frame >= frames,) {
+            println!("Ran {} frames, {:?}", frame, status);
+            break;
+        }
+        let elapsed = now.elapsed();
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed,);
+        }
+    }
+}
+
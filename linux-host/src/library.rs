@@ -0,0 +1,155 @@
+use game_core::{flow::GameStatus, hot::ABI_VERSION, GameInput, Platform};
+use libc;
+use std::{
+    ffi::{CStr, CString},
+    fmt, fs, mem,
+    os::raw::c_void,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant, SystemTime},
+};
+
+// The functions of game-dylib, built from the same ABI_VERSION.
+type AbiVersionFn = extern "C" fn() -> u64;
+#[allow(improper_ctypes_definitions)]
+type LoadFn = unsafe extern "C" fn(Platform, f32, f32, usize, *mut c_void,) -> bool;
+#[allow(improper_ctypes_definitions)]
+type LoopFn = unsafe extern "C" fn(*const GameInput, usize, f32, f32,) -> GameStatus;
+type UnloadFn = extern "C" fn() -> *mut c_void;
+
+#[derive(Debug)]
+pub enum LibraryError {
+    Io(String,),
+    Open(String,),
+    Symbol(String,),
+    /// The library was built against another `hot::ABI_VERSION` than the host.
+    Abi { found: u64, },
+}
+
+impl fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match self {
+            LibraryError::Io(message,) => write!(f, "could not copy the library: {}", message),
+            LibraryError::Open(message,) => write!(f, "could not load the library: {}", message),
+            LibraryError::Symbol(name,) => write!(f, "the library has no {}", name),
+            LibraryError::Abi { found, } => write!(
+                f,
+                "the library has ABI version {} and the host {}, rebuild the host",
+                found, ABI_VERSION
+            ),
+        }
+    }
+}
+
+/// The game functions of a loaded build of `game-dylib`.
+///
+/// Libraries are never closed. A game handed over to a new build can still hold pointers to
+/// functions of the old one, like the setters of its tweens.
+pub struct GameLibrary {
+    pub load: LoadFn,
+    pub game_loop: LoopFn,
+    pub unload: UnloadFn,
+}
+
+impl GameLibrary {
+    /// Loads a copy of `path`, numbered by `generation`. The linker can then write the next
+    /// build over `path`, and the dynamic loader does not hand back the build it already has.
+    pub fn open(path: &Path, generation: u32,) -> Result<GameLibrary, LibraryError,> {
+        let copy = std::env::temp_dir().join(format!(
+            "game-dylib-{}-{}.so",
+            process::id(),
+            generation
+        ),);
+        fs::copy(path, &copy,).map_err(|e| LibraryError::Io(e.to_string(),),)?;
+        let result = unsafe { GameLibrary::open_copy(&copy,) };
+        // the loader keeps its own mapping of the file
+        let _ = fs::remove_file(&copy,);
+        result
+    }
+
+    unsafe fn open_copy(copy: &Path) -> Result<GameLibrary, LibraryError,> {
+        let name = CString::new(copy.to_string_lossy().as_bytes(),)
+            .map_err(|e| LibraryError::Open(e.to_string(),),)?;
+        let handle = libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL,);
+        if handle.is_null() {
+            return Err(LibraryError::Open(last_error(),),);
+        }
+        let abi_version =
+            mem::transmute::<*mut c_void, AbiVersionFn,>(symbol(handle, "hot_abi_version",)?,);
+        let found = abi_version();
+        if found != ABI_VERSION {
+            libc::dlclose(handle,);
+            return Err(LibraryError::Abi { found, },);
+        }
+        Ok(GameLibrary {
+            load: mem::transmute::<*mut c_void, LoadFn,>(symbol(handle, "hot_load",)?,),
+            game_loop: mem::transmute::<*mut c_void, LoopFn,>(symbol(handle, "hot_loop",)?,),
+            unload: mem::transmute::<*mut c_void, UnloadFn,>(symbol(handle, "hot_unload",)?,),
+        },)
+    }
+}
+
+unsafe fn symbol(handle: *mut c_void, name: &str,) -> Result<*mut c_void, LibraryError,> {
+    let c_name = CString::new(name,).unwrap();
+    let address = libc::dlsym(handle, c_name.as_ptr(),);
+    if address.is_null() {
+        return Err(LibraryError::Symbol(name.to_string(),),);
+    }
+    Ok(address,)
+}
+
+unsafe fn last_error() -> String {
+    let error = libc::dlerror();
+    if error.is_null() {
+        return String::from("unknown error",);
+    }
+    CStr::from_ptr(error,).to_string_lossy().into_owned()
+}
+
+/// Seconds between looks at the library file.
+const CHECK_INTERVAL: f32 = 0.25;
+
+/// Notices new builds of a file.
+pub struct Watcher {
+    path: PathBuf,
+    modified: Option<SystemTime,>,
+    /// Changed time seen at the last check, the file is only reported once it stays the same.
+    pending: Option<SystemTime,>,
+    last_check: Instant,
+}
+
+impl Watcher {
+    pub fn new(path: &Path) -> Watcher {
+        Watcher {
+            path: path.to_path_buf(),
+            modified: modified(path,),
+            pending: None,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// True once the file changed and then stayed the same for a check, so a library the
+    /// linker is still writing is not loaded.
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < Duration::from_millis((CHECK_INTERVAL * 1000.0) as u64,) {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let current = modified(&self.path,);
+        if current.is_none() || current == self.modified {
+            self.pending = None;
+            return false;
+        }
+        if current != self.pending {
+            self.pending = current;
+            return false;
+        }
+        self.modified = current;
+        self.pending = None;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime,> {
+    fs::metadata(path,).and_then(|m| m.modified(),).ok()
+}
@@ -0,0 +1,29 @@
+//! Runs game-core from the `game-dylib` library and swaps in new builds of it while the game
+//! runs, keeping the game state. Draws with the software rasterizer into an X11 window.
+//!
+//!     cargo build -p game-dylib && cargo run -p linux-host
+//!
+//! Options: `--lib <path>` for the library, next to the executable by default, `--players <n>`
+//! and `--headless <frames>` to run without a window.
+
+extern crate game_core;
+#[cfg(target_os = "linux")]
+extern crate libc;
+extern crate rasterizer;
+
+#[cfg(target_os = "linux")]
+mod host;
+#[cfg(target_os = "linux")]
+mod library;
+#[cfg(target_os = "linux")]
+mod x11;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    host::run();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    println!("linux-host only runs on Linux");
+}
@@ -0,0 +1,389 @@
+//! Just enough Xlib for a window showing the software canvas and the keyboard and mouse.
+//! libX11 is loaded when a window is opened, headless runs and builds do not need it.
+
+use game_core::{
+    action_map::{Key, KeyState},
+    GameInput,
+};
+use libc;
+use rasterizer::Canvas;
+use std::{
+    ffi::CString,
+    mem,
+    os::raw::{c_char, c_int, c_long, c_uint, c_ulong, c_void},
+    ptr::null_mut,
+};
+
+type Display = c_void;
+type Visual = c_void;
+type Gc = *mut c_void;
+type XImage = c_void;
+type XWindow = c_ulong;
+type Atom = c_ulong;
+type KeySym = c_ulong;
+
+/// Big enough for any event, like Xlib's union.
+#[repr(C)]
+struct XEvent {
+    pad: [c_long; 24],
+}
+
+impl XEvent {
+    fn kind(&self) -> c_int {
+        self.pad[0] as c_int
+    }
+}
+
+/// Layout shared by key, button and motion events up to `detail`, which is the keycode or
+/// the button.
+#[allow(dead_code)]
+#[repr(C)]
+struct XInputEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    window: XWindow,
+    root: XWindow,
+    subwindow: XWindow,
+    time: c_ulong,
+    x: c_int,
+    y: c_int,
+    x_root: c_int,
+    y_root: c_int,
+    state: c_uint,
+    detail: c_uint,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct XClientMessageEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    window: XWindow,
+    message_type: Atom,
+    format: c_int,
+    data: [c_long; 5],
+}
+
+/// Declares the Xlib functions the window uses. They are looked up when a window is opened, so
+/// a headless host runs without libX11.
+macro_rules! xlib {
+    ($($name:ident: fn($($arg:ty,)*) -> $ret:ty;)*) => {
+        #[allow(non_snake_case)]
+        #[derive(Copy, Clone)]
+        struct Xlib {
+            $($name: unsafe extern "C" fn($($arg,)*) -> $ret,)*
+        }
+
+        impl Xlib {
+            /// None when libX11 is missing. It is never closed, like the game libraries.
+            unsafe fn load() -> Option<Xlib,> {
+                let name = CString::new("libX11.so.6",).unwrap();
+                let handle = libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL,);
+                if handle.is_null() {
+                    return None;
+                }
+                Some(Xlib {
+                    $($name: mem::transmute::<
+                        *mut c_void,
+                        unsafe extern "C" fn($($arg,)*) -> $ret,
+                    >(symbol(handle, stringify!($name),)?,),)*
+                },)
+            }
+        }
+    };
+}
+
+xlib! {
+    XOpenDisplay: fn(*const c_char,) -> *mut Display;
+    XCloseDisplay: fn(*mut Display,) -> c_int;
+    XDefaultScreen: fn(*mut Display,) -> c_int;
+    XRootWindow: fn(*mut Display, c_int,) -> XWindow;
+    XBlackPixel: fn(*mut Display, c_int,) -> c_ulong;
+    XDefaultGC: fn(*mut Display, c_int,) -> Gc;
+    XDefaultVisual: fn(*mut Display, c_int,) -> *mut Visual;
+    XDefaultDepth: fn(*mut Display, c_int,) -> c_int;
+    // display, parent, x, y, width, height, border width, border, background
+    XCreateSimpleWindow: fn(
+        *mut Display,
+        XWindow,
+        c_int,
+        c_int,
+        c_uint,
+        c_uint,
+        c_uint,
+        c_ulong,
+        c_ulong,
+    ) -> XWindow;
+    XStoreName: fn(*mut Display, XWindow, *const c_char,) -> c_int;
+    XSelectInput: fn(*mut Display, XWindow, c_long,) -> c_int;
+    XMapWindow: fn(*mut Display, XWindow,) -> c_int;
+    // display, name, only if exists
+    XInternAtom: fn(*mut Display, *const c_char, c_int,) -> Atom;
+    XSetWMProtocols: fn(*mut Display, XWindow, *mut Atom, c_int,) -> c_int;
+    XkbSetDetectableAutoRepeat: fn(*mut Display, c_int, *mut c_int,) -> c_int;
+    XPending: fn(*mut Display,) -> c_int;
+    XNextEvent: fn(*mut Display, *mut XEvent,) -> c_int;
+    XLookupKeysym: fn(*mut XEvent, c_int,) -> KeySym;
+    // display, visual, depth, format, offset, data, width, height, bitmap pad, bytes per line
+    XCreateImage: fn(
+        *mut Display,
+        *mut Visual,
+        c_uint,
+        c_int,
+        c_int,
+        *mut c_char,
+        c_uint,
+        c_uint,
+        c_int,
+        c_int,
+    ) -> *mut XImage;
+    // display, drawable, gc, image, source x and y, destination x and y, width, height
+    XPutImage: fn(
+        *mut Display,
+        XWindow,
+        Gc,
+        *mut XImage,
+        c_int,
+        c_int,
+        c_int,
+        c_int,
+        c_uint,
+        c_uint,
+    ) -> c_int;
+    XFlush: fn(*mut Display,) -> c_int;
+}
+
+unsafe fn symbol(handle: *mut c_void, name: &str,) -> Option<*mut c_void,> {
+    let c_name = CString::new(name,).unwrap();
+    let address = libc::dlsym(handle, c_name.as_ptr(),);
+    if address.is_null() {
+        None
+    } else {
+        Some(address,)
+    }
+}
+
+const KEY_PRESS_MASK: c_long = 1;
+const KEY_RELEASE_MASK: c_long = 1 << 1;
+const BUTTON_PRESS_MASK: c_long = 1 << 2;
+const BUTTON_RELEASE_MASK: c_long = 1 << 3;
+const POINTER_MOTION_MASK: c_long = 1 << 6;
+
+const KEY_PRESS: c_int = 2;
+const KEY_RELEASE: c_int = 3;
+const BUTTON_PRESS: c_int = 4;
+const BUTTON_RELEASE: c_int = 5;
+const MOTION_NOTIFY: c_int = 6;
+const CLIENT_MESSAGE: c_int = 33;
+
+const Z_PIXMAP: c_int = 2;
+
+pub struct Window {
+    xlib: Xlib,
+    display: *mut Display,
+    window: XWindow,
+    gc: Gc,
+    image: *mut XImage,
+    /// Top row first, 0x00RRGGBB. Xlib reads it through `image`, so it is never resized.
+    pixels: Box<[u32],>,
+    width: i32,
+    height: i32,
+    delete_message: Atom,
+    keys: KeyState,
+    mouse_x: f32,
+    mouse_y: f32,
+    mouse_left: bool,
+    mouse_right: bool,
+    closed: bool,
+}
+
+impl Window {
+    /// None when libX11 or an X display to open the window on is missing.
+    pub fn open(title: &str, width: i32, height: i32,) -> Option<Window,> {
+        unsafe {
+            let xlib = Xlib::load()?;
+            let display = (xlib.XOpenDisplay)(null_mut(),);
+            if display.is_null() {
+                return None;
+            }
+            let screen = (xlib.XDefaultScreen)(display,);
+            let depth = (xlib.XDefaultDepth)(display, screen,);
+            if depth != 24 && depth != 32 {
+                (xlib.XCloseDisplay)(display,);
+                return None;
+            }
+            let window = (xlib.XCreateSimpleWindow)(
+                display,
+                (xlib.XRootWindow)(display, screen,),
+                0,
+                0,
+                width as c_uint,
+                height as c_uint,
+                0,
+                (xlib.XBlackPixel)(display, screen,),
+                (xlib.XBlackPixel)(display, screen,),
+            );
+            let title = CString::new(title,).unwrap_or_default();
+            (xlib.XStoreName)(display, window, title.as_ptr(),);
+            (xlib.XSelectInput)(
+                display,
+                window,
+                KEY_PRESS_MASK
+                    | KEY_RELEASE_MASK
+                    | BUTTON_PRESS_MASK
+                    | BUTTON_RELEASE_MASK
+                    | POINTER_MOTION_MASK,
+            );
+            let delete_name = CString::new("WM_DELETE_WINDOW",).unwrap();
+            let mut delete_message = (xlib.XInternAtom)(display, delete_name.as_ptr(), 0,);
+            (xlib.XSetWMProtocols)(display, window, &mut delete_message, 1,);
+            // held keys send a single press instead of a press and release per repeat
+            (xlib.XkbSetDetectableAutoRepeat)(display, 1, null_mut(),);
+            (xlib.XMapWindow)(display, window,);
+
+            let mut pixels = vec![0u32; (width * height) as usize].into_boxed_slice();
+            let image = (xlib.XCreateImage)(
+                display,
+                (xlib.XDefaultVisual)(display, screen,),
+                depth as c_uint,
+                Z_PIXMAP,
+                0,
+                pixels.as_mut_ptr() as *mut c_char,
+                width as c_uint,
+                height as c_uint,
+                32,
+                width * 4,
+            );
+            if image.is_null() {
+                (xlib.XCloseDisplay)(display,);
+                return None;
+            }
+            Some(Window {
+                xlib,
+                display,
+                window,
+                gc: (xlib.XDefaultGC)(display, screen,),
+                image,
+                pixels,
+                width,
+                height,
+                delete_message,
+                keys: KeyState::new(),
+                mouse_x: 0.0,
+                mouse_y: 0.0,
+                mouse_left: false,
+                mouse_right: false,
+                closed: false,
+            },)
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Handles the events that arrived since the last call.
+    pub fn poll(&mut self) {
+        unsafe {
+            while (self.xlib.XPending)(self.display,) > 0 {
+                let mut event = XEvent { pad: [0; 24], };
+                (self.xlib.XNextEvent)(self.display, &mut event,);
+                match event.kind() {
+                    KEY_PRESS | KEY_RELEASE => {
+                        let keysym = (self.xlib.XLookupKeysym)(&mut event, 0,);
+                        if let Some(key,) = key_from_keysym(keysym,) {
+                            self.keys.set(key, event.kind() == KEY_PRESS,);
+                        }
+                    }
+                    BUTTON_PRESS | BUTTON_RELEASE => {
+                        let input = &*(&event as *const XEvent as *const XInputEvent);
+                        let pressed = event.kind() == BUTTON_PRESS;
+                        match input.detail {
+                            1 => self.mouse_left = pressed,
+                            3 => self.mouse_right = pressed,
+                            _ => {}
+                        }
+                    }
+                    MOTION_NOTIFY => {
+                        let input = &*(&event as *const XEvent as *const XInputEvent);
+                        self.mouse_x = input.x as f32;
+                        self.mouse_y = input.y as f32;
+                    }
+                    CLIENT_MESSAGE => {
+                        let message =
+                            &*(&event as *const XEvent as *const XClientMessageEvent);
+                        if message.data[0] as Atom == self.delete_message {
+                            self.closed = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Keys and mouse as of the last `poll`. The action map turns the keys into actions.
+    pub fn input(&self) -> GameInput {
+        let mut input = GameInput::new();
+        input.keys = self.keys;
+        input.mouse_x = self.mouse_x;
+        input.mouse_y = self.mouse_y;
+        input.mouse_left = self.mouse_left;
+        input.mouse_right = self.mouse_right;
+        input
+    }
+
+    /// Shows `canvas`, which has row 0 at the bottom.
+    pub fn present(&mut self, canvas: &Canvas) {
+        let width = self.width.min(canvas.width(),) as usize;
+        let height = self.height.min(canvas.height(),) as usize;
+        let source = canvas.pixels();
+        for y in 0..height {
+            let from = (canvas.height() as usize - 1 - y) * canvas.width() as usize;
+            let to = y * self.width as usize;
+            for x in 0..width {
+                self.pixels[to + x] = source[from + x] & 0x00ff_ffff;
+            }
+        }
+        unsafe {
+            (self.xlib.XPutImage)(
+                self.display,
+                self.window,
+                self.gc,
+                self.image,
+                0,
+                0,
+                0,
+                0,
+                self.width as c_uint,
+                self.height as c_uint,
+            );
+            (self.xlib.XFlush)(self.display,);
+        }
+    }
+}
+
+fn key_from_keysym(keysym: KeySym) -> Option<Key,> {
+    use game_core::action_map::ALL_KEYS;
+    let key = match keysym {
+        // XK_a to XK_z and XK_0 to XK_9, in the same order as `ALL_KEYS`
+        0x61..=0x7a => ALL_KEYS[(keysym - 0x61) as usize],
+        0x30..=0x39 => ALL_KEYS[26 + (keysym - 0x30) as usize],
+        0xff52 => Key::Up,
+        0xff54 => Key::Down,
+        0xff51 => Key::Left,
+        0xff53 => Key::Right,
+        0x20 => Key::Space,
+        0xff1b => Key::Escape,
+        0xff0d => Key::Enter,
+        0xff09 => Key::Tab,
+        0xffe1 | 0xffe2 => Key::Shift,
+        0xffe3 | 0xffe4 => Key::Control,
+        _ => return None,
+    };
+    Some(key,)
+}
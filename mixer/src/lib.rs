@@ -0,0 +1,170 @@
+extern crate game_core;
+
+use game_core::sound::{Sound, SoundEvent};
+use synth::{Envelope, Sfx, Voice, Waveform};
+
+pub mod synth;
+pub mod wav;
+
+/// Sounds playing at once. When full the oldest one is cut off.
+pub const MAX_VOICES: usize = 16;
+
+/// Software mixer. Frontends pass it the game's sound events and pull PCM from it for their
+/// audio device.
+pub struct Mixer {
+    sample_rate: u32,
+    voices: Vec<Voice,>,
+    pub master_volume: f32,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Mixer {
+        Mixer {
+            sample_rate,
+            voices: Vec::new(),
+            master_volume: 0.5,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn play(&mut self, sfx: Sfx, pitch: f32, volume: f32,) {
+        if pitch.is_nan() || pitch <= 0.0 {
+            return;
+        }
+        if self.voices.len() >= MAX_VOICES {
+            self.voices.remove(0,);
+        }
+        self.voices.push(Voice::new(sfx, pitch, volume,),);
+    }
+
+    pub fn play_event(&mut self, event: &SoundEvent,) {
+        self.play(sfx_for(event.sound,), event.pitch, event.volume,);
+    }
+
+    /// Fills `out` with mono samples in -1.0..1.0. Finished voices are dropped.
+    pub fn render(&mut self, out: &mut [f32],) {
+        for sample in out.iter_mut() {
+            *sample = 0.0;
+        }
+        let sample_rate = self.sample_rate;
+        for voice in &mut self.voices {
+            voice.render_add(out, sample_rate,);
+        }
+        self.voices.retain(|v| !v.is_finished(),);
+        for sample in out.iter_mut() {
+            // soft clip, so many sounds at once get louder without wrapping around
+            *sample = (*sample * self.master_volume).tanh();
+        }
+    }
+}
+
+/// The synthesized version of each game sound.
+pub fn sfx_for(sound: Sound) -> Sfx {
+    match sound {
+        Sound::Shot => Sfx {
+            waveform: Waveform::Square { duty: 0.25, },
+            frequency: 880.0,
+            frequency_end: 440.0,
+            envelope: Envelope {
+                attack: 0.002,
+                decay: 0.03,
+                sustain: 0.4,
+                hold: 0.0,
+                release: 0.05,
+            },
+            volume: 0.6,
+        },
+        Sound::EnemyKilled => Sfx {
+            waveform: Waveform::Noise,
+            frequency: 4000.0,
+            frequency_end: 800.0,
+            envelope: Envelope {
+                attack: 0.001,
+                decay: 0.05,
+                sustain: 0.5,
+                hold: 0.05,
+                release: 0.15,
+            },
+            volume: 0.8,
+        },
+        Sound::PlayerHit => Sfx {
+            waveform: Waveform::Square { duty: 0.5, },
+            frequency: 220.0,
+            frequency_end: 55.0,
+            envelope: Envelope {
+                attack: 0.005,
+                decay: 0.1,
+                sustain: 0.7,
+                hold: 0.1,
+                release: 0.3,
+            },
+            volume: 1.0,
+        },
+    }
+}
+
+#[test]
+fn test_render_event_sounds_to_wav() {
+    let sounds = [
+        (Sound::Shot, "shot",),
+        (Sound::EnemyKilled, "enemy_killed",),
+        (Sound::PlayerHit, "player_hit",),
+    ];
+    for (sound, name,) in sounds.iter() {
+        let mut mixer = Mixer::new(44100,);
+        mixer.play_event(&SoundEvent::new(*sound, 1.0, 1.0,),);
+        let length = (sfx_for(*sound,).envelope.duration() * 44100.0) as usize + 100;
+        let mut samples = vec![0.0; length];
+        mixer.render(&mut samples,);
+
+        assert!(samples.iter().any(|s| s.abs() > 0.05,), "{} is silent", name);
+        assert!(samples.iter().all(|s| s.abs() <= 1.0,));
+        // the voice is done and nothing is left playing
+        assert_eq!(mixer.active_voices(), 0);
+        assert_eq!(samples[length - 1], 0.0);
+
+        let path = std::env::temp_dir().join(format!("mixer_{}.wav", name),);
+        wav::save_wav(path.to_str().unwrap(), &samples, 44100,).unwrap();
+        let size = std::fs::metadata(&path,).unwrap().len();
+        assert_eq!(size, 44 + length as u64 * 2);
+    }
+}
+
+#[test]
+fn test_mixing_is_repeatable() {
+    let render = || {
+        let mut mixer = Mixer::new(22050,);
+        mixer.play_event(&SoundEvent::new(Sound::EnemyKilled, 1.2, 0.7,),);
+        mixer.play_event(&SoundEvent::new(Sound::Shot, 0.8, 1.0,),);
+        let mut samples = vec![0.0; 4096];
+        mixer.render(&mut samples,);
+        samples
+    };
+    assert_eq!(render(), render());
+}
+
+#[test]
+fn test_pitch_shortens_sound() {
+    let mut mixer = Mixer::new(1000,);
+    mixer.play_event(&SoundEvent::new(Sound::PlayerHit, 2.0, 1.0,),);
+    let length = sfx_for(Sound::PlayerHit,).envelope.duration();
+    let mut samples = vec![0.0; (length * 1000.0 / 2.0) as usize + 2];
+    mixer.render(&mut samples,);
+    assert_eq!(mixer.active_voices(), 0);
+}
+
+#[test]
+fn test_voice_limit() {
+    let mut mixer = Mixer::new(44100,);
+    for _ in 0..MAX_VOICES + 5 {
+        mixer.play_event(&SoundEvent::new(Sound::Shot, 1.0, 1.0,),);
+    }
+    assert_eq!(mixer.active_voices(), MAX_VOICES);
+}
@@ -0,0 +1,151 @@
+/// Sound source of a sound effect.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Waveform {
+    /// `duty` is the part of each period the wave is high, 0.5 is a plain square.
+    Square { duty: f32, },
+    /// White noise, a new random level `frequency` times per second.
+    Noise,
+}
+
+/// Attack, decay, sustain, release. Times are in seconds, `sustain` is a level from 0 to 1.
+/// The sustain level is held for `hold` seconds before the release starts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub hold: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    pub fn duration(&self) -> f32 {
+        self.attack + self.decay + self.hold + self.release
+    }
+
+    /// Level at `t` seconds after the start.
+    pub fn level(&self, t: f32,) -> f32 {
+        if t < 0.0 {
+            return 0.0;
+        }
+        if t < self.attack {
+            return t / self.attack;
+        }
+        let t = t - self.attack;
+        if t < self.decay {
+            return 1.0 - (1.0 - self.sustain) * (t / self.decay);
+        }
+        let t = t - self.decay;
+        if t < self.hold {
+            return self.sustain;
+        }
+        let t = t - self.hold;
+        if t < self.release {
+            return self.sustain * (1.0 - t / self.release);
+        }
+        0.0
+    }
+}
+
+/// A synthesized sound effect. The frequency slides from `frequency` to `frequency_end` over
+/// the length of the envelope.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sfx {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub frequency_end: f32,
+    pub envelope: Envelope,
+    pub volume: f32,
+}
+
+/// One playing sound.
+pub struct Voice {
+    sfx: Sfx,
+    pitch: f32,
+    volume: f32,
+    time: f32,
+    phase: f32,
+    noise: u32,
+    noise_level: f32,
+}
+
+impl Voice {
+    pub fn new(sfx: Sfx, pitch: f32, volume: f32,) -> Voice {
+        Voice {
+            sfx,
+            pitch,
+            volume,
+            time: 0.0,
+            phase: 0.0,
+            noise: 0xACE1,
+            noise_level: 1.0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.sfx.envelope.duration() / self.pitch
+    }
+
+    /// Adds this voice to `out`, one sample per entry.
+    pub fn render_add(&mut self, out: &mut [f32], sample_rate: u32,) {
+        let dt = 1.0 / sample_rate as f32;
+        let length = self.sfx.envelope.duration();
+        for sample in out.iter_mut() {
+            if self.is_finished() {
+                return;
+            }
+            // pitch speeds the whole sound up, so time runs faster too
+            let t = self.time * self.pitch;
+            let progress = if length > 0.0 { t / length } else { 1.0 };
+            let frequency = (self.sfx.frequency
+                + (self.sfx.frequency_end - self.sfx.frequency) * progress)
+                * self.pitch;
+
+            self.phase += frequency * dt;
+            let wrapped = self.phase >= 1.0;
+            self.phase -= self.phase.floor();
+
+            let value = match self.sfx.waveform {
+                Waveform::Square { duty, } => {
+                    if self.phase < duty {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Waveform::Noise => {
+                    if wrapped {
+                        self.step_noise();
+                    }
+                    self.noise_level
+                }
+            };
+            *sample += value * self.sfx.envelope.level(t,) * self.sfx.volume * self.volume;
+            self.time += dt;
+        }
+    }
+
+    /// 15 bit linear feedback shift register, like the NES noise channel.
+    fn step_noise(&mut self) {
+        let bit = (self.noise ^ (self.noise >> 1)) & 1;
+        self.noise = (self.noise >> 1) | (bit << 14);
+        self.noise_level = if self.noise & 1 == 0 { 1.0 } else { -1.0 };
+    }
+}
+
+#[test]
+fn test_envelope() {
+    let env = Envelope {
+        attack: 0.1,
+        decay: 0.1,
+        sustain: 0.5,
+        hold: 0.2,
+        release: 0.2,
+    };
+    assert_eq!(env.level(0.0,), 0.0);
+    assert_eq!(env.level(0.1,), 1.0);
+    assert!((env.level(0.3,) - 0.5).abs() < 0.0001);
+    assert!(env.level(0.5,) < 0.5);
+    assert_eq!(env.level(0.7,), 0.0);
+    assert!((env.duration() - 0.6).abs() < 0.0001);
+}
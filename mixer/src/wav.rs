@@ -0,0 +1,49 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+/// Writes mono samples as a 16 bit PCM WAV file. Samples are clamped to -1.0..1.0.
+pub fn write_wav<W: Write,>(out: &mut W, samples: &[f32], sample_rate: u32,) -> io::Result<(),> {
+    let data_size = samples.len() as u32 * 2;
+    out.write_all(b"RIFF",)?;
+    out.write_all(&(36 + data_size).to_le_bytes(),)?;
+    out.write_all(b"WAVE",)?;
+
+    out.write_all(b"fmt ",)?;
+    out.write_all(&16u32.to_le_bytes(),)?;
+    out.write_all(&1u16.to_le_bytes(),)?; // PCM
+    out.write_all(&1u16.to_le_bytes(),)?; // mono
+    out.write_all(&sample_rate.to_le_bytes(),)?;
+    out.write_all(&(sample_rate * 2).to_le_bytes(),)?; // bytes per second
+    out.write_all(&2u16.to_le_bytes(),)?; // bytes per frame
+    out.write_all(&16u16.to_le_bytes(),)?; // bits per sample
+
+    out.write_all(b"data",)?;
+    out.write_all(&data_size.to_le_bytes(),)?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0,) * 32767.0) as i16;
+        out.write_all(&value.to_le_bytes(),)?;
+    }
+    Ok((),)
+}
+
+pub fn save_wav(path: &str, samples: &[f32], sample_rate: u32,) -> io::Result<(),> {
+    let mut out = BufWriter::new(File::create(path,)?,);
+    write_wav(&mut out, samples, sample_rate,)?;
+    out.flush()
+}
+
+#[test]
+fn test_header() {
+    let mut bytes = Vec::new();
+    write_wav(&mut bytes, &[0.0, 1.0, -1.0, 2.0], 44100,).unwrap();
+    assert_eq!(bytes.len(), 44 + 8);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(&bytes[8..12], b"WAVE");
+    assert_eq!(&bytes[44..46], &[0, 0]);
+    assert_eq!(&bytes[46..48], &32767i16.to_le_bytes());
+    assert_eq!(&bytes[48..50], &(-32767i16).to_le_bytes());
+    // clamped
+    assert_eq!(&bytes[50..52], &32767i16.to_le_bytes());
+}
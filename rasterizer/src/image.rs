@@ -0,0 +1,98 @@
+use png::{self, HasParameters};
+use std::{fmt, fs::File, io::BufWriter};
+use {Canvas, Texture};
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(String,),
+    /// Not a PNG, or a kind of PNG we do not read.
+    Format(String,),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter,) -> fmt::Result {
+        match self {
+            ImageError::Io(message,) => write!(f, "could not open image: {}", message),
+            ImageError::Format(message,) => write!(f, "bad image: {}", message),
+        }
+    }
+}
+
+/// 8 bit RGBA pixels, rows from the top down.
+pub struct Rgba8 {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8,>,
+}
+
+impl Rgba8 {
+    /// The canvas as an opaque image, turned so the top row comes first.
+    pub fn from_canvas(canvas: &Canvas) -> Rgba8 {
+        let width = canvas.width().max(0,) as u32;
+        let height = canvas.height().max(0,) as u32;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize,);
+        for y in (0..canvas.height()).rev() {
+            for x in 0..canvas.width() {
+                let pixel = canvas.get_pixel(x, y,);
+                let rgba = [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 255];
+                pixels.extend_from_slice(&rgba,);
+            }
+        }
+        Rgba8 {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Reads an 8 bit RGB or RGBA PNG. RGB images get full alpha.
+pub fn load_png(path: &str) -> Result<Rgba8, ImageError,> {
+    let file = File::open(path,).map_err(|e| ImageError::Io(format!("{}: {}", path, e),),)?;
+    let decoder = png::Decoder::new(file,);
+    let (info, mut reader,) = decoder
+        .read_info()
+        .map_err(|e| ImageError::Format(format!("{}: {}", path, e),),)?;
+    let mut buffer = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut buffer,)
+        .map_err(|e| ImageError::Format(format!("{}: {}", path, e),),)?;
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err(ImageError::Format(format!("{}: only 8 bit images are supported", path),),);
+    }
+    let pixels = match info.color_type {
+        png::ColorType::RGBA => buffer,
+        png::ColorType::RGB => buffer
+            .chunks(3,)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255],)
+            .collect(),
+        other => {
+            return Err(ImageError::Format(format!(
+                "{}: {:?} images are not supported",
+                path, other
+            ),),)
+        }
+    };
+    Ok(Rgba8 {
+        width: info.width,
+        height: info.height,
+        pixels,
+    },)
+}
+
+pub fn load_texture(path: &str) -> Result<Texture, ImageError,> {
+    let image = load_png(path,)?;
+    Ok(Texture::from_rgba8(image.width, image.height, &image.pixels,),)
+}
+
+pub fn save_png(path: &str, image: &Rgba8,) -> Result<(), ImageError,> {
+    let file = File::create(path,).map_err(|e| ImageError::Io(format!("{}: {}", path, e),),)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file,), image.width, image.height,);
+    encoder.set(png::ColorType::RGBA,).set(png::BitDepth::Eight,);
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| ImageError::Io(format!("{}: {}", path, e),),)?;
+    writer
+        .write_image_data(&image.pixels,)
+        .map_err(|e| ImageError::Io(format!("{}: {}", path, e),),)
+}
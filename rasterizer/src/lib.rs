@@ -0,0 +1,522 @@
+extern crate game_core;
+#[cfg(feature = "png")]
+extern crate png;
+
+use game_core::{
+    entities::{BoundingBox, Color},
+    math::vector::Vector2d,
+    sprite::{Flip, SourceRect},
+};
+
+use std::mem;
+
+#[cfg(feature = "png")]
+pub mod image;
+
+/// Packs a colour as 0xAARRGGBB, the layout of a 32 bit Windows DIB.
+pub fn pack_color(color: &Color) -> u32 {
+    let alpha = ((color.a * 255.0) as u8) as u32;
+    let red = ((color.r * 255.0) as u8) as u32;
+    let green = ((color.g * 255.0) as u8) as u32;
+    let blue = ((color.b * 255.0) as u8) as u32;
+    alpha << 24 | red << 16 | green << 8 | blue
+}
+
+/// An image to draw sprites from. Row 0 is the top of the image, like in a PNG.
+pub struct Texture {
+    width: u32,
+    height: u32,
+    /// 0xAARRGGBB
+    pixels: Vec<u32,>,
+}
+
+impl Texture {
+    /// `rgba` holds 4 bytes per pixel, rows from the top down.
+    pub fn from_rgba8(width: u32, height: u32, rgba: &[u8],) -> Texture {
+        assert_eq!(rgba.len(), (width * height * 4) as usize);
+        let pixels = rgba
+            .chunks(4,)
+            .map(|p| (p[3] as u32) << 24 | (p[0] as u32) << 16 | (p[1] as u32) << 8 | p[2] as u32,)
+            .collect();
+        Texture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32,) -> u32 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EdgeMode {
+    /// Pixels on an edge are blended by how much of them the shape covers, so shapes can move
+    /// and grow by less than a pixel.
+    AntiAliased,
+    /// Edges are rounded to the nearest pixel boundary and every pixel is fully in or out.
+    PixelSnapped,
+}
+
+/// How much of the pixel column (or row) starting at `pixel` lies between `min` and `max`,
+/// from 0.0 to 1.0.
+pub fn coverage(min: f32, max: f32, pixel: i32,) -> f32 {
+    let start = pixel as f32;
+    (max.min(start + 1.0,) - min.max(start,)).clamp(0.0, 1.0,)
+}
+
+/// Software render target. Row 0 is the bottom of the screen, so game coordinates with y up map
+/// straight to rows.
+pub struct Canvas {
+    width: i32,
+    height: i32,
+    /// 0xAARRGGBB
+    pixels: Vec<u32,>,
+    edge_mode: EdgeMode,
+}
+
+impl Canvas {
+    pub fn new(width: i32, height: i32,) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; (width * height) as usize],
+            edge_mode: EdgeMode::AntiAliased,
+        }
+    }
+
+    pub fn edge_mode(&self) -> EdgeMode {
+        self.edge_mode
+    }
+
+    pub fn set_edge_mode(&mut self, edge_mode: EdgeMode,) {
+        self.edge_mode = edge_mode;
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn get_pixel(&self, x: i32, y: i32,) -> u32 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = color;
+        }
+    }
+
+    /// Rectangle edges after applying the edge mode.
+    fn snap(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32,) -> (f32, f32, f32, f32,) {
+        match self.edge_mode {
+            EdgeMode::AntiAliased => (min_x, min_y, max_x, max_y,),
+            EdgeMode::PixelSnapped => (min_x.round(), min_y.round(), max_x.round(), max_y.round(),),
+        }
+    }
+
+    /// Pixels touched by a rectangle, clipped to the canvas.
+    fn clip(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32,) -> (i32, i32, i32, i32,) {
+        let start_x = (min_x.floor() as i32).max(0,);
+        let start_y = (min_y.floor() as i32).max(0,);
+        let end_x = (max_x.ceil() as i32).min(self.width,);
+        let end_y = (max_y.ceil() as i32).min(self.height,);
+        (start_x, start_y, end_x, end_y,)
+    }
+
+    pub fn draw_rectangle(
+        &mut self,
+        min_x: f32,
+        min_y: f32,
+        max_x: f32,
+        max_y: f32,
+        color: Color,
+    ) {
+        let rgb = pack_color(&color,) & 0x00ffffff;
+        let (min_x, min_y, max_x, max_y,) = self.snap(min_x, min_y, max_x, max_y,);
+        let (start_x, start_y, end_x, end_y,) = self.clip(min_x, min_y, max_x, max_y,);
+        for y in start_y..end_y {
+            let row = (y * self.width) as usize;
+            let row_alpha = color.a * coverage(min_y, max_y, y,);
+            for x in start_x..end_x {
+                let alpha = (row_alpha * coverage(min_x, max_x, x,) * 255.0) as u32;
+                let pixel = &mut self.pixels[row + x as usize];
+                *pixel = blend(*pixel, alpha.min(255,) << 24 | rgb,);
+            }
+        }
+    }
+
+    /// Fills a convex polygon, corners in order around it either way. Anti-aliased edges get
+    /// the exact area of each pixel inside the polygon, pixel-snapped ones fill the pixels
+    /// whose center is inside.
+    pub fn fill_convex(&mut self, corners: &[Vector2d], color: Color,) {
+        if corners.len() < 3 || corners.iter().any(|c| !c.x.is_finite() || !c.y.is_finite(),) {
+            return;
+        }
+        let mut min = corners[0];
+        let mut max = corners[0];
+        for c in corners {
+            min = Vector2d::new(min.x.min(c.x,), min.y.min(c.y,),);
+            max = Vector2d::new(max.x.max(c.x,), max.y.max(c.y,),);
+        }
+        // make the corners go counter clockwise so "inside" is left of every edge
+        let mut points: Vec<Vector2d,> = corners.to_vec();
+        if polygon_area(&points,) < 0.0 {
+            points.reverse();
+        }
+
+        let rgb = pack_color(&color,) & 0x00ffffff;
+        let (start_x, start_y, end_x, end_y,) = self.clip(min.x, min.y, max.x, max.y,);
+        let mut scratch = (Vec::new(), Vec::new(),);
+        for y in start_y..end_y {
+            let row = (y * self.width) as usize;
+            for x in start_x..end_x {
+                let covered = match self.edge_mode {
+                    EdgeMode::AntiAliased => pixel_coverage(&points, x, y, &mut scratch,),
+                    EdgeMode::PixelSnapped => {
+                        let center = Vector2d::new(x as f32 + 0.5, y as f32 + 0.5,);
+                        if inside(&points, center,) {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    }
+                };
+                let alpha = (color.a * covered * 255.0) as u32;
+                if alpha == 0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[row + x as usize];
+                *pixel = blend(*pixel, alpha.min(255,) << 24 | rgb,);
+            }
+        }
+    }
+
+    /// Stretches `source` of `texture` over `dest` with nearest neighbour sampling and
+    /// multiplies it by `tint`. Edges follow the edge mode like rectangles do.
+    pub fn draw_sprite(
+        &mut self,
+        texture: &Texture,
+        source: SourceRect,
+        dest: &BoundingBox,
+        tint: Color,
+        flip: Flip,
+    ) {
+        let (min_x, min_y, max_x, max_y,) =
+            self.snap(dest.left, dest.bottom, dest.right, dest.top,);
+        let dest_width = max_x - min_x;
+        let dest_height = max_y - min_y;
+        if dest_width <= 0.0 || dest_height <= 0.0 || source.width == 0 || source.height == 0 {
+            return;
+        }
+        let (start_x, start_y, end_x, end_y,) = self.clip(min_x, min_y, max_x, max_y,);
+        for y in start_y..end_y {
+            // texture rows go down, canvas rows go up
+            let mut v = (max_y - (y as f32 + 0.5)) / dest_height;
+            if flip.vertical {
+                v = 1.0 - v;
+            }
+            let texel_y = source.y + ((v * source.height as f32) as u32).min(source.height - 1,);
+            let row = (y * self.width) as usize;
+            let row_coverage = coverage(min_y, max_y, y,);
+            for x in start_x..end_x {
+                let mut u = (x as f32 + 0.5 - min_x) / dest_width;
+                if flip.horizontal {
+                    u = 1.0 - u;
+                }
+                let texel_x = source.x + ((u * source.width as f32) as u32).min(source.width - 1,);
+                let texel = texture.get_pixel(texel_x, texel_y,);
+                let edge = Color {
+                    a: tint.a * row_coverage * coverage(min_x, max_x, x,),
+                    ..tint
+                };
+                let pixel = &mut self.pixels[row + x as usize];
+                *pixel = blend(*pixel, modulate(texel, &edge,),);
+            }
+        }
+    }
+}
+
+/// Twice the signed area, positive when the corners go counter clockwise.
+fn polygon_area(points: &[Vector2d]) -> f32 {
+    let mut area = 0.0;
+    for (i, a,) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.perp_dot(&b,);
+    }
+    area
+}
+
+/// For counter clockwise convex polygons.
+fn inside(points: &[Vector2d], p: Vector2d,) -> bool {
+    points.iter().enumerate().all(|(i, a,)| {
+        let b = points[(i + 1) % points.len()];
+        (b - *a).perp_dot(&(p - *a),) >= 0.0
+    },)
+}
+
+/// Part of the pixel at `x`, `y` covered by a counter clockwise convex polygon, found by
+/// clipping the polygon to the pixel square.
+fn pixel_coverage(
+    points: &[Vector2d],
+    x: i32,
+    y: i32,
+    scratch: &mut (Vec<Vector2d,>, Vec<Vector2d,>,),
+) -> f32 {
+    let (x0, y0,) = (x as f32, y as f32,);
+    let corners = [
+        Vector2d::new(x0, y0,),
+        Vector2d::new(x0 + 1.0, y0,),
+        Vector2d::new(x0 + 1.0, y0 + 1.0,),
+        Vector2d::new(x0, y0 + 1.0,),
+    ];
+    if corners.iter().all(|c| inside(points, *c,),) {
+        return 1.0;
+    }
+    let (ref mut current, ref mut next,) = *scratch;
+    current.clear();
+    current.extend_from_slice(points,);
+    // keep the part on the inside of each pixel edge: x >= x0, x <= x0 + 1, y >= y0, y <= y0 + 1
+    let planes: [(Vector2d, f32,); 4] = [
+        (Vector2d::new(1.0, 0.0,), x0,),
+        (Vector2d::new(-1.0, 0.0,), -(x0 + 1.0),),
+        (Vector2d::new(0.0, 1.0,), y0,),
+        (Vector2d::new(0.0, -1.0,), -(y0 + 1.0),),
+    ];
+    for (normal, offset,) in planes.iter() {
+        next.clear();
+        for (i, a,) in current.iter().enumerate() {
+            let b = current[(i + 1) % current.len()];
+            let da = normal.dot(a,) - offset;
+            let db = normal.dot(&b,) - offset;
+            if da >= 0.0 {
+                next.push(*a,);
+            }
+            if (da >= 0.0) != (db >= 0.0) {
+                next.push(a.lerp(&b, da / (da - db),),);
+            }
+        }
+        mem::swap(current, next,);
+        if current.len() < 3 {
+            return 0.0;
+        }
+    }
+    (polygon_area(current,) / 2.0).clamp(0.0, 1.0,)
+}
+
+/// Source over blending of two 0xAARRGGBB colours with straight, not premultiplied, alpha.
+pub fn blend(dst: u32, src: u32,) -> u32 {
+    let src_alpha = src >> 24;
+    match src_alpha {
+        0 => return dst,
+        255 => return src,
+        _ => {}
+    }
+    let dst_alpha = dst >> 24;
+    // alpha of the result, scaled by 255
+    let out_alpha = src_alpha * 255 + dst_alpha * (255 - src_alpha);
+    if out_alpha == 0 {
+        return 0;
+    }
+    let channel = |shift: u32| {
+        let s = (src >> shift) & 0xff;
+        let d = (dst >> shift) & 0xff;
+        let value = (s * src_alpha * 255 + d * dst_alpha * (255 - src_alpha) + out_alpha / 2)
+            / out_alpha;
+        value << shift
+    };
+    ((out_alpha + 127) / 255) << 24 | channel(16,) | channel(8,) | channel(0,)
+}
+
+/// Multiplies each channel of a packed texel with the tint.
+fn modulate(texel: u32, tint: &Color,) -> u32 {
+    let channel = |shift: u32, factor: f32| {
+        let value = ((texel >> shift) & 0xff) as f32 * factor.clamp(0.0, 1.0,);
+        (value as u32) << shift
+    };
+    channel(24, tint.a,) | channel(16, tint.r,) | channel(8, tint.g,) | channel(0, tint.b,)
+}
+
+#[cfg(test)]
+fn full_canvas() -> BoundingBox {
+    BoundingBox {
+        left: 0.0,
+        right: 4.0,
+        top: 4.0,
+        bottom: 0.0,
+    }
+}
+
+#[cfg(test)]
+fn white() -> Color {
+    Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    }
+}
+
+#[test]
+fn test_draw_rectangle_clips() {
+    let mut canvas = Canvas::new(4, 4,);
+    canvas.draw_rectangle(-2.0, 1.0, 2.0, 10.0, white(),);
+    assert_eq!(canvas.get_pixel(0, 0,), 0);
+    assert_eq!(canvas.get_pixel(1, 1,), 0xffffffff);
+    assert_eq!(canvas.get_pixel(1, 3,), 0xffffffff);
+    assert_eq!(canvas.get_pixel(2, 1,), 0);
+}
+
+#[test]
+fn test_draw_sprite() {
+    // 2x2 texture: red green / blue transparent
+    let texture = Texture::from_rgba8(
+        2,
+        2,
+        &[255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 0, 0, 0, 0],
+    );
+    let source = SourceRect {
+        x: 0,
+        y: 0,
+        width: 2,
+        height: 2,
+    };
+    let mut canvas = Canvas::new(4, 4,);
+    canvas.draw_sprite(&texture, source, &full_canvas(), white(), Flip::default(),);
+    // top of the texture is at the top of the canvas
+    assert_eq!(canvas.get_pixel(0, 3,), 0xffff0000);
+    assert_eq!(canvas.get_pixel(3, 2,), 0xff00ff00);
+    assert_eq!(canvas.get_pixel(1, 0,), 0xff0000ff);
+    assert_eq!(canvas.get_pixel(3, 0,), 0);
+
+    let flip = Flip {
+        horizontal: true,
+        vertical: false,
+    };
+    let tint = Color {
+        r: 1.0,
+        g: 0.5,
+        b: 1.0,
+        a: 1.0,
+    };
+    canvas.clear(0,);
+    canvas.draw_sprite(&texture, source, &full_canvas(), tint, flip,);
+    assert_eq!(canvas.get_pixel(0, 3,), 0xff007f00);
+    assert_eq!(canvas.get_pixel(3, 3,), 0xffff0000);
+    assert_eq!(canvas.get_pixel(0, 0,), 0);
+}
+
+#[test]
+fn test_fill_convex() {
+    let alphas =
+        |canvas: &Canvas| -> Vec<u32,> { canvas.pixels().iter().map(|p| p >> 24,).collect() };
+    // a diamond touching the middle of each side of a 2x2 canvas covers half of every pixel
+    let diamond = [
+        Vector2d::new(1.0, 0.0,),
+        Vector2d::new(2.0, 1.0,),
+        Vector2d::new(1.0, 2.0,),
+        Vector2d::new(0.0, 1.0,),
+    ];
+    let mut canvas = Canvas::new(2, 2,);
+    canvas.fill_convex(&diamond, white(),);
+    assert_eq!(alphas(&canvas), vec![127, 127, 127, 127]);
+
+    // same shape wound the other way
+    let mut reversed = diamond;
+    reversed.reverse();
+    canvas.clear(0,);
+    canvas.fill_convex(&reversed, white(),);
+    assert_eq!(alphas(&canvas), vec![127, 127, 127, 127]);
+
+    // an axis aligned quad gives the same coverage as a rectangle
+    let mut quad_canvas = Canvas::new(3, 3,);
+    let quad = [
+        Vector2d::new(0.4, 0.4,),
+        Vector2d::new(1.6, 0.4,),
+        Vector2d::new(1.6, 1.6,),
+        Vector2d::new(0.4, 1.6,),
+    ];
+    quad_canvas.fill_convex(&quad, white(),);
+    let mut rect_canvas = Canvas::new(3, 3,);
+    rect_canvas.draw_rectangle(0.4, 0.4, 1.6, 1.6, white(),);
+    assert_eq!(quad_canvas.pixels(), rect_canvas.pixels());
+
+    // pixel snapped: only pixels with their center inside
+    let triangle = [
+        Vector2d::new(0.0, 0.0,),
+        Vector2d::new(2.0, 0.0,),
+        Vector2d::new(0.0, 2.0,),
+    ];
+    canvas.clear(0,);
+    canvas.set_edge_mode(EdgeMode::PixelSnapped,);
+    canvas.fill_convex(&triangle, white(),);
+    assert_eq!(alphas(&canvas), vec![255, 255, 255, 0]);
+}
+
+#[test]
+fn test_blend() {
+    // opaque over anything replaces it, transparent changes nothing
+    assert_eq!(blend(0xff123456, 0xffabcdef,), 0xffabcdef);
+    assert_eq!(blend(0xff123456, 0x00abcdef,), 0xff123456);
+    // half white over opaque black is opaque grey
+    assert_eq!(blend(0xff000000, 0x80ffffff,), 0xff808080);
+    // half red over nothing stays half red
+    assert_eq!(blend(0x00000000, 0x80ff0000,), 0x80ff0000);
+
+    let mut canvas = Canvas::new(2, 1,);
+    canvas.clear(0xff0000ff,);
+    let half_red = Color {
+        r: 1.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.5,
+    };
+    canvas.draw_rectangle(0.0, 0.0, 1.0, 1.0, half_red,);
+    assert_eq!(canvas.get_pixel(0, 0,), 0xff7f0080);
+    assert_eq!(canvas.get_pixel(1, 0,), 0xff0000ff);
+}
+
+#[test]
+fn test_edge_coverage() {
+    assert_eq!(coverage(0.25, 2.5, 0,), 0.75);
+    assert_eq!(coverage(0.25, 2.5, 1,), 1.0);
+    assert_eq!(coverage(0.25, 2.5, 2,), 0.5);
+    assert_eq!(coverage(0.25, 2.5, 3,), 0.0);
+    assert_eq!(coverage(1.2, 1.7, 1,), 0.5);
+
+    // white over nothing, so the alpha of each pixel is its coverage
+    let alphas =
+        |canvas: &Canvas| -> Vec<u32,> { canvas.pixels().iter().map(|p| p >> 24,).collect() };
+    let mut canvas = Canvas::new(3, 2,);
+    canvas.draw_rectangle(0.25, 0.5, 2.5, 1.0, white(),);
+    // coverage 0.375, 0.5 and 0.25 on the bottom row
+    assert_eq!(alphas(&canvas), vec![95, 127, 63, 0, 0, 0]);
+    assert_eq!(canvas.get_pixel(1, 0,) & 0xffffff, 0xffffff);
+
+    let mut canvas = Canvas::new(3, 3,);
+    canvas.draw_rectangle(0.4, 0.4, 1.6, 1.6, white(),);
+    // 0.6 * 0.6 in the corners, 0.6 on the sides
+    assert_eq!(alphas(&canvas), vec![91, 91, 0, 91, 91, 0, 0, 0, 0]);
+
+    canvas.clear(0,);
+    canvas.set_edge_mode(EdgeMode::PixelSnapped,);
+    canvas.draw_rectangle(0.4, 0.4, 1.6, 1.6, white(),);
+    assert_eq!(alphas(&canvas), vec![255, 255, 0, 255, 255, 0, 0, 0, 0]);
+}
@@ -1,18 +1,28 @@
 #![feature(use_extern_macros)]
 extern crate game_core;
+extern crate mixer;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
 extern crate wasm_bindgen;
 
-use game_core::{entities::Color, game_init, game_loop, GameInput};
+use game_core::{
+    action_map::{Key, KeyState},
+    entities::Color,
+    game_init_players, game_loop_players, math::vector::Vector2d, set_atlas,
+    sound::SoundEvent,
+    sprite::{Atlas, Flip, Sprite},
+    GameInput,
+};
+use mixer::Mixer;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(module = "./platform")]
 extern "C" {
     fn random() -> f32;
     fn log(s: String);
+    fn now() -> f64;
     fn start_frame();
     fn end_frame();
     fn draw_rectangle(
@@ -23,10 +33,43 @@ extern "C" {
         red: f32,
         green: f32,
         blue: f32,
+        alpha: f32,
+    );
+    #[wasm_bindgen(js_name = draw_sprite)]
+    fn draw_sprite_js(
+        atlas: u32,
+        source_x: u32,
+        source_y: u32,
+        source_width: u32,
+        source_height: u32,
+        min_x: f32,
+        min_y: f32,
+        max_x: f32,
+        max_y: f32,
+        alpha: f32,
+        flip_horizontal: bool,
+        flip_vertical: bool,
+    );
+    #[wasm_bindgen(js_name = draw_quad)]
+    fn draw_quad_js(
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        x3: f32,
+        y3: f32,
+        red: f32,
+        green: f32,
+        blue: f32,
+        alpha: f32,
     );
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Fields left out of the JSON default to not pressed / zero.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct PlayerInput {
     pub up_key: bool,
     pub down_key: bool,
@@ -38,10 +81,27 @@ pub struct PlayerInput {
     pub shoot_down: bool,
     pub quit_key: bool,
     pub space: bool,
+    pub debug_key: bool,
+    pub move_x: f32,
+    pub move_y: f32,
+    pub aim_x: f32,
+    pub aim_y: f32,
+    pub mouse_x: f32,
+    pub mouse_y: f32,
+    pub mouse_left: bool,
+    pub mouse_right: bool,
+    /// Names of the keys held down, see `Key::from_name`.
+    pub keys: Vec<String,>,
 }
 
 impl PlayerInput {
     fn to_game_input(&self) -> GameInput {
+        let mut keys = KeyState::new();
+        for name in &self.keys {
+            if let Some(key,) = Key::from_name(name,) {
+                keys.set(key, true,);
+            }
+        }
         GameInput {
             up_key: self.up_key,
             down_key: self.down_key,
@@ -53,30 +113,160 @@ impl PlayerInput {
             shoot_down: self.shoot_down,
             quit_key: self.quit_key,
             space: self.space,
+            debug_key: self.debug_key,
+            move_x: self.move_x,
+            move_y: self.move_y,
+            aim_x: self.aim_x,
+            aim_y: self.aim_y,
+            mouse_x: self.mouse_x,
+            mouse_y: self.mouse_y,
+            mouse_left: self.mouse_left,
+            mouse_right: self.mouse_right,
+            keys,
         }
     }
 }
 
 fn draw_rectangle_inner(min_x: f32, min_y: f32, max_x: f32, max_y: f32, color: Color,) {
-    draw_rectangle(min_x, min_y, max_x, max_y, color.r, color.g, color.b,);
+    draw_rectangle(min_x, min_y, max_x, max_y, color.r, color.g, color.b, color.a,);
+}
+
+/// The canvas can not multiply images by a colour cheaply, so only the tint alpha is used.
+fn draw_sprite(
+    sprite: Sprite,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+    tint: Color,
+    flip: Flip,
+) {
+    let s = sprite.source;
+    draw_sprite_js(
+        sprite.atlas,
+        s.x,
+        s.y,
+        s.width,
+        s.height,
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+        tint.a,
+        flip.horizontal,
+        flip.vertical,
+    );
+}
+
+fn draw_quad(corners: [Vector2d; 4], color: Color,) {
+    let c = corners;
+    draw_quad_js(
+        c[0].x, c[0].y, c[1].x, c[1].y, c[2].x, c[2].y, c[3].x, c[3].y, color.r, color.g,
+        color.b, color.a,
+    );
+}
+
+/// `metadata` is the text of an atlas file written by `atlas-packer`.
+/// The image itself is handed to `set_atlas_image` in platform.js.
+#[wasm_bindgen]
+pub fn load_atlas(atlas: u32, metadata: String,) -> bool {
+    match Atlas::parse(&metadata,) {
+        Ok(metadata,) => {
+            set_atlas(atlas, &metadata,);
+            true
+        }
+        Err(e,) => {
+            log(e.to_string(),);
+            false
+        }
+    }
+}
+
+/// `source` is the text of a Rhai script, see `game_core::script`.
+#[cfg(feature = "scripting")]
+#[wasm_bindgen]
+pub fn load_scripts(source: String) -> bool {
+    match game_core::script::load_scripts_from_str(&source,) {
+        Ok((),) => true,
+        Err(e,) => {
+            log(e.to_string(),);
+            false
+        }
+    }
+}
+
+/// `source` is the text of a tunables file, see `game_core::tunables`.
+#[wasm_bindgen]
+pub fn load_tunables(source: String) -> bool {
+    match game_core::tunables::load_tunables_from_str(&source,) {
+        Ok((),) => true,
+        Err(e,) => {
+            log(e.to_string(),);
+            false
+        }
+    }
+}
+
+/// Timings so far as Chrome trace JSON, for chrome://tracing or Perfetto.
+#[cfg(feature = "profiling")]
+#[wasm_bindgen]
+pub fn chrome_trace() -> String {
+    game_core::profile::chrome_trace()
+}
+
+static mut MIXER: Option<Mixer,> = None;
+
+fn play_sound(event: SoundEvent) {
+    unsafe {
+        if let Some(ref mut mixer,) = MIXER {
+            mixer.play_event(&event,);
+        }
+    }
+}
+
+/// Sounds are dropped until this is called, browsers only allow audio after a user gesture.
+#[wasm_bindgen]
+pub fn init_audio(sample_rate: u32) {
+    unsafe {
+        MIXER = Some(Mixer::new(sample_rate,),);
+    }
+}
+
+/// Next `length` mono samples for the audio output.
+#[wasm_bindgen]
+pub fn render_audio(length: usize) -> Vec<f32,> {
+    let mut samples = vec![0.0; length];
+    unsafe {
+        if let Some(ref mut mixer,) = MIXER {
+            mixer.render(&mut samples,);
+        }
+    }
+    samples
 }
 
 #[wasm_bindgen]
-pub fn init() {
+pub fn init(player_count: usize) {
     let platform = game_core::Platform {
         random,
         log,
         start_frame,
         end_frame,
         draw_rectangle: draw_rectangle_inner,
+        play_sound,
+        draw_sprite,
+        draw_quad,
+        now,
     };
-    game_init(960.0, 540.0, platform,);
+    game_init_players(960.0, 540.0, player_count, platform,);
 }
 
+/// `input_string` is a JSON array with one `PlayerInput` per player slot.
+/// Returns true when the player quit from the title screen.
 #[wasm_bindgen]
-pub fn update(input_string: String, time_elapsed: f32, delta: f32,) {
-    let input: PlayerInput = serde_json::from_str(&input_string,).unwrap();
-    game_loop(input.to_game_input(), time_elapsed, delta,);
+pub fn update(input_string: String, time_elapsed: f32, delta: f32,) -> bool {
+    let inputs: Vec<PlayerInput,> = serde_json::from_str(&input_string,).unwrap();
+    let inputs = inputs.iter().map(|i| i.to_game_input(),).collect();
+    game_loop_players(inputs, time_elapsed, delta,).should_quit()
 }
 
 #[test]
@@ -87,7 +277,7 @@ fn test_loop() {
     let p = Platform {
         random: test_random,
     };
-    game_init(960.0, 540.0, p,);
+    game_core::game_init(960.0, 540.0, p,);
     let g = GameInput {
         up_key: false,
         down_key: false,
@@ -99,7 +289,17 @@ fn test_loop() {
         shoot_down: false,
         quit_key: false,
         space: false,
+        debug_key: false,
+        move_x: 0.0,
+        move_y: 0.0,
+        aim_x: 0.0,
+        aim_y: 0.0,
+        mouse_x: 0.0,
+        mouse_y: 0.0,
+        mouse_left: false,
+        mouse_right: false,
+        keys: KeyState::new(),
     };
-    let ret = game_loop(g, 0.1, 0.1,);
+    let ret = game_core::game_loop(g, 0.1, 0.1,);
     println!("{:?}", ret);
 }
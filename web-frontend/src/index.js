@@ -1,66 +1,141 @@
 const js = import("./wasm/wasm_lib.js");
+import {set_atlas_image} from "./wasm/platform.js";
 
-let input = {
-    up_key: false,
-    left_key: false,
-    right_key: false,
-    down_key: false,
-    shoot_down: false,
-    shoot_up: false,
-    shoot_left: false,
-    shoot_right: false,
-    quit_key: false,
-    space: false,
+// index.html?players=2 for local multiplayer
+const playerCount = Math.max(1, parseInt(new URLSearchParams(window.location.search).get('players')) || 1);
+
+// only the first player gets the mouse
+let mouse = {
+    mouse_x: 0,
+    mouse_y: 0,
+    mouse_left: false,
+    mouse_right: false,
+};
+
+let pressedKeys = [];
+
+// names understood by game-core's Key::from_name
+const specialKeys = {
+    ' ': 'space',
+    'ArrowUp': 'up',
+    'ArrowDown': 'down',
+    'ArrowLeft': 'left',
+    'ArrowRight': 'right',
+    'Escape': 'escape',
+    'Enter': 'enter',
+    'Tab': 'tab',
+    'Shift': 'shift',
+    'Control': 'control',
 };
 
+const keyName = key => specialKeys[key] || key.toLowerCase();
+
 let update;
 let startTime = 0;
 let lastFrameTime = 0;
 
+// browsers only start audio after a user gesture, so this runs on the first key or click
+let audio = null;
+const startAudio = js => {
+    if (audio) return;
+    audio = new (window.AudioContext || window.webkitAudioContext)();
+    js.init_audio(audio.sampleRate);
+    const node = audio.createScriptProcessor(1024, 0, 1);
+    node.onaudioprocess = event => {
+        const out = event.outputBuffer.getChannelData(0);
+        out.set(js.render_audio(out.length));
+    };
+    node.connect(audio.destination);
+};
+
 
 js.then(js => {
     update = js.update;
+    // only raw key state is reported, game-core maps keys to actions
     document.addEventListener('keydown', event => {
-
-        if (event.key === 'w') input.up_key = true;
-        if (event.key === 'a') input.left_key = true;
-        if (event.key === 's') input.down_key = true;
-        if (event.key === 'd') input.right_key = true;
-        if (event.key === 'i') input.shoot_up = true;
-        if (event.key === 'j') input.shoot_left = true;
-        if (event.key === 'k') input.shoot_down = true;
-        if (event.key === 'l') input.shoot_right = true;
-        if (event.key === 'q') input.quit_key = true;
-        if (event.key === ' ') input.space = true;
-        //  console.log(input);
+        startAudio(js);
+        const name = keyName(event.key);
+        if (!pressedKeys.includes(name)) pressedKeys.push(name);
     })
 
     document.addEventListener('keyup', event => {
-        if (event.key === 'w') input.up_key = false;
-        if (event.key === 'a') input.left_key = false;
-        if (event.key === 's') input.down_key = false;
-        if (event.key === 'd') input.right_key = false;
-        if (event.key === 'i') input.shoot_up = false;
-        if (event.key === 'j') input.shoot_left = false;
-        if (event.key === 'l') input.shoot_right = false;
-        if (event.key === 'k') input.shoot_down = false;
-        if (event.key === 'q') input.quit_key = false;
-        if (event.key === ' ') input.space = false;
+        const name = keyName(event.key);
+        pressedKeys = pressedKeys.filter(k => k !== name);
     })
 
-    js.init();
+    // mouse position is sent in canvas pixels, game-core does the conversion to world space
+    const canvas = document.getElementById('canvas');
+    canvas.addEventListener('mousemove', event => {
+        const rect = canvas.getBoundingClientRect();
+        mouse.mouse_x = (event.clientX - rect.left) * canvas.width / rect.width;
+        mouse.mouse_y = (event.clientY - rect.top) * canvas.height / rect.height;
+    })
+
+    canvas.addEventListener('mousedown', event => {
+        startAudio(js);
+        if (event.button === 0) mouse.mouse_left = true;
+        if (event.button === 2) mouse.mouse_right = true;
+    })
+
+    document.addEventListener('mouseup', event => {
+        if (event.button === 0) mouse.mouse_left = false;
+        if (event.button === 2) mouse.mouse_right = false;
+    })
+
+    canvas.addEventListener('contextmenu', event => event.preventDefault())
+
+    js.init(playerCount);
+    loadAtlas(js, 0, 'assets/atlas.txt');
     startTime = performance.now();
     requestAnimationFrame(mainLoop);
 });
 
+// the game draws plain boxes until the atlas has loaded, or if there is none
+const loadAtlas = (js, atlas, metadataUrl) => {
+    fetch(metadataUrl)
+        .then(response => response.ok ? response.text() : Promise.reject(response.status))
+        .then(metadata => {
+            const texture = metadata.match(/^texture = (.*)$/m);
+            if (!texture) return;
+            const image = new Image();
+            image.onload = () => {
+                set_atlas_image(atlas, image);
+                js.load_atlas(atlas, metadata);
+            };
+            image.src = metadataUrl.replace(/[^/]*$/, '') + texture[1].trim();
+        })
+        .catch(() => console.log(`No sprite atlas at ${metadataUrl}`));
+};
+
 let frameCounter = 0;
 
+// Standard gamepad mapping: axes 0/1 left stick, 2/3 right stick. Browser y points down.
+const readGamepad = (input, slot) => {
+    if (!navigator.getGamepads) return;
+    const pad = navigator.getGamepads()[slot];
+    if (!pad || pad.axes.length < 4) return;
+    input.move_x = pad.axes[0];
+    input.move_y = -pad.axes[1];
+    input.aim_x = pad.axes[2];
+    input.aim_y = -pad.axes[3];
+};
+
+// every slot sees the whole keyboard, the action map of the slot decides which keys it uses
+const readInput = slot => {
+    let input = {keys: pressedKeys};
+    if (slot === 0) Object.assign(input, mouse);
+    readGamepad(input, slot);
+    return input;
+};
+
 const mainLoop = () => {
     let currentTime = performance.now();
     let elapsedTime = (currentTime - startTime) / 1000;
     let delta = (currentTime - lastFrameTime) /1000;
     lastFrameTime = currentTime;
-    update(JSON.stringify(input), elapsedTime, delta);
+    let inputs = [];
+    for (let slot = 0; slot < playerCount; slot++) inputs.push(readInput(slot));
+    update(JSON.stringify(inputs), elapsedTime, delta);
     requestAnimationFrame(mainLoop);
     frameCounter++;
 };
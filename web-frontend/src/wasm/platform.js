@@ -6,6 +6,11 @@ export const log = s => {
     console.log(`FROM RUST: ${s}`);
 }
 
+// seconds, for the profiler
+export const now = () => {
+    return performance.now() / 1000;
+}
+
 let ctx = null;
 
 export const start_frame = () => {
@@ -17,12 +22,46 @@ export const start_frame = () => {
 export const end_frame = () => {
 }
 
-export const draw_rectangle = (min_x, min_y, max_x, max_y, red, green, blue) => {
+export const draw_rectangle = (min_x, min_y, max_x, max_y, red, green, blue, alpha) => {
     let width = max_x - min_x;
     let height = max_y - min_y;
     let left = min_x;
     let top = 540 - max_y;
-    ctx.fillStyle = 'rgb(' + Math.floor(red * 255) + ',' + Math.floor(green * 255) + ',' + Math.floor(blue * 255) + ')';
+    // the canvas blends source over by default
+    ctx.fillStyle = 'rgba(' + Math.floor(red * 255) + ',' + Math.floor(green * 255) + ',' + Math.floor(blue * 255) + ',' + alpha + ')';
     ctx.fillRect(left, top, width, height);
 
 }
+
+// atlas id -> loaded Image
+const atlasImages = [];
+
+export const set_atlas_image = (atlas, image) => {
+    atlasImages[atlas] = image;
+}
+
+export const draw_sprite = (atlas, source_x, source_y, source_width, source_height,
+                            min_x, min_y, max_x, max_y, alpha, flip_horizontal, flip_vertical) => {
+    const image = atlasImages[atlas];
+    if (!image) return;
+    const width = max_x - min_x;
+    const height = max_y - min_y;
+    ctx.save();
+    ctx.globalAlpha = alpha;
+    ctx.translate(min_x + width / 2, 540 - max_y + height / 2);
+    ctx.scale(flip_horizontal ? -1 : 1, flip_vertical ? -1 : 1);
+    ctx.drawImage(image, source_x, source_y, source_width, source_height,
+        -width / 2, -height / 2, width, height);
+    ctx.restore();
+}
+
+export const draw_quad = (x0, y0, x1, y1, x2, y2, x3, y3, red, green, blue, alpha) => {
+    ctx.fillStyle = 'rgba(' + Math.floor(red * 255) + ',' + Math.floor(green * 255) + ',' + Math.floor(blue * 255) + ',' + alpha + ')';
+    ctx.beginPath();
+    ctx.moveTo(x0, 540 - y0);
+    ctx.lineTo(x1, 540 - y1);
+    ctx.lineTo(x2, 540 - y2);
+    ctx.lineTo(x3, 540 - y3);
+    ctx.closePath();
+    ctx.fill();
+}
@@ -0,0 +1,88 @@
+use mixer::Mixer;
+use std::{mem, ptr::null_mut};
+use winapi::{
+    shared::{
+        minwindef::DWORD,
+        mmreg::{WAVEFORMATEX, WAVE_FORMAT_PCM},
+    },
+    um::{
+        mmeapi::{waveOutOpen, waveOutPrepareHeader, waveOutWrite},
+        mmsystem::{CALLBACK_NULL, HWAVEOUT, MMSYSERR_NOERROR, WAVEHDR, WAVE_MAPPER, WHDR_DONE},
+    },
+};
+
+pub const SAMPLE_RATE: u32 = 44100;
+/// About 23 ms per buffer. With four of them queued sounds start at most ~90 ms late.
+const BUFFER_SAMPLES: usize = 1024;
+const BUFFER_COUNT: usize = 4;
+
+/// Plays a `Mixer` through waveOut. `update` must be called every frame to refill the buffers
+/// the device has finished with.
+pub struct AudioOutput {
+    device: HWAVEOUT,
+    // boxed so the headers and samples stay put while the device holds pointers to them
+    headers: Box<[WAVEHDR; BUFFER_COUNT],>,
+    buffers: Box<[[i16; BUFFER_SAMPLES]; BUFFER_COUNT],>,
+    scratch: Vec<f32,>,
+    pub mixer: Mixer,
+}
+
+impl AudioOutput {
+    /// None when there is no sound device.
+    pub fn open() -> Option<AudioOutput,> {
+        let format = WAVEFORMATEX {
+            wFormatTag: WAVE_FORMAT_PCM,
+            nChannels: 1,
+            nSamplesPerSec: SAMPLE_RATE,
+            nAvgBytesPerSec: SAMPLE_RATE * 2,
+            nBlockAlign: 2,
+            wBitsPerSample: 16,
+            cbSize: 0,
+        };
+        let mut device: HWAVEOUT = null_mut();
+        let result =
+            unsafe { waveOutOpen(&mut device, WAVE_MAPPER, &format, 0, 0, CALLBACK_NULL,) };
+        if result != MMSYSERR_NOERROR {
+            return None;
+        }
+
+        let mut output = AudioOutput {
+            device,
+            headers: Box::new(unsafe { mem::zeroed() },),
+            buffers: Box::new([[0; BUFFER_SAMPLES]; BUFFER_COUNT],),
+            scratch: vec![0.0; BUFFER_SAMPLES],
+            mixer: Mixer::new(SAMPLE_RATE,),
+        };
+        for i in 0..BUFFER_COUNT {
+            let header = &mut output.headers[i];
+            header.lpData = output.buffers[i].as_mut_ptr() as *mut i8;
+            header.dwBufferLength = (BUFFER_SAMPLES * 2) as DWORD;
+            unsafe {
+                waveOutPrepareHeader(device, header, mem::size_of::<WAVEHDR,>() as u32,);
+            }
+            // prepared buffers that were never written count as done, so they get filled first
+            header.dwFlags |= WHDR_DONE;
+        }
+        Some(output,)
+    }
+
+    pub fn update(&mut self) {
+        for i in 0..BUFFER_COUNT {
+            if self.headers[i].dwFlags & WHDR_DONE == 0 {
+                continue;
+            }
+            self.mixer.render(&mut self.scratch,);
+            for (out, sample,) in self.buffers[i].iter_mut().zip(self.scratch.iter(),) {
+                *out = (sample * 32767.0) as i16;
+            }
+            self.headers[i].dwFlags &= !WHDR_DONE;
+            unsafe {
+                waveOutWrite(
+                    self.device,
+                    &mut self.headers[i],
+                    mem::size_of::<WAVEHDR,>() as u32,
+                );
+            }
+        }
+    }
+}
@@ -3,7 +3,9 @@
 
 extern crate game_core;
 extern crate libc;
+extern crate mixer;
 extern crate rand;
+extern crate rasterizer;
 #[cfg(windows)]
 extern crate winapi;
 // https://docs.rs/winapi/*/x86_64-pc-windows-msvc/winapi/um/libloaderapi/index.html?search=winuser
@@ -11,20 +13,38 @@ extern crate winapi;
 use self::winapi::{
     shared::{
         minwindef::{LPARAM, LRESULT, UINT, WPARAM},
-        windef::HWND,
+        windef::{HWND, POINT, RECT},
     },
     um::{
         libloaderapi::GetModuleHandleW,
         wincon::GetConsoleWindow,
         winuser::{
-            CreateWindowExW, DefWindowProcW, DispatchMessageW, GetAsyncKeyState, PeekMessageW,
-            PostQuitMessage, RegisterClassW, ShowWindow, TranslateMessage, CS_HREDRAW, CS_OWNDC,
-            CS_VREDRAW, CW_USEDEFAULT, MSG, PM_REMOVE, SW_HIDE, VK_ESCAPE, VK_SPACE, WM_CREATE,
-            WM_DESTROY, WNDCLASSW, WS_OVERLAPPEDWINDOW, WS_VISIBLE,
+            CreateWindowExW, DefWindowProcW, DispatchMessageW, GetAsyncKeyState, GetClientRect,
+            GetCursorPos, PeekMessageW, PostQuitMessage, RegisterClassW, ScreenToClient,
+            ShowWindow, TranslateMessage, CS_HREDRAW, CS_OWNDC, CS_VREDRAW, CW_USEDEFAULT, MSG,
+            PM_REMOVE, SW_HIDE, VK_CONTROL, VK_DOWN, VK_ESCAPE, VK_LBUTTON, VK_LEFT, VK_RBUTTON,
+            VK_RETURN, VK_RIGHT, VK_SHIFT, VK_SPACE, VK_TAB, VK_UP, WM_CREATE, WM_DESTROY,
+            WNDCLASSW, WS_OVERLAPPEDWINDOW, WS_VISIBLE,
         },
+        xinput::{XInputGetState, XINPUT_STATE},
     },
 };
-use game_core::{entities::Color, game_init, game_loop, GameInput, Platform};
+use game_core::{
+    action_map::{Key, ALL_KEYS},
+    entities::Color,
+    flow::GameStatus,
+    game_init_network, game_init_players, game_loop_network, game_loop_players, load_action_map,
+    load_atlas,
+    math::vector::Vector2d,
+    net::{
+        lobby::Lobby, lockstep::DEFAULT_INPUT_DELAY, rollback::DEFAULT_ROLLBACK_INPUT_DELAY,
+        NetSession, UdpTransport,
+    },
+    sound::SoundEvent,
+    sprite::{Flip, Sprite},
+    GameInput, Platform,
+};
+use audio::AudioOutput;
 use rand::prelude::*;
 use renderer::simplerenderer::SimpleRenderer;
 use std::{
@@ -37,6 +57,7 @@ use std::{
     time::{Duration, Instant},
 };
 
+mod audio;
 mod renderer;
 
 // We have to encode text to wide format for Windows
@@ -167,19 +188,62 @@ fn handle_messages(window: &mut Window) -> bool {
     }
 }
 
-fn get_input() -> GameInput {
+// Virtual key code for a game key. Letters and digits use their ASCII code.
+fn virtual_key(key: Key) -> i32 {
+    match key {
+        Key::Up => VK_UP,
+        Key::Down => VK_DOWN,
+        Key::Left => VK_LEFT,
+        Key::Right => VK_RIGHT,
+        Key::Space => VK_SPACE,
+        Key::Escape => VK_ESCAPE,
+        Key::Enter => VK_RETURN,
+        Key::Tab => VK_TAB,
+        Key::Shift => VK_SHIFT,
+        Key::Control => VK_CONTROL,
+        _ => key.name().to_uppercase().as_bytes()[0] as i32,
+    }
+}
+
+// Every slot sees the whole keyboard, the action map of the slot decides which keys it uses.
+// Slot n reads controller n, and only the first player gets the mouse.
+fn get_input(window: &Window, slot: usize,) -> GameInput {
     let mut input = GameInput::new();
     unsafe {
-        input.quit_key = GetAsyncKeyState(VK_ESCAPE,) != 0;
-        input.left_key = GetAsyncKeyState(0x41,) != 0;
-        input.right_key = GetAsyncKeyState(0x44,) != 0;
-        input.down_key = GetAsyncKeyState(0x53,) != 0;
-        input.up_key = GetAsyncKeyState(0x57,) != 0;
-        input.space = GetAsyncKeyState(VK_SPACE,) != 0;
-        input.shoot_down = GetAsyncKeyState(0x4B,) != 0;
-        input.shoot_up = GetAsyncKeyState(0x49,) != 0;
-        input.shoot_left = GetAsyncKeyState(0x4A,) != 0;
-        input.shoot_right = GetAsyncKeyState(0x4C,) != 0;
+        // only raw key state is reported, game-core maps keys to actions
+        for key in ALL_KEYS.iter() {
+            input.keys.set(*key, GetAsyncKeyState(virtual_key(*key,),) != 0,);
+        }
+
+        // XInputGetState returns 0 (ERROR_SUCCESS) when the controller is connected
+        let mut state: XINPUT_STATE = mem::zeroed();
+        if XInputGetState(slot as u32, &mut state,) == 0 {
+            let pad = state.Gamepad;
+            input.move_x = pad.sThumbLX as f32 / 32767.0;
+            input.move_y = pad.sThumbLY as f32 / 32767.0;
+            input.aim_x = pad.sThumbRX as f32 / 32767.0;
+            input.aim_y = pad.sThumbRY as f32 / 32767.0;
+        }
+
+        if slot != 0 {
+            return input;
+        }
+
+        // the back buffer is stretched over the client area, so scale the cursor back to
+        // back buffer pixels
+        input.mouse_left = GetAsyncKeyState(VK_LBUTTON,) != 0;
+        input.mouse_right = GetAsyncKeyState(VK_RBUTTON,) != 0;
+        let mut cursor = POINT { x: 0, y: 0, };
+        let mut client: RECT = mem::zeroed();
+        if GetCursorPos(&mut cursor,) != 0
+            && ScreenToClient(window.handle, &mut cursor,) != 0
+            && GetClientRect(window.handle, &mut client,) != 0
+            && client.right > 0
+            && client.bottom > 0
+        {
+            input.mouse_x = cursor.x as f32 * 960.0 / client.right as f32;
+            input.mouse_y = cursor.y as f32 * 540.0 / client.bottom as f32;
+        }
     }
     input
 }
@@ -187,6 +251,39 @@ fn get_input() -> GameInput {
 static mut START_TIME: Option<Instant,> = None;
 static mut LAST_FRAME_START: Option<Instant,> = None;
 static mut RENDERER: Option<SimpleRenderer,> = None;
+static mut AUDIO: Option<AudioOutput,> = None;
+static mut PLAYER_COUNT: usize = 1;
+static mut NETWORKED: bool = false;
+
+fn arg_value(name: &str) -> Option<String,> {
+    let args: Vec<String,> = std::env::args().collect();
+    let i = args.iter().position(|a| a == name,)?;
+    args.get(i + 1,).cloned()
+}
+
+// `win32.exe --players 2`
+fn player_count_from_args() -> usize {
+    arg_value("--players",)
+        .and_then(|n| n.parse().ok(),)
+        .unwrap_or(1,)
+}
+
+// `win32.exe --host 0.0.0.0:7000 --peer 10.0.0.2:7000` on one machine and
+// `win32.exe --join 0.0.0.0:7000 --peer 10.0.0.1:7000` on the other.
+// Add `--rollback` on both to use rollback instead of lockstep.
+fn lobby_from_args() -> Option<Lobby<UdpTransport,>,> {
+    let peer = arg_value("--peer",)?;
+    if let Some(local,) = arg_value("--host",) {
+        let transport = UdpTransport::bind(&local, &peer,).unwrap();
+        let seed = (get_random() as f64 * std::u32::MAX as f64) as u64;
+        return Some(Lobby::host(transport, seed,),);
+    }
+    if let Some(local,) = arg_value("--join",) {
+        let transport = UdpTransport::bind(&local, &peer,).unwrap();
+        return Some(Lobby::join(transport,),);
+    }
+    None
+}
 
 fn get_random() -> f32 {
     let mut rng = thread_rng();
@@ -198,6 +295,18 @@ fn log(s: String) {
     println!("{}", s);
 }
 
+fn now() -> f64 {
+    unsafe {
+        match START_TIME {
+            Some(start,) => {
+                let elapsed = start.elapsed();
+                elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0
+            }
+            None => 0.0,
+        }
+    }
+}
+
 fn start_frame() {
     unsafe {
         match RENDERER {
@@ -231,6 +340,90 @@ fn draw_rectangle(min_x: f32, min_y: f32, max_x: f32, max_y: f32, color: Color,)
     }
 }
 
+fn draw_sprite(
+    sprite: Sprite,
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+    tint: Color,
+    flip: Flip,
+) {
+    unsafe {
+        match RENDERER {
+            Some(ref mut r,) => {
+                r.draw_sprite(sprite, min_x, min_y, max_x, max_y, tint, flip,);
+            }
+            None => panic!("No renderer given"),
+        }
+    }
+}
+
+fn draw_quad(corners: [Vector2d; 4], color: Color,) {
+    unsafe {
+        match RENDERER {
+            Some(ref mut r,) => {
+                r.draw_quad(corners, color,);
+            }
+            None => panic!("No renderer given"),
+        }
+    }
+}
+
+/// Loads `assets/atlas.txt` and its texture when they exist, the game draws boxes without them.
+fn load_sprites() {
+    let metadata_path = "assets/atlas.txt";
+    if !std::path::Path::new(metadata_path,).exists() {
+        return;
+    }
+    let atlas = match load_atlas(0, metadata_path,) {
+        Ok(atlas,) => atlas,
+        Err(e,) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let texture_path = format!("assets/{}", atlas.texture);
+    match rasterizer::image::load_texture(&texture_path,) {
+        Ok(texture,) => unsafe {
+            if let Some(ref mut r,) = RENDERER {
+                r.load_texture(0, texture,);
+            }
+        },
+        Err(e,) => println!("{}", e),
+    }
+}
+
+/// Loads `assets/tunables.cfg` when it exists. Local games reload it when it changes, networked
+/// ones use the defaults.
+fn load_tunables() {
+    let path = "assets/tunables.cfg";
+    if std::path::Path::new(path,).exists() {
+        if let Err(e,) = game_core::tunables::load_tunables(path,) {
+            println!("{}", e);
+        }
+    }
+}
+
+/// Loads `assets/scripts/game.rhai` when it exists. Debug builds reload it when it changes.
+#[cfg(feature = "scripting")]
+fn load_scripts() {
+    let path = "assets/scripts/game.rhai";
+    if std::path::Path::new(path,).exists() {
+        if let Err(e,) = game_core::script::load_scripts(path,) {
+            println!("{}", e);
+        }
+    }
+}
+
+fn play_sound(event: SoundEvent) {
+    unsafe {
+        if let Some(ref mut audio,) = AUDIO {
+            audio.mixer.play_event(&event,);
+        }
+    }
+}
+
 #[cfg(windows)]
 fn main() {
     hide_console_window();
@@ -239,8 +432,19 @@ fn main() {
 
     unsafe {
         RENDERER = Some(renderer::create_simple_renderer(window.handle, 960, 540,),);
+        // hard pixel edges instead of anti-aliased ones
+        if std::env::args().any(|a| a == "--pixel-snap",) {
+            if let Some(ref mut r,) = RENDERER {
+                r.set_edge_mode(rasterizer::EdgeMode::PixelSnapped,);
+            }
+        }
         START_TIME = Some(Instant::now(),);
         LAST_FRAME_START = Some(Instant::now(),);
+        PLAYER_COUNT = player_count_from_args();
+        AUDIO = AudioOutput::open();
+        if let None = AUDIO {
+            println!("No sound device, playing without sound");
+        }
     }
 
     let platform = Platform {
@@ -249,15 +453,81 @@ fn main() {
         start_frame,
         end_frame,
         draw_rectangle,
+        play_sound,
+        draw_sprite,
+        draw_quad,
+        now,
     };
-    game_init(960.0, 540.0, platform,);
+    load_sprites();
+    load_tunables();
+    #[cfg(feature = "scripting")]
+    load_scripts();
+    if let Some(mut lobby,) = lobby_from_args() {
+        println!("Waiting for peer");
+        while !lobby.poll() {
+            if handle_messages(&mut window,) || lobby.version_mismatch() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(15,),);
+        }
+        let session = if std::env::args().any(|a| a == "--rollback",) {
+            NetSession::Rollback(lobby.into_rollback(DEFAULT_ROLLBACK_INPUT_DELAY,).unwrap(),)
+        } else {
+            NetSession::Lockstep(lobby.into_lockstep(DEFAULT_INPUT_DELAY,).unwrap(),)
+        };
+        game_init_network(960.0, 540.0, session, platform,);
+        unsafe {
+            NETWORKED = true;
+        }
+        if std::path::Path::new("bindings.cfg",).exists() {
+            if let Err(e,) = load_action_map(0, "bindings.cfg",) {
+                println!("{}", e);
+            }
+        }
+        loop {
+            if main_loop(&mut window,) {
+                break;
+            }
+        }
+        write_profile();
+        return;
+    }
+
+    unsafe {
+        game_init_players(960.0, 540.0, PLAYER_COUNT, platform,);
+        // bindings.cfg for the first player, bindings2.cfg for the second and so on
+        for slot in 0..PLAYER_COUNT {
+            let path = match slot {
+                0 => String::from("bindings.cfg",),
+                _ => format!("bindings{}.cfg", slot + 1),
+            };
+            if std::path::Path::new(&path,).exists() {
+                if let Err(e,) = load_action_map(slot, &path,) {
+                    println!("{}", e);
+                }
+            }
+        }
+    }
     loop {
         if main_loop(&mut window,) {
             break;
         }
     }
+    write_profile();
+}
+
+/// Writes the timings of the run to trace.json, open it in chrome://tracing or Perfetto.
+#[cfg(feature = "profiling")]
+fn write_profile() {
+    match game_core::profile::write_chrome_trace("trace.json",) {
+        Ok((),) => println!("Wrote trace.json"),
+        Err(e,) => println!("Could not write trace.json: {}", e),
+    }
 }
 
+#[cfg(not(feature = "profiling"))]
+fn write_profile() {}
+
 fn main_loop(window: &mut Window) -> bool {
     if handle_messages(window,) {
         return true;
@@ -273,11 +543,24 @@ fn main_loop(window: &mut Window) -> bool {
         let mut time_elapsed = total_time.as_secs() as f32;
         time_elapsed += total_time.subsec_micros() as f32 / (1000.0 * 1000.0);
 
-        let input = get_input();
-        let game_output = game_loop(input, time_elapsed, delta,);
-        if game_output {
+        let game_output = if NETWORKED {
+            // the simulation runs at a fixed step, time and delta are not used
+            game_loop_network(get_input(window, 0,),)
+        } else {
+            let inputs = (0..PLAYER_COUNT).map(|slot| get_input(window, slot,),).collect();
+            game_loop_players(inputs, time_elapsed, delta,)
+        };
+        if game_output.should_quit() {
             return true;
         }
+        // network games can not be restarted, both peers see the game end on the same frame
+        if NETWORKED && game_output == GameStatus::GameOver {
+            println!("Game over");
+            return true;
+        }
+        if let Some(ref mut audio,) = AUDIO {
+            audio.update();
+        }
 
         let frame_time = LAST_FRAME_START.unwrap().elapsed();
         //        println!("Frame time {:?}", frame_time.subsec_millis());
@@ -1,25 +1,25 @@
-use game_core::entities::Color;
+use game_core::{
+    entities::{BoundingBox, Color},
+    math::vector::Vector2d,
+    sprite::{Flip, Sprite},
+};
 use libc;
-use std::{self, mem};
+use rasterizer::{Canvas, EdgeMode, Texture};
+use std::mem;
 use winapi::{
     shared::{
-        minwindef::LPVOID,
+        ctypes::c_void,
         windef::{HDC, HWND, LPRECT, RECT},
     },
     um::{
-        memoryapi::{VirtualAlloc, VirtualFree},
         wingdi::{StretchDIBits, BITMAPINFO, BITMAPINFOHEADER, DIB_RGB_COLORS, RGBQUAD, SRCCOPY},
-        winnt::{MEM_COMMIT, MEM_RELEASE, PAGE_READWRITE},
         winuser::{GetClientRect, GetDC},
     },
 };
 
 struct OffscreenBuffer {
     info: BITMAPINFO,
-    memory: LPVOID,
-    width: i32,
-    height: i32,
-    pitch: i32,
+    canvas: Canvas,
 }
 
 pub struct SimpleRenderer {
@@ -27,6 +27,8 @@ pub struct SimpleRenderer {
     window_width: i32,
     window_height: i32,
     hdc: HDC,
+    /// Indexed by atlas id.
+    textures: Vec<Option<Texture,>,>,
 }
 
 pub fn create_simple_renderer(
@@ -43,13 +45,7 @@ pub fn create_simple_renderer(
             let client_width = (*lp_rect).right;
             let client_height = (*lp_rect).bottom;
 
-            let bytes_per_pixel = 4;
-            let bitmap_memory_size = back_buffer_width * back_buffer_height * bytes_per_pixel;
             let buffer = OffscreenBuffer {
-                width: back_buffer_width,
-                height: back_buffer_height,
-                pitch: back_buffer_width,
-
                 info: BITMAPINFO {
                     bmiHeader: BITMAPINFOHEADER {
                         biSize: mem::size_of::<BITMAPINFOHEADER,>() as u32,
@@ -71,12 +67,7 @@ pub fn create_simple_renderer(
                         rgbReserved: 0,
                     }; 1],
                 },
-                memory: VirtualAlloc(
-                    std::ptr::null_mut(),
-                    bitmap_memory_size as usize,
-                    MEM_COMMIT,
-                    PAGE_READWRITE,
-                ),
+                canvas: Canvas::new(back_buffer_width, back_buffer_height,),
             };
 
             return SimpleRenderer {
@@ -84,82 +75,64 @@ pub fn create_simple_renderer(
                 window_width: client_width,
                 window_height: client_height,
                 hdc: dc,
+                textures: Vec::new(),
             };
         }
     }
 }
 
 impl SimpleRenderer {
-    pub fn draw_rectangle(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32, color: Color,) {
-        let mut start_x = min_x as i32;
-        if start_x < 0 {
-            start_x = 0;
-        }
-        let mut start_y = min_y as i32;
-        if start_y < 0 {
-            start_y = 0;
-        }
-        let mut end_x = max_x as i32;
-        if end_x > self.back_buffer.width {
-            end_x = self.back_buffer.width;
-        }
-        let mut end_y = max_y as i32;
-        if end_y > self.back_buffer.height {
-            end_y = self.back_buffer.height;
-        }
+    pub fn draw_rectangle(
+        &mut self,
+        min_x: f32,
+        min_y: f32,
+        max_x: f32,
+        max_y: f32,
+        color: Color,
+    ) {
+        self.back_buffer.canvas.draw_rectangle(min_x, min_y, max_x, max_y, color,);
+    }
 
-        let mut c: u32 = 0;
-        let alpha: u32 = ((color.a * 255.0) as u8) as u32;
-        let red: u32 = ((color.r * 255.0) as u8) as u32;
-        let green: u32 = ((color.g * 255.0) as u8) as u32;
-        let blue: u32 = ((color.b * 255.0) as u8) as u32;
+    pub fn draw_quad(&mut self, corners: [Vector2d; 4], color: Color,) {
+        self.back_buffer.canvas.fill_convex(&corners, color,);
+    }
 
-        c = c | alpha << 24;
-        c = c | red << 16;
-        c = c | green << 8;
-        c = c | blue << 0;
+    pub fn set_edge_mode(&mut self, edge_mode: EdgeMode,) {
+        self.back_buffer.canvas.set_edge_mode(edge_mode,);
+    }
 
-        unsafe {
-            let start_of_memory = self.back_buffer.memory as *mut u32;
-            for y in start_y..end_y {
-                let mut offset: isize = self.back_buffer.pitch as isize * y as isize;
-                offset += start_x as isize;
-                for _x in start_x..end_x {
-                    let mut pixel = start_of_memory.offset(offset,);
-                    *pixel = c;
-                    offset += 1;
-                }
-            }
+    pub fn load_texture(&mut self, atlas: u32, texture: Texture,) {
+        let index = atlas as usize;
+        while self.textures.len() <= index {
+            self.textures.push(None,);
         }
+        self.textures[index] = Some(texture,);
     }
 
-    fn _render_gradient(&self, x_offset: i32, y_offset: i32,) {
-        unsafe {
-            let start_of_memory = self.back_buffer.memory as *mut u32;
-            let mut offset = 0;
-            for y in 0..self.back_buffer.height {
-                for x in 0..self.back_buffer.width {
-                    offset += 1;
-                    let blue: u32 = ((x + x_offset) as u8).into();
-                    let green: u32 = ((y + y_offset) as u8).into();
-                    let mut pixel = start_of_memory.offset(offset,);
-                    *pixel = green << 8 | blue;
-                }
-            }
+    /// Sprites from atlases that were never loaded are not drawn.
+    pub fn draw_sprite(
+        &mut self,
+        sprite: Sprite,
+        min_x: f32,
+        min_y: f32,
+        max_x: f32,
+        max_y: f32,
+        tint: Color,
+        flip: Flip,
+    ) {
+        if let Some(Some(ref texture,),) = self.textures.get(sprite.atlas as usize,) {
+            let dest = BoundingBox {
+                left: min_x,
+                right: max_x,
+                top: max_y,
+                bottom: min_y,
+            };
+            self.back_buffer.canvas.draw_sprite(texture, sprite.source, &dest, tint, flip,);
         }
     }
 
     pub fn clear_screen(&mut self) {
-        unsafe {
-            VirtualFree(self.back_buffer.memory, 0, MEM_RELEASE,);
-            let bitmap_memory_size = self.back_buffer.width * self.back_buffer.height * 4;
-            self.back_buffer.memory = VirtualAlloc(
-                std::ptr::null_mut(),
-                bitmap_memory_size as usize,
-                MEM_COMMIT,
-                PAGE_READWRITE,
-            )
-        }
+        self.back_buffer.canvas.clear(0xff00_0000,);
     }
 
     pub fn end_frame(&self) {
@@ -172,9 +145,9 @@ impl SimpleRenderer {
                 self.window_height,
                 0,
                 0,
-                self.back_buffer.width,
-                self.back_buffer.height,
-                self.back_buffer.memory,
+                self.back_buffer.canvas.width(),
+                self.back_buffer.canvas.height(),
+                self.back_buffer.canvas.pixels().as_ptr() as *const c_void,
                 &self.back_buffer.info,
                 DIB_RGB_COLORS,
                 SRCCOPY,